@@ -0,0 +1,198 @@
+//! GwenOS 堆分配器模块
+//!
+//! 在 `memory::map_heap` 映射好的虚拟地址范围上实现全局分配器，
+//! 让内核可以使用 `alloc` crate（Box、Vec、String 等）
+//!
+//! 目前是最简单的线性（bump）分配器：
+//! 分配时只把指针往后推，释放时只减少计数，所有分配都释放后才整体回收
+//!
+//! 堆空间不足时分配返回空指针，`alloc` crate 随后调用这里注册的 `alloc_error_handler`
+
+use crate::memory;
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+use spin::{Mutex, MutexGuard};
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::{FrameAllocator, Size4KiB};
+
+// =============================================================================
+// 堆的位置和大小
+// =============================================================================
+
+/// 堆的起始虚拟地址（远离内核和 bootloader 使用的区域，便于识别）
+pub const HEAP_START: usize = 0x_4444_4444_0000;
+
+/// 堆的大小（100 KiB）
+pub const HEAP_SIZE: usize = 100 * 1024;
+
+// =============================================================================
+// 线性分配器
+// =============================================================================
+
+/// 线性（bump）分配器
+pub struct BumpAllocator {
+    heap_start: usize,
+    heap_end: usize,
+    /// 下一次分配的起始地址
+    next: usize,
+    /// 尚未释放的分配数量
+    allocations: usize,
+}
+
+impl Default for BumpAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BumpAllocator {
+    /// 创建一个空的分配器，使用前必须调用 `init`
+    pub const fn new() -> Self {
+        BumpAllocator {
+            heap_start: 0,
+            heap_end: 0,
+            next: 0,
+            allocations: 0,
+        }
+    }
+
+    /// 设置分配器管理的内存范围
+    ///
+    /// # Safety
+    /// `heap_start..heap_start + heap_size` 必须已经映射、可写且没有被其他代码使用，
+    /// 并且只能调用一次
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_start = heap_start;
+        self.heap_end = heap_start + heap_size;
+        self.next = heap_start;
+    }
+}
+
+/// spin::Mutex 包装，用于为外部类型实现 GlobalAlloc
+/// （不能直接为 `spin::Mutex<BumpAllocator>` 实现外部 trait）
+pub struct Locked<A> {
+    inner: Mutex<A>,
+}
+
+impl<A> Locked<A> {
+    pub const fn new(inner: A) -> Self {
+        Locked {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, A> {
+        self.inner.lock()
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<BumpAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut bump = self.lock();
+
+        let alloc_start = bump.next.next_multiple_of(layout.align());
+        let alloc_end = match alloc_start.checked_add(layout.size()) {
+            Some(end) => end,
+            None => return ptr::null_mut(),
+        };
+
+        if alloc_end > bump.heap_end {
+            // 堆空间不足
+            ptr::null_mut()
+        } else {
+            bump.next = alloc_end;
+            bump.allocations += 1;
+            alloc_start as *mut u8
+        }
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+        let mut bump = self.lock();
+
+        bump.allocations -= 1;
+        // 所有分配都已释放，整个堆可以重新使用
+        if bump.allocations == 0 {
+            bump.next = bump.heap_start;
+        }
+    }
+}
+
+/// 全局分配器实例
+#[global_allocator]
+static ALLOCATOR: Locked<BumpAllocator> = Locked::new(BumpAllocator::new());
+
+/// 堆耗尽（分配返回空指针）时由 `alloc` crate 调用
+#[alloc_error_handler]
+fn alloc_error_handler(layout: Layout) -> ! {
+    crate::panic::out_of_memory(layout)
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 堆的使用情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapUsage {
+    /// 已经分配出去的字节数（线性分配器在全部释放之前不会回收）
+    pub used: usize,
+    /// 堆的总大小
+    pub size: usize,
+    /// 尚未释放的分配数量
+    pub allocations: usize,
+}
+
+/// 当前的堆使用情况
+///
+/// 堆还没有初始化，或者分配器正被占用（例如在分配过程中 panic）时返回 None，不会等待
+pub fn usage() -> Option<HeapUsage> {
+    let bump = ALLOCATOR.inner.try_lock()?;
+    if bump.heap_end == 0 {
+        return None;
+    }
+    Some(HeapUsage {
+        used: bump.next - bump.heap_start,
+        size: bump.heap_end - bump.heap_start,
+        allocations: bump.allocations,
+    })
+}
+
+/// 映射堆的虚拟地址范围并初始化全局分配器
+///
+/// 需要在 `memory::init_paging()` 之后调用
+///
+/// # 参数
+/// - `frame_allocator`: 为堆提供物理页帧
+pub fn init_heap(
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), MapToError<Size4KiB>> {
+    memory::map_heap(frame_allocator)?;
+
+    unsafe {
+        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+    }
+    Ok(())
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn heap_range_is_mapped_and_writable() {
+        let heap = HEAP_START as *mut u8;
+
+        // 每个字节写入和地址相关的值，相邻页面的内容各不相同
+        for offset in 0..HEAP_SIZE {
+            unsafe { ptr::write_volatile(heap.add(offset), (offset % 251) as u8) };
+        }
+        for offset in 0..HEAP_SIZE {
+            let value = unsafe { ptr::read_volatile(heap.add(offset)) };
+            assert_eq!(value, (offset % 251) as u8);
+        }
+    }
+}
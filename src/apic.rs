@@ -0,0 +1,171 @@
+//! GwenOS 本地 APIC 模块
+//!
+//! 本地 APIC 是每个 CPU 核心自带的中断控制器，内置一个定时器，
+//! 精度比 PIT 高，而且每个核心各有一个，以后做多核调度时可以直接使用
+//!
+//! # 工作方式
+//! - 寄存器通过 MMIO 访问，物理基址在 `IA32_APIC_BASE` MSR 中（通常是 0xFEE00000）
+//! - 伪中断向量寄存器（SVR）的位 8 是软件使能位
+//! - 定时器从初始计数开始递减，到 0 时触发中断，周期模式下自动重新装载
+//!
+//! APIC 定时器启动后 PIT 被屏蔽，tick 由 APIC 定时器推进；
+//! 键盘、串口等设备中断仍然由 8259 PIC 经 LINT0（ExtINT）送进来
+
+use crate::memory;
+use crate::{cpu, cpuid, pic, serial_println, timer};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::PhysAddr;
+use x86_64::structures::paging::{FrameAllocator, PageTableFlags, Size4KiB};
+
+// =============================================================================
+// 常量定义
+// =============================================================================
+
+/// APIC 定时器中断的向量号（紧接在两片 PIC 的 16 个向量之后）
+pub const TIMER_VECTOR: u8 = pic::PIC_2_OFFSET + 8;
+
+/// APIC 伪中断的向量号（低 4 位在老式 APIC 上必须全为 1）
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+/// `IA32_APIC_BASE` MSR
+const IA32_APIC_BASE_MSR: u32 = 0x1B;
+/// MSR 中的全局使能位
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+/// MSR 中基址所在的位（12~51）
+const APIC_BASE_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+// 寄存器偏移
+const REG_TPR: usize = 0x080; // 任务优先级
+const REG_EOI: usize = 0x0B0; // 中断结束
+const REG_SVR: usize = 0x0F0; // 伪中断向量
+const REG_LVT_TIMER: usize = 0x320; // 本地向量表：定时器
+const REG_LVT_LINT0: usize = 0x350; // 本地向量表：LINT0
+const REG_LVT_LINT1: usize = 0x360; // 本地向量表：LINT1
+const REG_TIMER_INITIAL: usize = 0x380; // 定时器初始计数
+const REG_TIMER_CURRENT: usize = 0x390; // 定时器当前计数
+const REG_TIMER_DIVIDE: usize = 0x3E0; // 定时器分频
+
+// 寄存器取值
+const SVR_ENABLE: u32 = 1 << 8; // 软件使能
+const LVT_MASKED: u32 = 1 << 16; // 屏蔽该中断
+const LVT_TIMER_PERIODIC: u32 = 1 << 17; // 周期模式
+const LVT_DELIVERY_EXTINT: u32 = 0b111 << 8; // ExtINT：转发 8259 PIC 的中断
+const LVT_DELIVERY_NMI: u32 = 0b100 << 8; // NMI
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+
+/// 校准 APIC 定时器时测量的毫秒数
+const CALIBRATION_MS: u64 = 10;
+
+// =============================================================================
+// 状态
+// =============================================================================
+
+/// APIC 寄存器的基址（恒等映射，所以也是虚拟地址），0 表示还没有映射
+static APIC_BASE: AtomicU64 = AtomicU64::new(0);
+
+/// APIC 定时器是否已经代替 PIT 产生时钟中断
+static TIMER_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// APIC 初始化错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApicError {
+    /// CPUID 报告 CPU 没有本地 APIC
+    NotPresent,
+    /// TSC 还没有校准，无法换算 APIC 定时器的频率
+    TscNotCalibrated,
+    /// 映射 APIC 寄存器页失败
+    MapFailed,
+}
+
+// =============================================================================
+// 寄存器访问
+// =============================================================================
+
+/// 读取一个 APIC 寄存器
+fn read(reg: usize) -> u32 {
+    let base = APIC_BASE.load(Ordering::Relaxed) as usize;
+    unsafe { ((base + reg) as *const u32).read_volatile() }
+}
+
+/// 写入一个 APIC 寄存器
+fn write(reg: usize, value: u32) {
+    let base = APIC_BASE.load(Ordering::Relaxed) as usize;
+    unsafe { ((base + reg) as *mut u32).write_volatile(value) };
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 启用本地 APIC，并用 APIC 定时器代替 PIT 产生时钟中断
+///
+/// 需要在 `cpu::calibrate_tsc()` 和 `memory::init_paging()` 之后、开启中断的情况下调用；
+/// 失败时不做任何改动，PIT 继续工作；APIC 定时器已经启动时只输出警告
+///
+/// # 步骤
+/// 1. 通过 CPUID 确认 CPU 有本地 APIC
+/// 2. 从 `IA32_APIC_BASE` MSR 读出寄存器基址并恒等映射
+/// 3. 设置 SVR 软件使能，LINT0 设为 ExtINT 让 PIC 的设备中断继续送达
+/// 4. 用 TSC 测量 APIC 定时器的频率，算出每个 tick 的初始计数
+/// 5. 屏蔽 PIT，以 `TICK_HZ` 启动周期模式的 APIC 定时器
+///
+/// # 参数
+/// - `frame_allocator`: 映射寄存器页时新建页表所需的页帧
+pub fn init(frame_allocator: &mut impl FrameAllocator<Size4KiB>) -> Result<(), ApicError> {
+    if timer_active() {
+        serial_println!("[WARN] apic::init called twice, ignored");
+        return Ok(());
+    }
+    if !cpuid::has_feature(cpuid::Feature::Apic) {
+        return Err(ApicError::NotPresent);
+    }
+    if cpu::tsc_khz().is_none() {
+        return Err(ApicError::TscNotCalibrated);
+    }
+
+    // 1. 映射寄存器页并打开全局使能
+    let msr = unsafe { cpu::rdmsr(IA32_APIC_BASE_MSR) };
+    let base = msr & APIC_BASE_MASK;
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE;
+    memory::identity_map(PhysAddr::new(base), flags, frame_allocator)
+        .map_err(|_| ApicError::MapFailed)?;
+    unsafe { cpu::wrmsr(IA32_APIC_BASE_MSR, msr | APIC_BASE_ENABLE) };
+    APIC_BASE.store(base, Ordering::Relaxed);
+
+    // 2. 软件使能，接收所有优先级的中断，PIC 经 LINT0 转发，LINT1 接 NMI
+    write(REG_SVR, SVR_ENABLE | u32::from(SPURIOUS_VECTOR));
+    write(REG_TPR, 0);
+    write(REG_LVT_LINT0, LVT_DELIVERY_EXTINT);
+    write(REG_LVT_LINT1, LVT_DELIVERY_NMI);
+
+    // 3. 校准：屏蔽定时器中断，从最大值倒数一段固定时间
+    write(REG_TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+    write(REG_LVT_TIMER, LVT_MASKED | u32::from(TIMER_VECTOR));
+    write(REG_TIMER_INITIAL, u32::MAX);
+    cpu::delay_us(CALIBRATION_MS * 1000);
+    let elapsed = u64::from(u32::MAX - read(REG_TIMER_CURRENT));
+    write(REG_TIMER_INITIAL, 0);
+
+    let per_tick = (elapsed * timer::MS_PER_TICK / CALIBRATION_MS).clamp(1, u64::from(u32::MAX));
+
+    // 4. 切换时钟源：关中断期间屏蔽 PIT 并启动 APIC 定时器，tick 不会重复也不会中断
+    cpu::without_interrupts(|| {
+        pic::mask(timer::TIMER_IRQ);
+        write(REG_LVT_TIMER, LVT_TIMER_PERIODIC | u32::from(TIMER_VECTOR));
+        write(REG_TIMER_INITIAL, per_tick as u32);
+        TIMER_ACTIVE.store(true, Ordering::Relaxed);
+    });
+    Ok(())
+}
+
+/// APIC 定时器是否正在产生时钟中断（否则由 PIT 产生）
+pub fn timer_active() -> bool {
+    TIMER_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// 向本地 APIC 发送中断结束（EOI）信号
+///
+/// 只用于 APIC 自己产生的中断（定时器）；经 PIC 转发的中断仍然发给 PIC
+pub fn end_of_interrupt() {
+    write(REG_EOI, 0);
+}
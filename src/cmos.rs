@@ -0,0 +1,305 @@
+//! GwenOS CMOS 实时时钟（RTC）模块
+//!
+//! 通过 CMOS 的 0x70（索引）/0x71（数据）端口读取墙上时间
+//!
+//! # 读取 RTC 的注意事项
+//! - RTC 每秒更新一次，更新期间读出的值可能不一致，
+//!   所以要等待"正在更新"标志清零，并连续读两次直到结果相同
+//! - 数值可能是 BCD 编码（由状态寄存器 B 决定）
+//! - 选择索引寄存器时，端口 0x70 的最高位控制 NMI，置 1 表示禁用。访问期间禁用 NMI，
+//!   结束后恢复原来的状态；端口 0x70 是只写的，原来的状态记录在 `NMI_MASKED` 中
+//!
+//! # 周期中断（IRQ8）
+//! RTC 可以按 2 的幂次频率（2 Hz ~ 8192 Hz）产生周期中断，比 PIT 更稳定。
+//! 每次中断后必须读取状态寄存器 C 确认，否则 RTC 不会再产生下一个中断
+
+use crate::port::{inb, outb};
+use crate::{cpu, pic};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// =============================================================================
+// CMOS 端口与寄存器定义
+// =============================================================================
+
+const CMOS_INDEX: u16 = 0x70; // 索引端口（位 7 = 禁用 NMI）
+const CMOS_DATA: u16 = 0x71; // 数据端口
+
+/// 选择寄存器时同时禁用 NMI
+const NMI_DISABLE: u8 = 0x80;
+
+// RTC 寄存器索引
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A; // 位 7：正在更新
+const REG_STATUS_B: u8 = 0x0B; // 位 1：24 小时制，位 2：二进制模式，位 6：周期中断
+const REG_STATUS_C: u8 = 0x0C; // 中断标志，读取后清零
+const REG_STATUS_D: u8 = 0x0D; // 访问结束后索引停在这里（只读，选中它没有副作用）
+
+// 状态寄存器位
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+const STATUS_A_RATE_MASK: u8 = 0x0F; // 位 0~3：周期中断的分频值
+const STATUS_B_24_HOUR: u8 = 0x02;
+const STATUS_B_BINARY: u8 = 0x04;
+const STATUS_B_PERIODIC: u8 = 0x40;
+const STATUS_C_PERIODIC: u8 = 0x40; // 本次中断来自周期中断
+
+/// RTC 使用的 IRQ 线（在从 PIC 上）
+pub const RTC_IRQ: u8 = 8;
+
+/// 分频值的有效范围：1、2 在 32.768 kHz 晶振下无法正常工作，0 表示关闭
+const RATE_MIN: u8 = 3;
+const RATE_MAX: u8 = 15;
+
+/// 1024 Hz 对应的分频值（也是 RTC 上电时的默认值）
+pub const RATE_1024_HZ: u8 = 6;
+
+/// 12 小时制下，小时寄存器的最高位表示下午
+const HOUR_PM: u8 = 0x80;
+
+// =============================================================================
+// 日期时间结构
+// =============================================================================
+
+/// 从 RTC 读出的日期时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    /// 年（完整四位，如 2025）
+    pub year: u16,
+    /// 月（1-12）
+    pub month: u8,
+    /// 日（1-31）
+    pub day: u8,
+    /// 时（0-23）
+    pub hour: u8,
+    /// 分（0-59）
+    pub minute: u8,
+    /// 秒（0-59）
+    pub second: u8,
+}
+
+/// 以 `YYYY-MM-DD HH:MM:SS` 格式显示
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// =============================================================================
+// 寄存器读取
+// =============================================================================
+
+/// NMI 是否被屏蔽（端口 0x70 的位 7）；端口只写，无法读回，所以在这里记录
+static NMI_MASKED: AtomicBool = AtomicBool::new(false);
+
+/// 屏蔽或重新允许 NMI
+#[allow(dead_code)]
+pub fn set_nmi_masked(masked: bool) {
+    NMI_MASKED.store(masked, Ordering::Relaxed);
+    restore_nmi();
+}
+
+/// 按 `NMI_MASKED` 恢复 NMI 状态，索引停在状态寄存器 D 上
+fn restore_nmi() {
+    let nmi = if NMI_MASKED.load(Ordering::Relaxed) {
+        NMI_DISABLE
+    } else {
+        0
+    };
+    outb(CMOS_INDEX, nmi | REG_STATUS_D);
+}
+
+/// 读取一个 CMOS 寄存器（访问期间禁用 NMI，结束后恢复）
+///
+/// 选择索引和读取数据之间关中断：IRQ8 处理函数会选中状态寄存器 C，
+/// 如果在两次端口访问之间被打断，这里读到的就是寄存器 C
+fn read_register(reg: u8) -> u8 {
+    cpu::without_interrupts(|| {
+        outb(CMOS_INDEX, NMI_DISABLE | reg);
+        let value = inb(CMOS_DATA);
+        restore_nmi();
+        value
+    })
+}
+
+/// 写入一个 CMOS 寄存器（访问期间禁用 NMI，结束后恢复）
+///
+/// 和 `read_register` 一样，索引和数据之间关中断
+fn write_register(reg: u8, value: u8) {
+    cpu::without_interrupts(|| {
+        outb(CMOS_INDEX, NMI_DISABLE | reg);
+        outb(CMOS_DATA, value);
+        restore_nmi();
+    });
+}
+
+/// RTC 是否正在更新
+fn update_in_progress() -> bool {
+    read_register(REG_STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS != 0
+}
+
+/// 读取一次原始时间寄存器（未做 BCD 转换）
+fn read_raw() -> [u8; 6] {
+    // 等待更新结束
+    while update_in_progress() {
+        cpu::pause();
+    }
+
+    [
+        read_register(REG_SECONDS),
+        read_register(REG_MINUTES),
+        read_register(REG_HOURS),
+        read_register(REG_DAY),
+        read_register(REG_MONTH),
+        read_register(REG_YEAR),
+    ]
+}
+
+/// BCD 编码转二进制（如 0x59 → 59）
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0F) + (value >> 4) * 10
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 读取当前墙上时间
+///
+/// 连续读取直到两次结果相同，避免读到更新中途的值；读取寄存器期间关中断，
+/// 不让 IRQ8 处理函数在中途改变 CMOS 索引
+/// 年份寄存器只有两位，这里按 2000 年之后处理
+pub fn read_time() -> DateTime {
+    let (raw, status_b) = cpu::without_interrupts(|| {
+        let mut raw = read_raw();
+        loop {
+            let again = read_raw();
+            if again == raw {
+                break;
+            }
+            raw = again;
+        }
+        (raw, read_register(REG_STATUS_B))
+    });
+    let [
+        mut second,
+        mut minute,
+        mut hour,
+        mut day,
+        mut month,
+        mut year,
+    ] = raw;
+
+    // 12 小时制下先取出 PM 标志，它不属于 BCD 数值
+    let pm = status_b & STATUS_B_24_HOUR == 0 && hour & HOUR_PM != 0;
+    hour &= !HOUR_PM;
+
+    // BCD 模式下转换为二进制
+    if status_b & STATUS_B_BINARY == 0 {
+        second = bcd_to_binary(second);
+        minute = bcd_to_binary(minute);
+        hour = bcd_to_binary(hour);
+        day = bcd_to_binary(day);
+        month = bcd_to_binary(month);
+        year = bcd_to_binary(year);
+    }
+
+    // 12 小时制转 24 小时制（12AM → 0，12PM → 12）
+    if status_b & STATUS_B_24_HOUR == 0 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    DateTime {
+        year: 2000 + year as u16,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    }
+}
+
+// =============================================================================
+// 周期中断
+// =============================================================================
+
+/// 启动以来 RTC 周期中断的次数
+static PERIODIC_TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// 分频值对应的中断频率（Hz）：`32768 >> (rate - 1)`
+///
+/// 0 表示关闭周期中断，返回 None；1、2 在 32.768 kHz 晶振下分别给出 256 Hz 和 128 Hz
+/// （和 8、9 相同）；分频值只有 4 位，超过 15 时返回 None
+pub const fn periodic_frequency_hz(rate: u8) -> Option<u32> {
+    match rate {
+        1 => Some(256),
+        2 => Some(128),
+        RATE_MIN..=RATE_MAX => Some(32768 >> (rate - 1)),
+        _ => None,
+    }
+}
+
+/// 打开 RTC 周期中断，并在 PIC 上打开 IRQ8
+///
+/// 设置状态寄存器 A 的分频值和状态寄存器 B 的位 6；修改期间关中断，
+/// 避免被打断的代码在两次端口访问之间选中别的寄存器。需要在 `pic::init()` 之后调用
+///
+/// # 参数
+/// - `rate`: 分频值（3~15，超出范围时限制在边界内），频率为 `32768 >> (rate - 1)` Hz，
+///   例如 `RATE_1024_HZ`
+pub fn enable_periodic_interrupt(rate: u8) {
+    let rate = rate.clamp(RATE_MIN, RATE_MAX);
+    cpu::without_interrupts(|| {
+        let status_a = read_register(REG_STATUS_A);
+        write_register(REG_STATUS_A, (status_a & !STATUS_A_RATE_MASK) | rate);
+        let status_b = read_register(REG_STATUS_B);
+        write_register(REG_STATUS_B, status_b | STATUS_B_PERIODIC);
+        // 清除打开之前可能已经挂起的中断标志，否则第一个中断不会到来
+        read_register(REG_STATUS_C);
+    });
+    pic::unmask(RTC_IRQ);
+}
+
+/// RTC 中断（IRQ8）调用：读取状态寄存器 C 确认中断，周期中断计数加一
+///
+/// 不读取寄存器 C 的话 RTC 不会再产生中断
+pub fn handle_interrupt() {
+    if read_register(REG_STATUS_C) & STATUS_C_PERIODIC != 0 {
+        PERIODIC_TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 启动以来 RTC 周期中断的次数
+pub fn periodic_ticks() -> u64 {
+    PERIODIC_TICKS.load(Ordering::Relaxed)
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn periodic_frequency_handles_every_rate() {
+        assert_eq!(periodic_frequency_hz(0), None);
+        assert_eq!(periodic_frequency_hz(1), Some(256));
+        assert_eq!(periodic_frequency_hz(2), Some(128));
+        assert_eq!(periodic_frequency_hz(RATE_1024_HZ), Some(1024));
+        assert_eq!(periodic_frequency_hz(15), Some(2));
+        assert_eq!(periodic_frequency_hz(16), None);
+    }
+}
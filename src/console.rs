@@ -0,0 +1,60 @@
+//! GwenOS 控制台模块
+//!
+//! 提供同时输出到串口和 VGA 屏幕的 `TeeWriter`，
+//! 以及对应的 `tee_print!`/`tee_println!` 宏
+//!
+//! # 加锁顺序
+//! 需要同时持有两个输出的锁时，一律先锁 `serial::SERIAL1`、再锁 `vga::WRITER`，
+//! 并且在关中断的情况下加锁，避免和中断处理函数或其他代码互相等待
+
+use crate::serial::SerialWriter;
+use crate::{cpu, serial, vga};
+use core::fmt;
+
+/// 把写入的内容同时转发给串口和 VGA 的写入器
+///
+/// 持有两个输出的可变引用，由调用者负责按照模块文档中的顺序加锁
+pub struct TeeWriter<'a> {
+    serial: &'a mut SerialWriter,
+    vga: &'a mut vga::Writer,
+}
+
+impl<'a> TeeWriter<'a> {
+    /// 由已经加锁的串口和 VGA 写入器创建
+    pub fn new(serial: &'a mut SerialWriter, vga: &'a mut vga::Writer) -> Self {
+        TeeWriter { serial, vga }
+    }
+}
+
+impl fmt::Write for TeeWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.serial.write_str(s)?;
+        self.vga.write_str(s)
+    }
+}
+
+/// 用于 tee_print! 宏的内部打印函数
+///
+/// 关中断后按固定顺序（串口、VGA）加锁，整条消息在两个输出上都不会被其他输出打断
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    cpu::without_interrupts(|| {
+        let mut serial = serial::SERIAL1.lock();
+        let mut vga = vga::WRITER.lock();
+        let _ = TeeWriter::new(&mut serial, &mut vga).write_fmt(args);
+    });
+}
+
+/// 同时向串口和 VGA 屏幕打印格式化文本
+#[macro_export]
+macro_rules! tee_print {
+    ($($arg:tt)*) => ($crate::console::_print(format_args!($($arg)*)));
+}
+
+/// 同时向串口和 VGA 屏幕打印格式化文本并换行
+#[macro_export]
+macro_rules! tee_println {
+    () => ($crate::tee_print!("\n"));
+    ($($arg:tt)*) => ($crate::tee_print!("{}\n", format_args!($($arg)*)));
+}
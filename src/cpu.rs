@@ -0,0 +1,265 @@
+//! GwenOS CPU 控制模块
+//!
+//! 封装停机、中断开关和时间戳计数器（TSC）等常用 CPU 指令，
+//! 调用者不需要直接使用 `x86_64::instructions` 或内联汇编
+
+use crate::timer;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+// =============================================================================
+// 停机
+// =============================================================================
+
+/// 执行 x86_64 的 HLT 指令
+///
+/// HLT 指令让 CPU 暂停执行，直到下一个中断
+/// 这比空循环更节能
+#[inline(always)]
+pub fn hlt() {
+    // 使用内联汇编执行 hlt 指令
+    unsafe {
+        core::arch::asm!("hlt", options(nomem, nostack));
+    }
+}
+
+/// 执行 PAUSE 指令，用在忙等循环的每一轮里
+///
+/// 提示 CPU 当前在自旋等待：降低功耗，也避免退出循环时因内存顺序冲突清空流水线
+#[inline(always)]
+pub fn pause() {
+    unsafe {
+        core::arch::asm!("pause", options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// 永久停机循环
+///
+/// 用于内核主循环、panic 和不可恢复的异常
+/// 每次被中断唤醒后继续 hlt，不会像空循环那样占满宿主机 CPU
+pub fn hlt_loop() -> ! {
+    loop {
+        hlt();
+    }
+}
+
+// =============================================================================
+// 中断开关
+// =============================================================================
+
+/// 开启硬件中断（sti）
+///
+/// 必须在 IDT 加载、PIC 重映射之后调用
+pub fn enable_interrupts() {
+    x86_64::instructions::interrupts::enable();
+}
+
+/// 关闭硬件中断（cli）
+pub fn disable_interrupts() {
+    x86_64::instructions::interrupts::disable();
+}
+
+/// 当前是否开启了硬件中断（RFLAGS.IF）
+pub fn interrupts_enabled() -> bool {
+    x86_64::instructions::interrupts::are_enabled()
+}
+
+/// 在关闭中断的情况下执行闭包，结束后恢复原来的中断状态
+///
+/// 用于访问同时被中断处理函数使用的数据，避免持锁时被中断导致死锁；
+/// 需要长期共享的数据优先使用 `sync::IrqMutex`
+pub fn without_interrupts<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    x86_64::instructions::interrupts::without_interrupts(f)
+}
+
+/// 开启中断并立即停机，直到下一个中断
+///
+/// `sti` 之后的一条指令执行完才会响应中断，所以两条指令之间不会漏掉中断：
+/// 关中断检查完"有没有事做"之后用它等待，不会在检查和停机之间错过唤醒
+pub fn enable_and_hlt() {
+    x86_64::instructions::interrupts::enable_and_hlt();
+}
+
+/// `disable_guard` 返回的守卫：创建时关闭中断，丢弃时恢复创建前的中断状态
+///
+/// 保存的是创建时 RFLAGS.IF 的值，所以可以嵌套：内层守卫丢弃时中断仍保持关闭，
+/// 只有最外层守卫丢弃时才重新开启
+#[must_use = "中断在守卫被丢弃时就会恢复"]
+pub struct InterruptGuard {
+    interrupts_were_enabled: bool,
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        if self.interrupts_were_enabled {
+            enable_interrupts();
+        }
+    }
+}
+
+/// 关闭中断，直到返回的守卫被丢弃
+///
+/// 作用范围不适合写成 `without_interrupts` 闭包时使用（例如只在某个分支里关中断）
+pub fn disable_guard() -> InterruptGuard {
+    let interrupts_were_enabled = interrupts_enabled();
+    disable_interrupts();
+    InterruptGuard {
+        interrupts_were_enabled,
+    }
+}
+
+// =============================================================================
+// 模型特定寄存器（MSR）
+// =============================================================================
+
+/// 读取模型特定寄存器（rdmsr）
+///
+/// # Safety
+/// `msr` 必须是当前 CPU 支持的 MSR，否则触发 #GP
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        core::arch::asm!(
+            "rdmsr",
+            in("ecx") msr,
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    (u64::from(high) << 32) | u64::from(low)
+}
+
+/// 写入模型特定寄存器（wrmsr）
+///
+/// # Safety
+/// `msr` 必须是当前 CPU 支持的 MSR，写入的值必须合法；
+/// 很多 MSR 会直接改变 CPU 的行为（例如 APIC 基址、EFER）
+pub unsafe fn wrmsr(msr: u32, value: u64) {
+    unsafe {
+        core::arch::asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+// =============================================================================
+// 时间戳计数器（TSC）
+// =============================================================================
+
+/// 校准时测量的 PIT tick 数（10 个 tick = 100ms）
+const CALIBRATION_TICKS: u64 = 10;
+
+/// 每毫秒的 TSC 计数（即 TSC 频率，单位 kHz），0 表示还没有校准
+static TSC_PER_MS: AtomicU64 = AtomicU64::new(0);
+
+/// 校准结束时的 TSC 值和对应的启动毫秒数，用于换算微秒级的启动时间
+static TSC_BASE: AtomicU64 = AtomicU64::new(0);
+static TSC_BASE_MS: AtomicU64 = AtomicU64::new(0);
+
+/// 读取时间戳计数器（rdtsc）
+///
+/// TSC 在上电后以固定频率递增，适合测量很短的时间间隔
+#[inline(always)]
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        core::arch::asm!(
+            "rdtsc",
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    (u64::from(high) << 32) | u64::from(low)
+}
+
+/// 用 PIT 时钟校准 TSC 频率
+///
+/// 从一个 tick 的边沿开始，数 `CALIBRATION_TICKS` 个 tick 期间 TSC 增加了多少。
+/// 需要在 `timer::init()` 并开启中断之后调用，会阻塞约 100ms
+pub fn calibrate_tsc() {
+    // 等到下一个 tick 边沿再开始计时，减少误差
+    let start_tick = timer::ticks();
+    while timer::ticks() == start_tick {
+        hlt();
+    }
+
+    let start_tick = timer::ticks();
+    let start_tsc = rdtsc();
+    while timer::ticks() < start_tick + CALIBRATION_TICKS {
+        hlt();
+    }
+    let end_tsc = rdtsc();
+
+    let elapsed_ms = CALIBRATION_TICKS * timer::MS_PER_TICK;
+    TSC_BASE.store(end_tsc, Ordering::Relaxed);
+    TSC_BASE_MS.store(timer::uptime_ms(), Ordering::Relaxed);
+    TSC_PER_MS.store((end_tsc - start_tsc) / elapsed_ms, Ordering::Relaxed);
+}
+
+/// 校准得到的 TSC 频率（kHz，即每毫秒的计数），还没有校准时返回 None
+pub fn tsc_khz() -> Option<u64> {
+    match TSC_PER_MS.load(Ordering::Relaxed) {
+        0 => None,
+        khz => Some(khz),
+    }
+}
+
+/// 用 TSC 换算的启动以来的微秒数，还没有校准时返回 None
+///
+/// 以校准时的 PIT 时间为基准，之后的增量由 TSC 计算
+pub fn uptime_us() -> Option<u64> {
+    let khz = tsc_khz()?;
+    let delta = rdtsc().wrapping_sub(TSC_BASE.load(Ordering::Relaxed));
+    Some(TSC_BASE_MS.load(Ordering::Relaxed) * 1000 + delta * 1000 / khz)
+}
+
+/// 忙等指定微秒数
+///
+/// 使用校准过的 TSC，精度远高于 PIT；还没有校准时退回到 `timer::sleep_ms`（精度 10ms）
+#[allow(dead_code)]
+pub fn delay_us(us: u64) {
+    let Some(khz) = tsc_khz() else {
+        timer::sleep_ms(us.div_ceil(1000));
+        return;
+    };
+
+    let start = rdtsc();
+    let cycles = us * khz / 1000;
+    while rdtsc().wrapping_sub(start) < cycles {
+        pause();
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn interrupt_guards_nest() {
+        assert!(interrupts_enabled());
+        {
+            let _outer = disable_guard();
+            {
+                let _inner = disable_guard();
+                assert!(!interrupts_enabled());
+            }
+            // 内层守卫恢复的是"关闭"
+            assert!(!interrupts_enabled());
+        }
+        assert!(interrupts_enabled());
+    }
+}
@@ -0,0 +1,196 @@
+//! GwenOS CPUID 模块
+//!
+//! 通过 `cpuid` 指令查询 CPU 厂商、型号和支持的特性
+//!
+//! # 什么是 CPUID？
+//! `cpuid` 以 EAX（叶号 leaf）和 ECX（子叶号 subleaf）为输入，
+//! 在 EAX/EBX/ECX/EDX 中返回对应的 CPU 信息：
+//! - 叶 0：最大基本叶号 + 厂商字符串
+//! - 叶 1：型号信息 + 特性位（ECX/EDX）
+//! - 叶 0x80000002~0x80000004：处理器品牌字符串
+
+// =============================================================================
+// CPUID 指令封装
+// =============================================================================
+
+/// 一次 CPUID 查询的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+/// 执行 `cpuid` 指令（指定子叶号）
+///
+/// # 参数
+/// - `leaf`: 叶号（EAX）
+/// - `subleaf`: 子叶号（ECX），大多数叶会忽略它
+///
+/// # 说明
+/// LLVM 保留了 RBX 寄存器，不能直接作为 asm 的输出，
+/// 所以先把它保存到临时寄存器，执行完 cpuid 再交换回来
+pub fn cpuid_count(leaf: u32, subleaf: u32) -> CpuidResult {
+    let eax: u32;
+    let ebx: u32;
+    let ecx: u32;
+    let edx: u32;
+    unsafe {
+        core::arch::asm!(
+            "mov {tmp:r}, rbx",
+            "cpuid",
+            "xchg {tmp:r}, rbx",
+            tmp = out(reg) ebx,
+            inout("eax") leaf => eax,
+            inout("ecx") subleaf => ecx,
+            out("edx") edx,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    CpuidResult { eax, ebx, ecx, edx }
+}
+
+/// 执行 `cpuid` 指令（子叶号为 0）
+pub fn cpuid(leaf: u32) -> CpuidResult {
+    cpuid_count(leaf, 0)
+}
+
+// =============================================================================
+// CPU 特性
+// =============================================================================
+
+/// 叶 1 中报告的 CPU 特性
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    // ECX 中的特性位
+    Sse3,
+    Ssse3,
+    Sse41,
+    Sse42,
+    X2Apic,
+    Popcnt,
+    Aes,
+    Xsave,
+    Avx,
+    Hypervisor,
+    // EDX 中的特性位
+    Fpu,
+    Tsc,
+    Msr,
+    Pae,
+    Mce,
+    Apic,
+    Mca,
+    Mmx,
+    Fxsr,
+    Sse,
+    Sse2,
+    Htt,
+}
+
+/// 特性所在的寄存器
+enum FeatureReg {
+    Ecx,
+    Edx,
+}
+
+impl Feature {
+    /// 特性在叶 1 返回值中的位置（寄存器，位序号）
+    fn location(self) -> (FeatureReg, u32) {
+        use FeatureReg::{Ecx, Edx};
+        match self {
+            Feature::Sse3 => (Ecx, 0),
+            Feature::Ssse3 => (Ecx, 9),
+            Feature::Sse41 => (Ecx, 19),
+            Feature::Sse42 => (Ecx, 20),
+            Feature::X2Apic => (Ecx, 21),
+            Feature::Popcnt => (Ecx, 23),
+            Feature::Aes => (Ecx, 25),
+            Feature::Xsave => (Ecx, 26),
+            Feature::Avx => (Ecx, 28),
+            Feature::Hypervisor => (Ecx, 31),
+            Feature::Fpu => (Edx, 0),
+            Feature::Tsc => (Edx, 4),
+            Feature::Msr => (Edx, 5),
+            Feature::Pae => (Edx, 6),
+            Feature::Mce => (Edx, 7),
+            Feature::Apic => (Edx, 9),
+            Feature::Mca => (Edx, 14),
+            Feature::Mmx => (Edx, 23),
+            Feature::Fxsr => (Edx, 24),
+            Feature::Sse => (Edx, 25),
+            Feature::Sse2 => (Edx, 26),
+            Feature::Htt => (Edx, 28),
+        }
+    }
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 叶 0x80000000：返回支持的最大扩展叶号
+const LEAF_EXTENDED_MAX: u32 = 0x8000_0000;
+
+/// 品牌字符串所在的三个扩展叶
+const LEAF_BRAND_FIRST: u32 = 0x8000_0002;
+const LEAF_BRAND_LAST: u32 = 0x8000_0004;
+
+/// 读取 CPU 厂商字符串（如 `GenuineIntel`、`AuthenticAMD`）
+///
+/// 字符串按 EBX、EDX、ECX 的顺序存放在叶 0 的返回值中
+pub fn vendor_string() -> [u8; 12] {
+    let result = cpuid(0);
+    let mut vendor = [0u8; 12];
+    vendor[0..4].copy_from_slice(&result.ebx.to_le_bytes());
+    vendor[4..8].copy_from_slice(&result.edx.to_le_bytes());
+    vendor[8..12].copy_from_slice(&result.ecx.to_le_bytes());
+    vendor
+}
+
+/// 检查 CPU 是否支持某个特性
+#[allow(dead_code)]
+pub fn has_feature(feature: Feature) -> bool {
+    let result = cpuid(1);
+    let (reg, bit) = feature.location();
+    let value = match reg {
+        FeatureReg::Ecx => result.ecx,
+        FeatureReg::Edx => result.edx,
+    };
+    value & (1 << bit) != 0
+}
+
+/// 读取处理器品牌字符串（48 字节，以 NUL 填充）
+///
+/// # 返回
+/// CPU 不支持扩展叶 0x80000004 时返回 `None`
+pub fn brand_string() -> Option<[u8; 48]> {
+    if cpuid(LEAF_EXTENDED_MAX).eax < LEAF_BRAND_LAST {
+        return None;
+    }
+
+    let mut brand = [0u8; 48];
+    for (i, leaf) in (LEAF_BRAND_FIRST..=LEAF_BRAND_LAST).enumerate() {
+        // 每个叶提供 16 字节，依次为 EAX、EBX、ECX、EDX
+        let result = cpuid(leaf);
+        for (j, reg) in [result.eax, result.ebx, result.ecx, result.edx]
+            .into_iter()
+            .enumerate()
+        {
+            let offset = i * 16 + j * 4;
+            brand[offset..offset + 4].copy_from_slice(&reg.to_le_bytes());
+        }
+    }
+    Some(brand)
+}
+
+/// 把 CPUID 返回的字节串转换为去掉首尾空白和 NUL 的 `&str`
+///
+/// 非 UTF-8 内容返回 `"?"`
+pub fn bytes_as_str(bytes: &[u8]) -> &str {
+    core::str::from_utf8(bytes)
+        .unwrap_or("?")
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+}
@@ -0,0 +1,162 @@
+//! GwenOS 栈上格式化模块
+//!
+//! 提供不依赖堆、不需要加锁的格式化工具
+//! 用于 panic 处理、异常处理和早期启动这类不能安全使用全局 Writer 的场景
+
+use core::fmt;
+
+// =============================================================================
+// ByteMutWriter
+// =============================================================================
+
+/// 把格式化输出写入调用者提供的字节切片
+///
+/// 空间不足时静默截断（保证截断点落在 UTF-8 字符边界上），不会返回错误
+///
+/// # 示例
+/// ```ignore
+/// let mut buf = [0u8; 64];
+/// let mut w = ByteMutWriter::new(&mut buf);
+/// write!(w, "{}:{}", file, line).ok();
+/// vga::write_string_at(w.as_str(), 1, 0, 0x0c);
+/// ```
+pub struct ByteMutWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    truncated: bool,
+}
+
+impl<'a> ByteMutWriter<'a> {
+    /// 创建写入器，从切片开头开始写
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        ByteMutWriter {
+            buf,
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// 已写入的内容
+    pub fn as_str(&self) -> &str {
+        // write_str 只在字符边界截断，所以这里总是合法的 UTF-8
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+
+    /// 已写入的字节数
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 是否还没有写入任何内容
+    #[allow(dead_code)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 是否因为空间不足丢弃过内容
+    #[allow(dead_code)]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// 清空已写入的内容，重新从头开始写
+    #[allow(dead_code)]
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+}
+
+/// 实现 fmt::Write trait，支持 write! 宏
+impl fmt::Write for ByteMutWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let available = self.buf.len() - self.len;
+        let mut count = s.len();
+        if count > available {
+            // 空间不足：退到不超过剩余空间的最后一个字符边界
+            count = available;
+            while !s.is_char_boundary(count) {
+                count -= 1;
+            }
+            self.truncated = true;
+        }
+
+        self.buf[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+// =============================================================================
+// 数字格式化
+// =============================================================================
+
+/// u64 的十六进制最多 16 位
+pub const HEX_DIGITS_MAX: usize = 16;
+
+/// u64 的十进制最多 20 位
+pub const DEC_DIGITS_MAX: usize = 20;
+
+/// 不经过 `core::fmt`，把数字转换成小写十六进制数字（不带 `0x` 前缀）
+///
+/// 位数不足 `width` 时在前面补 0，`width` 超过 16 时按 16 处理；
+/// 不会 panic，适合异常处理函数和早期启动
+///
+/// # 返回
+/// `buf` 中存放数字的那一段
+pub fn hex_digits(value: u64, width: usize, buf: &mut [u8; HEX_DIGITS_MAX]) -> &[u8] {
+    let significant = (64 - value.leading_zeros() as usize).div_ceil(4).max(1);
+    let len = significant.max(width.min(HEX_DIGITS_MAX));
+    let start = HEX_DIGITS_MAX - len;
+    for (i, digit) in buf[start..].iter_mut().rev().enumerate() {
+        *digit = b"0123456789abcdef"[((value >> (i * 4)) & 0xF) as usize];
+    }
+    &buf[start..]
+}
+
+/// 不经过 `core::fmt`，把数字转换成十进制数字
+///
+/// # 返回
+/// `buf` 中存放数字的那一段
+pub fn dec_digits(value: u64, buf: &mut [u8; DEC_DIGITS_MAX]) -> &[u8] {
+    let mut start = DEC_DIGITS_MAX;
+    let mut rest = value;
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+    &buf[start..]
+}
+
+// =============================================================================
+// FieldEscaper
+// =============================================================================
+
+/// 转义写入的内容，使它能安全地作为 `key=value|key=value` 记录中的一个字段值
+///
+/// `|` 写成 `\|`，`\` 写成 `\\`，换行和回车分别写成 `\n`、`\r`，
+/// 保证整条记录只占一行、字段分隔符不会被消息内容打乱
+pub struct FieldEscaper<'a, W: fmt::Write>(pub &'a mut W);
+
+impl<W: fmt::Write> fmt::Write for FieldEscaper<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // 不需要转义的片段整段写入，只在特殊字符处拆开
+        let mut rest = s;
+        while let Some(pos) = rest.find(['|', '\\', '\n', '\r']) {
+            self.0.write_str(&rest[..pos])?;
+            self.0.write_str(match rest.as_bytes()[pos] {
+                b'|' => "\\|",
+                b'\\' => "\\\\",
+                b'\n' => "\\n",
+                _ => "\\r",
+            })?;
+            rest = &rest[pos + 1..];
+        }
+        self.0.write_str(rest)
+    }
+}
@@ -0,0 +1,610 @@
+//! GwenOS GDB 远程调试桩（GDB stub）
+//!
+//! 通过 COM1 实现 GDB 远程串行协议（RSP）的最小子集，在宿主机上用
+//! `target remote` 连接后可以查看和修改寄存器、内存，继续运行或单步执行
+//!
+//! # 协议格式
+//! - 数据包：`$<数据>#<校验和>`，校验和是数据所有字节之和（模 256）的两位十六进制
+//! - 收到数据包后回复 `+`（校验正确）或 `-`（请求重发）
+//!
+//! # 支持的命令
+//! | 命令 | 作用 | 回复 |
+//! |------|------|------|
+//! | `?` | 停止原因 | `S05`（SIGTRAP） |
+//! | `g` / `G` | 读 / 写全部寄存器 | 寄存器数据 / `OK` |
+//! | `m addr,len` / `M addr,len:XX..` | 读 / 写内存 | 内存数据 / `OK` |
+//! | `c [addr]` / `s [addr]` | 继续运行 / 单步执行 | 下次停止时回复 `S05` |
+//! | `D` | 断开调试器，之后断点不再进入调试桩 | `OK` |
+//!
+//! 其他命令回复空包，表示不支持
+//!
+//! # 使用方式
+//! 调用 `enable()` 后，断点异常（`int3`）和调试异常（单步）都会进入 `handle_exception`，
+//! 在关中断的状态下轮询串口和 GDB 交互，直到收到继续或单步命令才返回
+
+use crate::memory;
+use crate::registers::Registers;
+use crate::serial;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// =============================================================================
+// 常量定义
+// =============================================================================
+
+/// 数据包的最大长度（不含 `$`、`#` 和校验和）
+const PACKET_SIZE: usize = 1024;
+
+/// 一次 `m`/`M` 命令最多访问的字节数（每个字节对应两个十六进制字符）
+const MAX_MEMORY_ACCESS: usize = PACKET_SIZE / 2;
+
+/// 停止原因：SIGTRAP
+const STOP_REPLY: &[u8] = b"S05";
+
+/// 内存地址不可访问时的错误回复（EFAULT）
+const ERROR_FAULT: &[u8] = b"E14";
+
+/// 命令格式错误时的错误回复
+const ERROR_MALFORMED: &[u8] = b"E01";
+
+/// RFLAGS 的 TF 位：置位后每执行一条指令触发一次调试异常
+const RFLAGS_TF: u64 = 1 << 8;
+
+/// `g` 包中 64 位寄存器的个数（rax..r15、rip）
+const GDB_U64_REGS: usize = 17;
+
+/// `g` 包中 32 位寄存器的个数（eflags、cs、ss、ds、es、fs、gs）
+const GDB_U32_REGS: usize = 7;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+// =============================================================================
+// 状态
+// =============================================================================
+
+/// 断点异常是否进入调试桩（否则只在串口上记录）
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 上一次是否以继续或单步命令返回：是的话 GDB 正在等待停止回复
+static RESUMED: AtomicBool = AtomicBool::new(false);
+
+// =============================================================================
+// 连接
+// =============================================================================
+
+/// 调试桩和 GDB 之间的字节通道
+pub trait Connection {
+    /// 等待并读取一个字节
+    fn read_byte(&mut self) -> u8;
+    /// 发送一个字节
+    fn write_byte(&mut self, byte: u8);
+}
+
+/// 通过 COM1 轮询收发，不加锁也不依赖中断
+struct SerialConnection;
+
+impl Connection for SerialConnection {
+    fn read_byte(&mut self) -> u8 {
+        serial::read_byte_polling()
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        serial::write_byte_polling(byte);
+    }
+}
+
+// =============================================================================
+// 数据包收发
+// =============================================================================
+
+/// 数据的校验和：所有字节之和（模 256）
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// 读取一个完整的数据包，校验正确时回复 `+` 并返回数据，否则回复 `-` 等待重发
+///
+/// `$` 之前的字节（如 GDB 发送的确认符）都被忽略；超过 `PACKET_SIZE` 的数据包视为损坏
+fn read_packet<'a>(conn: &mut impl Connection, buf: &'a mut [u8; PACKET_SIZE]) -> &'a [u8] {
+    loop {
+        while conn.read_byte() != b'$' {}
+
+        let mut len = 0;
+        let mut overflow = false;
+        loop {
+            let byte = conn.read_byte();
+            if byte == b'#' {
+                break;
+            }
+            if len < PACKET_SIZE {
+                buf[len] = byte;
+                len += 1;
+            } else {
+                overflow = true;
+            }
+        }
+        let high = conn.read_byte();
+        let low = conn.read_byte();
+
+        if !overflow && parse_hex_byte(high, low) == Some(checksum(&buf[..len])) {
+            conn.write_byte(b'+');
+            return &buf[..len];
+        }
+        conn.write_byte(b'-');
+    }
+}
+
+/// 发送一个数据包，直到 GDB 回复 `+` 为止（收到 `-` 时重发）
+fn write_packet(conn: &mut impl Connection, data: &[u8]) {
+    let sum = checksum(data);
+    loop {
+        conn.write_byte(b'$');
+        for &byte in data {
+            conn.write_byte(byte);
+        }
+        conn.write_byte(b'#');
+        conn.write_byte(HEX_DIGITS[usize::from(sum >> 4)]);
+        conn.write_byte(HEX_DIGITS[usize::from(sum & 0xF)]);
+
+        loop {
+            match conn.read_byte() {
+                b'+' => return,
+                b'-' => break,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// 组装回复数据包的缓冲区，写满后多余的内容被丢弃
+struct Reply {
+    buf: [u8; PACKET_SIZE],
+    len: usize,
+}
+
+impl Reply {
+    const fn new() -> Self {
+        Reply {
+            buf: [0; PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len < PACKET_SIZE {
+            self.buf[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.push(byte);
+        }
+    }
+
+    /// 以两个十六进制字符追加一个字节
+    fn push_hex_byte(&mut self, byte: u8) {
+        self.push(HEX_DIGITS[usize::from(byte >> 4)]);
+        self.push(HEX_DIGITS[usize::from(byte & 0xF)]);
+    }
+
+    /// 按小端序追加 `value` 的低 `size` 个字节（GDB 寄存器数据的格式）
+    fn push_hex_le(&mut self, value: u64, size: usize) {
+        for byte in &value.to_le_bytes()[..size] {
+            self.push_hex_byte(*byte);
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+// =============================================================================
+// 十六进制解析
+// =============================================================================
+
+/// 单个十六进制字符的值
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// 两个十六进制字符组成的字节
+fn parse_hex_byte(high: u8, low: u8) -> Option<u8> {
+    Some(hex_value(high)? << 4 | hex_value(low)?)
+}
+
+/// 大端序的十六进制数（地址、长度），不能为空且最多 16 位
+fn parse_hex(s: &[u8]) -> Option<u64> {
+    if s.is_empty() || s.len() > 16 {
+        return None;
+    }
+    s.iter().try_fold(0u64, |value, &c| {
+        Some(value << 4 | u64::from(hex_value(c)?))
+    })
+}
+
+/// 小端序的十六进制数据（寄存器值），`s` 的长度必须是 `size` 个字节
+fn parse_hex_le(s: &[u8], size: usize) -> Option<u64> {
+    if s.len() != size * 2 {
+        return None;
+    }
+    let mut bytes = [0u8; 8];
+    for (byte, &[high, low]) in bytes.iter_mut().zip(s.as_chunks::<2>().0) {
+        *byte = parse_hex_byte(high, low)?;
+    }
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// 解析 `addr,len`
+fn parse_range(s: &[u8]) -> Option<(u64, usize)> {
+    let comma = s.iter().position(|&c| c == b',')?;
+    let addr = parse_hex(&s[..comma])?;
+    let len = usize::try_from(parse_hex(&s[comma + 1..])?).ok()?;
+    Some((addr, len))
+}
+
+// =============================================================================
+// 寄存器和内存
+// =============================================================================
+
+/// GDB x86-64 寄存器编号顺序中的 64 位寄存器（rax、rbx、rcx、rdx、rsi、rdi、rbp、rsp、
+/// r8~r15、rip），后面紧跟 32 位的 eflags
+fn gdb_registers(regs: &mut Registers) -> [&mut u64; GDB_U64_REGS + 1] {
+    [
+        &mut regs.rax,
+        &mut regs.rbx,
+        &mut regs.rcx,
+        &mut regs.rdx,
+        &mut regs.rsi,
+        &mut regs.rdi,
+        &mut regs.rbp,
+        &mut regs.rsp,
+        &mut regs.r8,
+        &mut regs.r9,
+        &mut regs.r10,
+        &mut regs.r11,
+        &mut regs.r12,
+        &mut regs.r13,
+        &mut regs.r14,
+        &mut regs.r15,
+        &mut regs.rip,
+        &mut regs.rflags,
+    ]
+}
+
+/// `g`：按 GDB 的顺序输出全部寄存器
+///
+/// 64 位寄存器各占 8 字节，eflags 和段寄存器各占 4 字节；
+/// ds、es、fs、gs 在 64 位模式下基本不用，入口也没有保存，固定为 0
+fn read_registers(regs: &mut Registers, reply: &mut Reply) {
+    let (cs, ss) = (regs.cs, regs.ss);
+    for (i, value) in gdb_registers(regs).into_iter().enumerate() {
+        let size = if i < GDB_U64_REGS { 8 } else { 4 };
+        reply.push_hex_le(*value, size);
+    }
+    for value in [cs, ss, 0, 0, 0, 0] {
+        reply.push_hex_le(value, 4);
+    }
+}
+
+/// `G`：按 `g` 的格式写回寄存器
+///
+/// 只写通用寄存器、rip 和 eflags；段寄存器写错会在返回时触发保护异常，忽略
+fn write_registers(regs: &mut Registers, data: &[u8]) -> bool {
+    let expected = (GDB_U64_REGS * 8 + GDB_U32_REGS * 4) * 2;
+    if data.len() != expected {
+        return false;
+    }
+    let mut values = [0u64; GDB_U64_REGS + 1];
+    let mut offset = 0;
+    for (i, value) in values.iter_mut().enumerate() {
+        let size = if i < GDB_U64_REGS { 8 } else { 4 };
+        match parse_hex_le(&data[offset..offset + size * 2], size) {
+            Some(v) => *value = v,
+            None => return false,
+        }
+        offset += size * 2;
+    }
+    for (reg, value) in gdb_registers(regs).into_iter().zip(values) {
+        *reg = value;
+    }
+    true
+}
+
+/// `m addr,len`：以十六进制输出一段内存
+fn read_memory(args: &[u8], reply: &mut Reply) {
+    let Some((addr, len)) = parse_range(args) else {
+        reply.push_bytes(ERROR_MALFORMED);
+        return;
+    };
+    // 回复放不下更多字节；先截断再检查，不去遍历调试器给出的任意长度
+    let len = len.min(MAX_MEMORY_ACCESS);
+    if memory::check_access(addr, len, false).is_err() {
+        reply.push_bytes(ERROR_FAULT);
+        return;
+    }
+    for i in 0..len {
+        // SAFETY: check_access 已确认这段地址全部映射
+        let byte = unsafe { ((addr as usize + i) as *const u8).read_volatile() };
+        reply.push_hex_byte(byte);
+    }
+}
+
+/// `M addr,len:XX..`：把十六进制数据写入内存
+///
+/// 长度超过 `MAX_MEMORY_ACCESS` 或和数据的长度不符时当作格式错误
+fn write_memory(args: &[u8], reply: &mut Reply) {
+    let parsed = args.iter().position(|&c| c == b':').and_then(|colon| {
+        let (addr, len) = parse_range(&args[..colon])?;
+        let data = &args[colon + 1..];
+        (len <= MAX_MEMORY_ACCESS && len.checked_mul(2) == Some(data.len()))
+            .then_some((addr, len, data))
+    });
+    let Some((addr, len, data)) = parsed else {
+        reply.push_bytes(ERROR_MALFORMED);
+        return;
+    };
+    if memory::check_access(addr, len, true).is_err() {
+        reply.push_bytes(ERROR_FAULT);
+        return;
+    }
+    for (i, &[high, low]) in data.as_chunks::<2>().0.iter().enumerate() {
+        let Some(byte) = parse_hex_byte(high, low) else {
+            reply.push_bytes(ERROR_MALFORMED);
+            return;
+        };
+        // SAFETY: check_access 已确认这段地址全部映射且可写
+        unsafe { ((addr as usize + i) as *mut u8).write_volatile(byte) };
+    }
+    reply.push_bytes(b"OK");
+}
+
+// =============================================================================
+// 命令处理
+// =============================================================================
+
+/// 处理完一个数据包之后要做的事
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// 发送回复，继续等待下一个命令
+    Reply,
+    /// 发送回复后返回被中断的代码（断开调试器）
+    ReplyAndResume,
+    /// 不回复，直接返回被中断的代码（继续、单步）
+    Resume,
+}
+
+/// 处理一个命令，回复写入 `reply`
+fn handle_packet(packet: &[u8], regs: &mut Registers, reply: &mut Reply) -> Action {
+    let Some((&command, args)) = packet.split_first() else {
+        return Action::Reply;
+    };
+    match command {
+        b'?' => reply.push_bytes(STOP_REPLY),
+        b'g' => read_registers(regs, reply),
+        b'G' => {
+            if write_registers(regs, args) {
+                reply.push_bytes(b"OK");
+            } else {
+                reply.push_bytes(ERROR_MALFORMED);
+            }
+        }
+        b'm' => read_memory(args, reply),
+        b'M' => write_memory(args, reply),
+        b'c' | b's' => {
+            if let Some(addr) = parse_hex(args) {
+                regs.rip = addr;
+            }
+            if command == b's' {
+                regs.rflags |= RFLAGS_TF;
+            }
+            return Action::Resume;
+        }
+        b'D' => {
+            ENABLED.store(false, Ordering::Relaxed);
+            reply.push_bytes(b"OK");
+            return Action::ReplyAndResume;
+        }
+        // 不支持的命令回复空包
+        _ => {}
+    }
+    Action::Reply
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 让之后的断点和单步异常进入调试桩
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// 调试桩是否已启用
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// 断点或调试异常进入调试桩：和 GDB 交互，直到收到继续或单步命令
+///
+/// 由异常的汇编入口在关中断的状态下调用；对 `regs` 的修改在返回时生效
+pub fn handle_exception(regs: &mut Registers) {
+    let mut conn = SerialConnection;
+
+    // 单步执行只走一条指令：清掉上次 `s` 设置的 TF，需要时由下一条 `s` 重新设置
+    regs.rflags &= !RFLAGS_TF;
+
+    // GDB 发出继续或单步后在等待停止回复；第一次进入时 GDB 还没有连接，等它发 `?`
+    if RESUMED.swap(false, Ordering::Relaxed) {
+        write_packet(&mut conn, STOP_REPLY);
+    }
+
+    let mut packet = [0u8; PACKET_SIZE];
+    loop {
+        let mut reply = Reply::new();
+        let action = handle_packet(read_packet(&mut conn, &mut packet), regs, &mut reply);
+        if action != Action::Resume {
+            write_packet(&mut conn, reply.as_bytes());
+        }
+        if action != Action::Reply {
+            RESUMED.store(action == Action::Resume, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 从固定输入读取、把输出记录下来的连接
+    struct MockConnection<'a> {
+        input: &'a [u8],
+        output: [u8; 64],
+        written: usize,
+    }
+
+    impl<'a> MockConnection<'a> {
+        fn new(input: &'a [u8]) -> Self {
+            MockConnection {
+                input,
+                output: [0; 64],
+                written: 0,
+            }
+        }
+
+        fn output(&self) -> &[u8] {
+            &self.output[..self.written]
+        }
+    }
+
+    impl Connection for MockConnection<'_> {
+        fn read_byte(&mut self) -> u8 {
+            let (&byte, rest) = self.input.split_first().expect("mock input exhausted");
+            self.input = rest;
+            byte
+        }
+
+        fn write_byte(&mut self, byte: u8) {
+            self.output[self.written] = byte;
+            self.written += 1;
+        }
+    }
+
+    fn empty_registers() -> Registers {
+        Registers {
+            r15: 0,
+            r14: 0,
+            r13: 0,
+            r12: 0,
+            r11: 0,
+            r10: 0,
+            r9: 0,
+            r8: 0,
+            rbp: 0,
+            rdi: 0,
+            rsi: 0,
+            rdx: 0,
+            rcx: 0,
+            rbx: 0,
+            rax: 0,
+            vector: 3,
+            error_code: 0,
+            rip: 0,
+            cs: 0x08,
+            rflags: 0x202,
+            rsp: 0,
+            ss: 0,
+        }
+    }
+
+    #[test_case]
+    fn read_packet_checks_checksum() {
+        // 第一个包校验和错误，应回复 `-`；重发的包正确，回复 `+`
+        let mut conn = MockConnection::new(b"+$g#00$g#67");
+        let mut buf = [0u8; PACKET_SIZE];
+        assert_eq!(read_packet(&mut conn, &mut buf), b"g");
+        assert_eq!(conn.output(), b"-+");
+    }
+
+    #[test_case]
+    fn write_packet_appends_checksum() {
+        let mut conn = MockConnection::new(b"+");
+        write_packet(&mut conn, b"OK");
+        assert_eq!(conn.output(), b"$OK#9a");
+    }
+
+    #[test_case]
+    fn registers_round_trip() {
+        let mut regs = empty_registers();
+        regs.rax = 0x1122_3344_5566_7788;
+        regs.rip = 0xdead_beef;
+        let mut reply = Reply::new();
+        read_registers(&mut regs, &mut reply);
+        assert_eq!(reply.len, (GDB_U64_REGS * 8 + GDB_U32_REGS * 4) * 2);
+        assert!(reply.as_bytes().starts_with(b"8877665544332211"));
+
+        let mut copy = empty_registers();
+        assert!(write_registers(&mut copy, reply.as_bytes()));
+        assert_eq!(copy.rax, regs.rax);
+        assert_eq!(copy.rip, regs.rip);
+        assert_eq!(copy.rflags, regs.rflags);
+    }
+
+    #[test_case]
+    fn memory_commands_access_mapped_memory() {
+        let value: u32 = 0x1234_5678;
+        let addr = &value as *const u32 as u64;
+        let mut regs = empty_registers();
+
+        let mut packet = Reply::new();
+        packet.push(b'm');
+        packet.push_hex_le(addr.swap_bytes(), 8);
+        packet.push_bytes(b",4");
+        let mut reply = Reply::new();
+        handle_packet(packet.as_bytes(), &mut regs, &mut reply);
+        assert_eq!(reply.as_bytes(), b"78563412");
+
+        // 地址 0 所在的页没有映射
+        let mut reply = Reply::new();
+        handle_packet(b"m0,4", &mut regs, &mut reply);
+        assert_eq!(reply.as_bytes(), ERROR_FAULT);
+    }
+
+    #[test_case]
+    fn write_memory_rejects_oversized_lengths() {
+        let mut regs = empty_registers();
+
+        // len * 2 溢出后等于 0，不能被当作和空数据相符
+        let mut reply = Reply::new();
+        handle_packet(b"M1000,8000000000000000:", &mut regs, &mut reply);
+        assert_eq!(reply.as_bytes(), ERROR_MALFORMED);
+
+        let mut reply = Reply::new();
+        handle_packet(b"M1000,3:0011", &mut regs, &mut reply);
+        assert_eq!(reply.as_bytes(), ERROR_MALFORMED);
+    }
+
+    #[test_case]
+    fn step_sets_trap_flag_and_resumes() {
+        let mut regs = empty_registers();
+        let mut reply = Reply::new();
+        assert_eq!(handle_packet(b"s", &mut regs, &mut reply), Action::Resume);
+        assert_ne!(regs.rflags & RFLAGS_TF, 0);
+        assert_eq!(
+            handle_packet(b"c1000", &mut regs, &mut reply),
+            Action::Resume
+        );
+        assert_eq!(regs.rip, 0x1000);
+    }
+}
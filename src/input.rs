@@ -0,0 +1,194 @@
+//! GwenOS 输入模块
+//!
+//! 把 PS/2 键盘和串口终端合并成同一个输入源：串口收到的字节经过 `serial::ansi`
+//! 的解析器（方向键、F1~F4 和单独的 Esc 在那里识别），再翻译成和键盘相同的 `KeyEvent`，
+//! 所以 `read_line` 在 QEMU 图形窗口和串口控制台中的行为完全一致
+
+use crate::keyboard::{self, ArrowKey, KeyEvent};
+use crate::serial::ansi::{Action, Arrow, Parser};
+use crate::sync::IrqMutex;
+use crate::{cpu, print, println, serial, vga};
+use alloc::string::String;
+
+// =============================================================================
+// 串口输入翻译
+// =============================================================================
+
+/// 把 ANSI 解析器的一个动作翻译成按键事件
+///
+/// 方向键、F1~F4 和 Esc 对应键盘的同名按键；回车（`\r` 或 `\n`）、退格（DEL 或 BS）、Tab
+/// 和可打印 ASCII 对应键盘的同名事件；其他控制字节、非 ASCII 字节和颜色序列被丢弃
+fn serial_event(action: Action) -> Option<KeyEvent> {
+    match action {
+        Action::Print(b'\r' | b'\n') => Some(KeyEvent::Enter),
+        Action::Print(0x7F | 0x08) => Some(KeyEvent::Backspace),
+        Action::Print(b'\t') => Some(KeyEvent::Tab),
+        Action::Print(byte @ 0x20..=0x7E) => Some(KeyEvent::Char(char::from(byte))),
+        Action::Print(_) | Action::Sgr(_) => None,
+        Action::Escape => Some(KeyEvent::Escape),
+        Action::Function(n) => Some(KeyEvent::Function(n)),
+        Action::Arrow(arrow) => Some(KeyEvent::Arrow(match arrow {
+            Arrow::Up => ArrowKey::Up,
+            Arrow::Down => ArrowKey::Down,
+            Arrow::Right => ArrowKey::Right,
+            Arrow::Left => ArrowKey::Left,
+        })),
+    }
+}
+
+/// COM1 输入使用的解析器：转义序列被拆在两次读取之间时也能接上
+static SERIAL_PARSER: IrqMutex<Parser> = IrqMutex::new(Parser::new());
+
+/// Esc 之后暂存的事件：单独的 Esc 和紧跟它的字节是同时解析出来的，只能一次返回一个
+static SERIAL_PENDING: IrqMutex<Option<KeyEvent>> = IrqMutex::new(None);
+
+/// 从 COM1 的接收队列取出字节，直到组成一个按键事件
+fn read_serial_event() -> Option<KeyEvent> {
+    let mut pending = SERIAL_PENDING.lock();
+    if let Some(event) = pending.take() {
+        return Some(event);
+    }
+    let mut parser = SERIAL_PARSER.lock();
+    while let Some(byte) = serial::read_char() {
+        let action = parser.advance(byte);
+        if action == Some(Action::Escape) {
+            *pending = parser.take_pending().and_then(serial_event);
+        }
+        if let Some(event) = action.and_then(serial_event) {
+            return Some(event);
+        }
+    }
+    None
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 取出一个按键事件，键盘优先，其次是串口终端；都没有输入时返回 None
+pub fn read_event() -> Option<KeyEvent> {
+    keyboard::read_event().or_else(read_serial_event)
+}
+
+/// 每按一次左右方向键水平滚动的列数
+const H_SCROLL_STEP: isize = 8;
+
+/// 读取一行输入（阻塞，直到按下回车）
+///
+/// 同时接受键盘和串口终端的输入；输入的字符会回显到屏幕，退格可以删除已输入的字符，
+/// 左右方向键水平滚动屏幕；
+/// 返回的字符串不包含换行符。调用时必须已开启中断
+pub fn read_line() -> String {
+    let mut line = String::new();
+    loop {
+        let next = {
+            let _irq = cpu::disable_guard();
+            let next = read_event();
+            if next.is_none() {
+                // 检查队列和停机之间不能被中断，否则刚到的输入要等下一个中断才会被处理
+                cpu::enable_and_hlt();
+            }
+            next
+        };
+        let Some(event) = next else {
+            continue;
+        };
+        match event {
+            KeyEvent::Enter => {
+                println!();
+                return line;
+            }
+            KeyEvent::Backspace => {
+                // 退格：删除最后一个字符，并在屏幕上擦除
+                if line.pop().is_some() {
+                    print!("\x08 \x08");
+                }
+            }
+            KeyEvent::Arrow(ArrowKey::Left) => vga::scroll_horizontal(-H_SCROLL_STEP),
+            KeyEvent::Arrow(ArrowKey::Right) => vga::scroll_horizontal(H_SCROLL_STEP),
+            KeyEvent::Char(c) if !c.is_control() => {
+                line.push(c);
+                print!("{}", c);
+            }
+            // 其他按键（Tab、Esc、上下方向键、功能键）忽略
+            _ => {}
+        }
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// 把整段字节送进新的解析器，收集翻译出的全部事件（和 `read_serial_event` 一样处理暂存的字节）
+    fn decode(bytes: &[u8]) -> Vec<KeyEvent> {
+        let mut parser = Parser::new();
+        let mut events = Vec::new();
+        for &byte in bytes {
+            let action = parser.advance(byte);
+            let pending = if action == Some(Action::Escape) {
+                parser.take_pending()
+            } else {
+                None
+            };
+            events.extend(action.and_then(serial_event));
+            events.extend(pending.and_then(serial_event));
+        }
+        events
+    }
+
+    #[test_case]
+    fn serial_bytes_match_keyboard_events() {
+        assert_eq!(
+            decode(b"a\x1b[D\x1b[31mb\x7f\r"),
+            [
+                KeyEvent::Char('a'),
+                KeyEvent::Arrow(ArrowKey::Left),
+                KeyEvent::Char('b'),
+                KeyEvent::Backspace,
+                KeyEvent::Enter,
+            ]
+        );
+    }
+
+    #[test_case]
+    fn lone_escape_is_reported_before_next_byte() {
+        assert_eq!(decode(b"\x1bx"), [KeyEvent::Escape, KeyEvent::Char('x')]);
+        // 连续两个 ESC：第一个是 Esc 键，第二个开始方向键序列
+        assert_eq!(
+            decode(b"\x1b\x1b[A"),
+            [KeyEvent::Escape, KeyEvent::Arrow(ArrowKey::Up)]
+        );
+    }
+
+    #[test_case]
+    fn ss3_arrows_decode_like_csi_arrows() {
+        assert_eq!(
+            decode(b"\x1bOA\x1bOB\x1bOC\x1bOD"),
+            [
+                KeyEvent::Arrow(ArrowKey::Up),
+                KeyEvent::Arrow(ArrowKey::Down),
+                KeyEvent::Arrow(ArrowKey::Right),
+                KeyEvent::Arrow(ArrowKey::Left),
+            ]
+        );
+    }
+
+    #[test_case]
+    fn ss3_function_keys_decode_to_f1_through_f4() {
+        assert_eq!(
+            decode(b"\x1bOP\x1bOQ\x1bOR\x1bOS"),
+            [
+                KeyEvent::Function(1),
+                KeyEvent::Function(2),
+                KeyEvent::Function(3),
+                KeyEvent::Function(4),
+            ]
+        );
+    }
+}
@@ -7,11 +7,134 @@
 //! - 异常（Exception）：CPU 自己触发，如除零、缺页
 //! - 硬件中断（IRQ）：外部设备触发，如键盘、定时器
 //! - 软件中断：程序主动触发，如系统调用
+//!
+//! # 哪些异常处理函数可以返回？
+//! 返回时 CPU 从栈帧中的 RIP 继续执行，所以要看 RIP 指向哪里：
+//! - 陷阱（Trap，如断点 #BP、溢出 #OF）：RIP 指向触发指令的下一条，返回后继续执行，安全
+//! - 故障（Fault，如 #BR、#GP、缺页）：RIP 指向出错的指令本身，返回后重新执行；
+//!   除非处理函数消除了出错的原因，否则会立刻再次触发，陷入无限循环
+//! - 中止（Abort，如双重故障）：现场已经无法恢复，只能停机
+//!
+//! 64 位模式下 `into`、`bound` 指令已被移除，#OF 和 #BR 只能由 `int 4`、`int 5` 触发；
+//! 软件中断的 RIP 总是指向下一条指令，所以这两个处理函数记录后直接返回
+//!
+//! 机器检查（#MC）是中止：硬件报告了无法纠正的错误，处理函数输出 MCA 寄存器后停机
 
-use crate::serial;
+use crate::qemu::{self, QemuExitCode};
+use crate::registers::Registers;
+use crate::{
+    apic, cmos, cpu, cpuid, gdbstub, keyboard, log, pic, serial, serial_debug, serial_println,
+    timer, vga,
+};
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU64, Ordering};
 use lazy_static::lazy_static;
+use x86_64::VirtAddr;
+use x86_64::registers::control::{Cr4, Cr4Flags};
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
 
+/// 关中断守卫：`interrupts::disable_guard()` 关闭中断并返回 `InterruptGuard`，
+/// 守卫丢弃时恢复创建前的中断状态，可以嵌套（实现在 `cpu` 中，这里重新导出）
+pub use crate::cpu::{InterruptGuard, disable_guard};
+
+// =============================================================================
+// 硬件中断向量
+// =============================================================================
+
+/// 硬件中断（IRQ）在 IDT 中的向量号
+/// PIC 重映射后，IRQ0 从 PIC_1_OFFSET 开始依次排列
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum InterruptIndex {
+    /// 时钟中断（IRQ0）
+    Timer = pic::PIC_1_OFFSET,
+    /// 键盘中断（IRQ1）
+    Keyboard,
+    /// COM1 串口中断（IRQ4）
+    Com1 = pic::PIC_1_OFFSET + serial::COM1_IRQ,
+    /// 主 PIC 的 IRQ7（可能是伪中断）
+    PicSpuriousMaster = pic::PIC_1_OFFSET + pic::SPURIOUS_MASTER_IRQ,
+    /// RTC 周期中断（IRQ8，从 PIC 上的第一条线）
+    Rtc = pic::PIC_1_OFFSET + cmos::RTC_IRQ,
+    /// 从 PIC 的 IRQ15（可能是伪中断）
+    PicSpuriousSlave = pic::PIC_1_OFFSET + pic::SPURIOUS_SLAVE_IRQ,
+    /// 本地 APIC 定时器中断（紧接在两片 PIC 的向量之后）
+    ApicTimer = apic::TIMER_VECTOR,
+    /// 本地 APIC 伪中断
+    ApicSpurious = apic::SPURIOUS_VECTOR,
+}
+
+impl InterruptIndex {
+    /// 向量号（u8）
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// 向量号（usize），用于索引 IDT
+    pub fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+}
+
+// =============================================================================
+// 中断统计
+// =============================================================================
+
+// CPU 异常的向量号
+const DEBUG_VECTOR: u8 = 1;
+const BREAKPOINT_VECTOR: u8 = 3;
+const OVERFLOW_VECTOR: u8 = 4;
+const BOUND_RANGE_VECTOR: u8 = 5;
+const DOUBLE_FAULT_VECTOR: u8 = 8;
+const INVALID_TSS_VECTOR: u8 = 10;
+const SEGMENT_NOT_PRESENT_VECTOR: u8 = 11;
+const ALIGNMENT_CHECK_VECTOR: u8 = 17;
+const MACHINE_CHECK_VECTOR: u8 = 18;
+
+/// RFLAGS 的 TF 位（单步执行）
+const RFLAGS_TF: u64 = 1 << 8;
+
+/// 每个中断向量触发的次数
+static INTERRUPT_COUNTS: [AtomicU64; 256] = [const { AtomicU64::new(0) }; 256];
+
+/// 记录一次中断，在每个处理函数开头调用
+fn record(vector: u8) {
+    INTERRUPT_COUNTS[usize::from(vector)].fetch_add(1, Ordering::Relaxed);
+}
+
+/// 获取某个中断向量触发的次数
+#[allow(dead_code)]
+pub fn count(vector: u8) -> u64 {
+    INTERRUPT_COUNTS[usize::from(vector)].load(Ordering::Relaxed)
+}
+
+/// 把所有非零的中断计数输出到串口
+///
+/// 用于确认定时器是否在正常计时，或者排查中断风暴；属于调试日志，安静启动时不输出
+pub fn dump_stats() {
+    if !log::enabled(log::Level::Debug) {
+        return;
+    }
+    serial_debug!("Interrupt statistics:");
+    write_counts();
+}
+
+/// 和 `dump_stats` 相同，但安静启动时也输出；用于崩溃报告
+pub fn report_stats() {
+    serial_println!("interrupts:");
+    write_counts();
+}
+
+/// 每个非零的中断计数输出一行
+fn write_counts() {
+    for (vector, counter) in INTERRUPT_COUNTS.iter().enumerate() {
+        let count = counter.load(Ordering::Relaxed);
+        if count != 0 {
+            serial_println!("  vector {:#04x} ({:3}): {}", vector, vector, count);
+        }
+    }
+}
+
 // =============================================================================
 // IDT 静态实例
 // =============================================================================
@@ -35,38 +158,613 @@ lazy_static! {
         // 创建新的 IDT
         let mut idt = InterruptDescriptorTable::new();
 
-        // 注册断点异常处理器（中断号 3）
-        idt.breakpoint.set_handler_fn(breakpoint_handler);
+        // 先把 32~255 号向量全部指向兜底入口，下面注册的处理器会覆盖各自的向量
+        // SAFETY: 兜底入口按不带错误码的栈帧编写，恢复寄存器后用 iretq 返回
+        for vector in FIRST_UNHANDLED_VECTOR..=u8::MAX {
+            unsafe { idt[usize::from(vector)].set_handler_addr(unhandled_entry_addr(vector)) };
+        }
+
+        // 注册调试异常和断点异常处理器（中断号 1、3）
+        // 使用汇编入口：GDB 调试桩需要读写全部寄存器
+        // SAFETY: 这两个入口按不带错误码的栈帧编写，恢复寄存器后用 iretq 返回
+        unsafe {
+            idt.debug.set_handler_addr(returning_entry_addr(debug_entry));
+            idt.breakpoint.set_handler_addr(returning_entry_addr(breakpoint_entry));
+        }
 
-        // 注册双重故障处理器（中断号 8）
-        idt.double_fault.set_handler_fn(double_fault_handler);
+        // 注册溢出和越界异常处理器（中断号 4、5），记录后返回
+        idt.overflow.set_handler_fn(overflow_handler);
+        idt.bound_range_exceeded.set_handler_fn(bound_range_exceeded_handler);
+
+        // 不可恢复的异常使用汇编入口，处理函数能拿到完整的寄存器状态
+        // SAFETY: 这些入口都按 CPU 压入的栈帧（含错误码）编写，且永不返回
+        unsafe {
+            // 注册双重故障处理器（中断号 8）
+            idt.double_fault.set_handler_addr(entry_addr(double_fault_entry));
+
+            // 注册段相关异常和对齐检查异常（中断号 10、11、17）
+            idt.invalid_tss.set_handler_addr(entry_addr(invalid_tss_entry));
+            idt.segment_not_present.set_handler_addr(entry_addr(segment_not_present_entry));
+            idt.alignment_check.set_handler_addr(entry_addr(alignment_check_entry));
+
+            // 注册机器检查处理器（中断号 18，没有错误码）
+            idt.machine_check.set_handler_addr(entry_addr(machine_check_entry));
+        }
+
+        // 注册时钟中断处理器（IRQ0）
+        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
+
+        // 注册键盘中断处理器（IRQ1）
+        idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+
+        // 注册串口中断处理器（IRQ4）
+        idt[InterruptIndex::Com1.as_usize()].set_handler_fn(com1_interrupt_handler);
+
+        // 注册 RTC 周期中断处理器（IRQ8）
+        idt[InterruptIndex::Rtc.as_usize()].set_handler_fn(rtc_interrupt_handler);
+
+        // 注册 PIC 的 IRQ7 和 IRQ15 处理器（区分伪中断）
+        idt[InterruptIndex::PicSpuriousMaster.as_usize()].set_handler_fn(pic_irq7_interrupt_handler);
+        idt[InterruptIndex::PicSpuriousSlave.as_usize()].set_handler_fn(pic_irq15_interrupt_handler);
+
+        // 注册本地 APIC 定时器和伪中断处理器
+        idt[InterruptIndex::ApicTimer.as_usize()].set_handler_fn(apic_timer_interrupt_handler);
+        idt[InterruptIndex::ApicSpurious.as_usize()].set_handler_fn(apic_spurious_interrupt_handler);
 
         idt
     };
 }
 
+// =============================================================================
+// 保存寄存器的异常入口
+// =============================================================================
+
+/// 生成带错误码异常的汇编入口
+///
+/// 入口压入向量号和全部通用寄存器，以栈上拼好的 `Registers` 调用 `$handler`（不会返回）。
+/// 进入异常时 CPU 已把栈对齐到 16 字节再压入 48 字节（栈帧 + 错误码），
+/// 再压 16 个 8 字节后 call 时栈仍是 16 字节对齐，符合 System V 调用约定
+macro_rules! exception_entry_with_error_code {
+    ($name:ident, $vector:expr, $handler:ident) => {
+        #[unsafe(naked)]
+        extern "C" fn $name() -> ! {
+            core::arch::naked_asm!(
+                "push {vector}",
+                "push rax",
+                "push rbx",
+                "push rcx",
+                "push rdx",
+                "push rsi",
+                "push rdi",
+                "push rbp",
+                "push r8",
+                "push r9",
+                "push r10",
+                "push r11",
+                "push r12",
+                "push r13",
+                "push r14",
+                "push r15",
+                "cld",
+                "mov rdi, rsp",
+                "call {handler}",
+                "ud2",
+                vector = const $vector,
+                handler = sym $handler,
+            );
+        }
+    };
+}
+
+exception_entry_with_error_code!(
+    double_fault_entry,
+    DOUBLE_FAULT_VECTOR,
+    double_fault_handler
+);
+exception_entry_with_error_code!(invalid_tss_entry, INVALID_TSS_VECTOR, invalid_tss_handler);
+exception_entry_with_error_code!(
+    segment_not_present_entry,
+    SEGMENT_NOT_PRESENT_VECTOR,
+    segment_not_present_handler
+);
+exception_entry_with_error_code!(
+    alignment_check_entry,
+    ALIGNMENT_CHECK_VECTOR,
+    alignment_check_handler
+);
+
+/// 生成不带错误码、不会返回的异常入口
+///
+/// 先压入 0 占住错误码的位置，其余和 `exception_entry_with_error_code` 相同
+macro_rules! exception_entry_without_error_code {
+    ($name:ident, $vector:expr, $handler:ident) => {
+        #[unsafe(naked)]
+        extern "C" fn $name() -> ! {
+            core::arch::naked_asm!(
+                "push 0",
+                "push {vector}",
+                "push rax",
+                "push rbx",
+                "push rcx",
+                "push rdx",
+                "push rsi",
+                "push rdi",
+                "push rbp",
+                "push r8",
+                "push r9",
+                "push r10",
+                "push r11",
+                "push r12",
+                "push r13",
+                "push r14",
+                "push r15",
+                "cld",
+                "mov rdi, rsp",
+                "call {handler}",
+                "ud2",
+                vector = const $vector,
+                handler = sym $handler,
+            );
+        }
+    };
+}
+
+exception_entry_without_error_code!(
+    machine_check_entry,
+    MACHINE_CHECK_VECTOR,
+    machine_check_handler
+);
+
+/// 生成不带错误码、处理完后返回的异常入口
+///
+/// 先压入 0 占住错误码的位置，栈布局就和 `Registers` 一致；`$handler` 返回后
+/// 按相反顺序恢复寄存器（处理函数对 `Registers` 的修改在这里生效），再用 `iretq` 返回。
+/// CPU 压入 40 字节栈帧，入口再压 17 个 8 字节，call 时栈同样是 16 字节对齐
+macro_rules! exception_entry_returning {
+    ($name:ident, $vector:expr, $handler:ident) => {
+        #[unsafe(naked)]
+        extern "C" fn $name() {
+            core::arch::naked_asm!(
+                "push 0",
+                "push {vector}",
+                "push rax",
+                "push rbx",
+                "push rcx",
+                "push rdx",
+                "push rsi",
+                "push rdi",
+                "push rbp",
+                "push r8",
+                "push r9",
+                "push r10",
+                "push r11",
+                "push r12",
+                "push r13",
+                "push r14",
+                "push r15",
+                "cld",
+                "mov rdi, rsp",
+                "call {handler}",
+                "pop r15",
+                "pop r14",
+                "pop r13",
+                "pop r12",
+                "pop r11",
+                "pop r10",
+                "pop r9",
+                "pop r8",
+                "pop rbp",
+                "pop rdi",
+                "pop rsi",
+                "pop rdx",
+                "pop rcx",
+                "pop rbx",
+                "pop rax",
+                "add rsp, 16",
+                "iretq",
+                vector = const $vector,
+                handler = sym $handler,
+            );
+        }
+    };
+}
+
+exception_entry_returning!(debug_entry, DEBUG_VECTOR, debug_handler);
+exception_entry_returning!(breakpoint_entry, BREAKPOINT_VECTOR, breakpoint_handler);
+
+// =============================================================================
+// 未注册的中断向量
+// =============================================================================
+//
+// 没有注册处理器的向量触发时 CPU 会先产生一般保护异常，接着是双重故障，
+// 看不出是哪个向量。所以 32~255 号向量默认都指向一个兜底入口：在串口报告向量号，
+// 属于 PIC 的向量发送 EOI，然后返回。兜底入口需要知道向量号，而 CPU 不会压入它，
+// 于是每个向量生成一小段跳板：压入 0（错误码）和向量号，再跳到公共入口。
+// 0~31 号是 CPU 异常，有的带错误码，返回后会再次出错，不在兜底范围内
+
+/// 兜底范围的第一个向量（CPU 异常之后）
+const FIRST_UNHANDLED_VECTOR: u8 = 32;
+
+/// 每段跳板占用的字节数（`push 0` 2 字节、`push imm32` 5 字节、`jmp rel32` 5 字节，对齐到 16）
+const UNHANDLED_STUB_SIZE: u64 = 16;
+
+/// 32~255 号向量的跳板，第 `n` 段属于向量 `32 + n`
+///
+/// 不能直接调用，只用来计算各段跳板的地址（见 `unhandled_entry_addr`）
+#[unsafe(naked)]
+extern "C" fn unhandled_stubs() {
+    core::arch::naked_asm!(
+        ".balign 16",
+        ".set gwen_unhandled_vector, {first}",
+        ".rept {count}",
+        "push 0",
+        // push imm32：向量号
+        ".byte 0x68",
+        ".long gwen_unhandled_vector",
+        "jmp {common}",
+        ".balign 16",
+        ".set gwen_unhandled_vector, gwen_unhandled_vector + 1",
+        ".endr",
+        first = const FIRST_UNHANDLED_VECTOR,
+        count = const 256 - FIRST_UNHANDLED_VECTOR as usize,
+        common = sym unhandled_common_entry,
+    );
+}
+
+/// 跳板的公共部分：栈上已经有错误码和向量号，其余和 `exception_entry_returning` 相同
+#[unsafe(naked)]
+extern "C" fn unhandled_common_entry() {
+    core::arch::naked_asm!(
+        "push rax",
+        "push rbx",
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push rbp",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "cld",
+        "mov rdi, rsp",
+        "call {handler}",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rbp",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop rcx",
+        "pop rbx",
+        "pop rax",
+        "add rsp, 16",
+        "iretq",
+        handler = sym unhandled_interrupt_handler,
+    );
+}
+
+/// `vector` 号向量的跳板地址
+fn unhandled_entry_addr(vector: u8) -> VirtAddr {
+    // 跳板从函数起点向上对齐到 16 字节的位置开始
+    let first = returning_entry_addr(unhandled_stubs)
+        .as_u64()
+        .next_multiple_of(UNHANDLED_STUB_SIZE);
+    VirtAddr::new(first + u64::from(vector - FIRST_UNHANDLED_VECTOR) * UNHANDLED_STUB_SIZE)
+}
+
+/// 没有注册处理器的向量的处理函数
+///
+/// 按 1、2、4、8……次报告，同一个向量反复触发时日志量按对数增长，不会刷屏；
+/// 属于 PIC 的向量只在 ISR 中对应的位置位时发送 EOI（软件 `int` 不经过 PIC）
+extern "C" fn unhandled_interrupt_handler(regs: &mut Registers) {
+    let vector = regs.vector as u8;
+    record(vector);
+    let total = count(vector);
+    if total.is_power_of_two() {
+        serial_println!(
+            "[WARN] Unhandled interrupt vector {:#04x} ({}) at {}, {} so far",
+            vector,
+            vector,
+            Symbolized::new(regs.rip),
+            total
+        );
+    }
+
+    let irq = vector.wrapping_sub(pic::PIC_1_OFFSET);
+    if irq < 16 && pic::read_isr() & (1 << irq) != 0 {
+        pic::end_of_interrupt(irq);
+    }
+}
+
+/// 汇编入口的地址，用于填写 IDT
+fn entry_addr(entry: extern "C" fn() -> !) -> VirtAddr {
+    VirtAddr::new(entry as usize as u64)
+}
+
+/// 会返回的汇编入口的地址，用于填写 IDT
+fn returning_entry_addr(entry: extern "C" fn()) -> VirtAddr {
+    VirtAddr::new(entry as usize as u64)
+}
+
+// =============================================================================
+// 符号解析
+// =============================================================================
+
+/// 把地址解析为（符号名，地址相对符号起点的偏移）；不认识的地址返回 `None`
+pub type SymbolResolver = fn(u64) -> Option<(&'static str, u64)>;
+
+/// 注册的符号解析函数，空指针表示没有注册
+///
+/// 用原子指针而不是锁：异常处理函数随时可能读取它，不能等一把可能永远不释放的锁
+static SYMBOL_RESOLVER: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+/// 注册符号解析函数，之后异常处理函数输出的 RIP 会带上 `(符号+偏移)`
+///
+/// 内核本身不解析 ELF 符号表，解析函数由使用者提供（例如查一张编译时生成的表）
+#[allow(dead_code)]
+pub fn set_symbol_resolver(resolver: SymbolResolver) {
+    SYMBOL_RESOLVER.store(resolver as *mut (), Ordering::SeqCst);
+}
+
+/// 取消注册的符号解析函数
+#[allow(dead_code)]
+pub fn clear_symbol_resolver() {
+    SYMBOL_RESOLVER.store(core::ptr::null_mut(), Ordering::SeqCst);
+}
+
+/// 用注册的解析函数查找地址所在的符号，没有注册时返回 `None`
+pub fn symbolize(addr: u64) -> Option<(&'static str, u64)> {
+    let resolver = SYMBOL_RESOLVER.load(Ordering::SeqCst);
+    if resolver.is_null() {
+        return None;
+    }
+    // SAFETY: 非空的值只能由 set_symbol_resolver 从 SymbolResolver 转换而来
+    let resolver = unsafe { core::mem::transmute::<*mut (), SymbolResolver>(resolver) };
+    resolver(addr)
+}
+
+/// 带符号的地址：`0x00000000deadbeef (kernel_main+0x1c)`，解析不出符号时只有地址
+///
+/// 符号在 `new` 中就解析好：格式化时往往已经持有 `SERIAL1` 的锁，
+/// 这时再调用使用者提供的解析函数，它一打印就会死锁
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symbolized {
+    addr: u64,
+    symbol: Option<(&'static str, u64)>,
+}
+
+impl Symbolized {
+    /// 解析 `addr` 所在的符号
+    pub fn new(addr: u64) -> Self {
+        Symbolized {
+            addr,
+            symbol: symbolize(addr),
+        }
+    }
+}
+
+impl fmt::Display for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#018x}", self.addr)?;
+        if let Some((name, offset)) = self.symbol {
+            write!(f, " ({}+{:#x})", name, offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// 有符号时在串口输出 ` (符号+0x偏移)`，不经过 `core::fmt`
+///
+/// `symbol` 必须在获取 `SERIAL1` 的锁之前用 `symbolize` 解析好
+fn write_symbol_suffix(serial: &serial::SerialWriter, symbol: Option<(&'static str, u64)>) {
+    if let Some((name, offset)) = symbol {
+        serial.write_string(" (");
+        serial.write_string(name);
+        serial.write_string("+0x");
+        serial.write_hex(offset, 1);
+        serial.write_byte(b')');
+    }
+}
+
+/// 解析得出符号时输出一行 `  Symbol: 0x... (符号+偏移)`，补在寄存器转储后面
+fn log_symbol(rip: u64) {
+    let symbolized = Symbolized::new(rip);
+    if symbolized.symbol.is_some() {
+        serial_println!("  Symbol:              {}", symbolized);
+    }
+}
+
+// =============================================================================
+// 栈帧摘要
+// =============================================================================
+
+/// CPU 在中断时压入的栈帧，展开成普通的整数字段，方便输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSummary {
+    /// 被中断指令的地址
+    pub rip: u64,
+    /// 代码段选择子
+    pub cs: u64,
+    /// 标志寄存器
+    pub rflags: u64,
+    /// 被中断时的栈指针
+    pub rsp: u64,
+    /// 栈段选择子
+    pub ss: u64,
+}
+
+impl FrameSummary {
+    /// 从 `x86-interrupt` 处理函数的栈帧中取出各个字段
+    pub fn from_frame(frame: &InterruptStackFrame) -> Self {
+        FrameSummary {
+            rip: frame.instruction_pointer.as_u64(),
+            cs: frame.code_segment,
+            rflags: frame.cpu_flags,
+            rsp: frame.stack_pointer.as_u64(),
+            ss: frame.stack_segment,
+        }
+    }
+
+    /// 从汇编入口保存的寄存器中取出栈帧字段
+    pub fn from_registers(regs: &Registers) -> Self {
+        FrameSummary {
+            rip: regs.rip,
+            cs: regs.cs,
+            rflags: regs.rflags,
+            rsp: regs.rsp,
+            ss: regs.ss,
+        }
+    }
+
+    /// 把栈帧输出到串口，每个字段一行
+    ///
+    /// 手工转换数字，不经过 `core::fmt`，在异常处理函数里也不会 panic
+    pub fn log_serial(&self) {
+        let fields = [
+            ("  Code Segment:        0x", self.cs, 4),
+            ("  CPU Flags:           0x", self.rflags, 8),
+            ("  Stack Pointer:       0x", self.rsp, 16),
+            ("  Stack Segment:       0x", self.ss, 4),
+        ];
+        let symbol = symbolize(self.rip);
+        let serial = serial::SERIAL1.lock();
+        serial.write_string("  Instruction Pointer: 0x");
+        serial.write_hex(self.rip, 16);
+        write_symbol_suffix(&serial, symbol);
+        serial.write_byte(b'\n');
+        for (label, value, width) in fields {
+            serial.write_string(label);
+            serial.write_hex(value, width);
+            serial.write_byte(b'\n');
+        }
+    }
+}
+
 // =============================================================================
 // 异常处理函数
 // =============================================================================
 
+// DR6 中的状态位
+const DR6_BREAKPOINTS: u64 = 0x0F; // 位 0~3：命中了 DR0~DR3 中的哪个硬件断点
+const DR6_SINGLE_STEP: u64 = 1 << 14; // BS：单步执行（RFLAGS.TF）
+
+/// 调试异常的原因，由 DR6 解码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugCause {
+    /// 命中的硬件断点：位 n 置位表示 DRn
+    pub breakpoints: u8,
+    /// 单步执行触发
+    pub single_step: bool,
+}
+
+impl DebugCause {
+    /// 解码 DR6 的值
+    pub const fn from_dr6(dr6: u64) -> Self {
+        DebugCause {
+            breakpoints: (dr6 & DR6_BREAKPOINTS) as u8,
+            single_step: dr6 & DR6_SINGLE_STEP != 0,
+        }
+    }
+}
+
+/// 例如 `single-step`、`hardware breakpoint 0 2`；都没有置位时是 `unknown`
+impl fmt::Display for DebugCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.single_step {
+            write!(f, "single-step")?;
+        }
+        if self.breakpoints != 0 {
+            if self.single_step {
+                write!(f, ", ")?;
+            }
+            write!(f, "hardware breakpoint")?;
+            for n in 0..4 {
+                if self.breakpoints & (1 << n) != 0 {
+                    write!(f, " {}", n)?;
+                }
+            }
+        }
+        if !self.single_step && self.breakpoints == 0 {
+            write!(f, "unknown")?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取 DR6（调试状态寄存器）
+fn read_dr6() -> u64 {
+    let value: u64;
+    // SAFETY: 内核态读取调试寄存器没有副作用
+    unsafe {
+        core::arch::asm!("mov {}, dr6", out(reg) value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// 清除 DR6 的状态位
+///
+/// CPU 只会置位、不会清除这些位，不清除的话下一次调试异常看到的是累积的结果
+fn clear_dr6() {
+    // SAFETY: 写入 0 只清除状态位，保留位由 CPU 忽略
+    unsafe {
+        core::arch::asm!("mov dr6, {}", in(reg) 0u64, options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// 调试异常处理器（中断号 1）
+///
+/// RFLAGS.TF 置位时每执行一条指令触发一次（单步执行），硬件断点（DR0~DR3）命中时也会触发；
+/// 读 DR6 判断原因后清除它。单步和执行断点都是陷阱，返回后继续执行。
+/// 启用了 GDB 调试桩时交给调试桩，否则清除 TF 后返回，避免每条指令都陷入
+extern "C" fn debug_handler(regs: &mut Registers) {
+    record(DEBUG_VECTOR);
+    let cause = DebugCause::from_dr6(read_dr6());
+    clear_dr6();
+    if gdbstub::is_enabled() {
+        gdbstub::handle_exception(regs);
+        return;
+    }
+    regs.rflags &= !RFLAGS_TF;
+    serial_println!(
+        "[WARN] Debug exception ({}) at RIP={}",
+        cause,
+        Symbolized::new(regs.rip)
+    );
+}
+
 /// 断点异常处理器（中断号 3）
 ///
 /// 当 CPU 执行 `int3` 指令时触发
-/// 这是调试器使用的断点机制
+/// 这是调试器使用的断点机制；启用了 GDB 调试桩时交给调试桩处理
 ///
 /// # 参数
-/// - `stack_frame`: 包含中断发生时的 CPU 状态
-extern "x86-interrupt" fn breakpoint_handler(_stack_frame: InterruptStackFrame) {
+/// - `regs`: 汇编入口保存的寄存器，返回时写回
+extern "C" fn breakpoint_handler(regs: &mut Registers) {
+    record(BREAKPOINT_VECTOR);
+    if gdbstub::is_enabled() {
+        gdbstub::handle_exception(regs);
+        return;
+    }
+
     serial::write_line("");
     serial::write_line("===========================================");
     serial::write_line("  EXCEPTION: Breakpoint (int3)");
     serial::write_line("===========================================");
     serial::write_line("");
 
-    // 打印指令指针（发生中断的位置）
-    serial::write_string("  Instruction Pointer: ");
-    // 注意：这里简化处理，实际应该格式化打印地址
-    serial::write_line("(see QEMU for details)");
+    // 打印发生中断的位置和当时的栈
+    FrameSummary::from_registers(regs).log_serial();
 
     serial::write_line("");
     serial::write_line("  Breakpoint handled, continuing...");
@@ -74,16 +772,76 @@ extern "x86-interrupt" fn breakpoint_handler(_stack_frame: InterruptStackFrame)
     serial::write_line("");
 }
 
+/// 溢出异常处理器（中断号 4）
+///
+/// 由 `int 4` 触发（64 位模式没有 `into` 指令）；这是陷阱，记录后返回，
+/// 从触发指令的下一条继续执行
+extern "x86-interrupt" fn overflow_handler(stack_frame: InterruptStackFrame) {
+    record(OVERFLOW_VECTOR);
+    log_recoverable("Overflow", stack_frame.instruction_pointer.as_u64());
+}
+
+/// 越界异常处理器（中断号 5）
+///
+/// 由 `int 5` 触发（64 位模式没有 `bound` 指令）。#BR 本身是故障，
+/// 但软件中断的 RIP 已经指向下一条指令，所以记录后可以安全返回
+extern "x86-interrupt" fn bound_range_exceeded_handler(stack_frame: InterruptStackFrame) {
+    record(BOUND_RANGE_VECTOR);
+    log_recoverable(
+        "BOUND range exceeded",
+        stack_frame.instruction_pointer.as_u64(),
+    );
+}
+
+/// 把可恢复异常记录为一行串口日志：`[WARN] <name> at RIP=0x...`
+///
+/// 手工转换数字，不经过 `core::fmt`
+fn log_recoverable(name: &str, rip: u64) {
+    let symbol = symbolize(rip);
+    let serial = serial::SERIAL1.lock();
+    serial.write_string("[WARN] ");
+    serial.write_string(name);
+    serial.write_string(" at RIP=0x");
+    serial.write_hex(rip, 16);
+    write_symbol_suffix(&serial, symbol);
+    serial.write_byte(b'\n');
+}
+
+/// 是否已经在处理双重故障（用于检测重入）
+static IN_DOUBLE_FAULT: AtomicBool = AtomicBool::new(false);
+
+/// 双重故障处理函数打印完信息后写入 QEMU 退出设备的值，0 表示不退出
+///
+/// 单元测试中默认以失败退出；集成测试可以通过 `set_double_fault_exit` 改成期望的结果
+static DOUBLE_FAULT_EXIT: AtomicU32 = AtomicU32::new(if cfg!(test) {
+    QemuExitCode::Failed as u32
+} else {
+    0
+});
+
+/// 让双重故障处理函数在打印完信息后以指定退出码退出 QEMU
+///
+/// 供故意触发双重故障的测试使用：处理函数确实运行了，QEMU 才会以这个退出码退出
+pub fn set_double_fault_exit(exit_code: QemuExitCode) {
+    DOUBLE_FAULT_EXIT.store(exit_code as u32, Ordering::SeqCst);
+}
+
 /// 双重故障异常处理器（中断号 8）
 ///
 /// 当处理一个异常时又发生异常，就会触发双重故障
 /// 这通常意味着内核有严重 bug
 ///
 /// 注意：双重故障是"发散"的（diverging），不能返回
-extern "x86-interrupt" fn double_fault_handler(
-    _stack_frame: InterruptStackFrame,
-    _error_code: u64,
-) -> ! {
+///
+/// 如果打印过程中再次出错（例如栈已经损坏），会重新进入这个处理函数；
+/// 这时不再打印，直接停机，避免继续出错演变成三重故障（CPU 复位）
+extern "C" fn double_fault_handler(regs: &Registers) -> ! {
+    record(DOUBLE_FAULT_VECTOR);
+
+    if IN_DOUBLE_FAULT.swap(true, Ordering::SeqCst) {
+        cpu::hlt_loop();
+    }
+
     serial::write_line("");
     serial::write_line("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
     serial::write_line("  EXCEPTION: Double Fault!");
@@ -91,29 +849,278 @@ extern "x86-interrupt" fn double_fault_handler(
     serial::write_line("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
     serial::write_line("");
 
+    // 错误码对双重故障总是 0，仍然打印出来便于确认
+    serial_println!("  Error Code:          {:#x}", regs.error_code);
+    regs.dump_serial();
+    log_symbol(regs.rip);
+    serial::write_line("");
+
+    // 测试中发生双重故障：按测试设置的结果退出 QEMU
+    if let Some(exit_code) = QemuExitCode::from_u32(DOUBLE_FAULT_EXIT.load(Ordering::SeqCst)) {
+        qemu::exit_qemu(exit_code);
+    }
+
     // 双重故障无法恢复，进入无限循环
-    loop {
-        x86_64::instructions::hlt();
+    cpu::hlt_loop();
+}
+
+/// 无效 TSS 异常处理器（中断号 10）
+///
+/// 任务切换或加载 TSS 时，TSS 描述符或其中的段选择子无效
+/// 错误码是出错的段选择子
+extern "C" fn invalid_tss_handler(regs: &Registers) -> ! {
+    record(INVALID_TSS_VECTOR);
+    report_fault("Invalid TSS", regs, true);
+    cpu::hlt_loop();
+}
+
+/// 段不存在异常处理器（中断号 11）
+///
+/// 加载了一个 Present 位为 0 的段描述符（或门描述符）
+/// 错误码是出错的段选择子
+extern "C" fn segment_not_present_handler(regs: &Registers) -> ! {
+    record(SEGMENT_NOT_PRESENT_VECTOR);
+    report_fault("Segment Not Present", regs, true);
+    cpu::hlt_loop();
+}
+
+/// 对齐检查异常处理器（中断号 17）
+///
+/// 只有在 CR0.AM 和 RFLAGS.AC 都置位时，用户态的未对齐访问才会触发
+/// 错误码总是 0
+extern "C" fn alignment_check_handler(regs: &Registers) -> ! {
+    record(ALIGNMENT_CHECK_VECTOR);
+    report_fault("Alignment Check", regs, false);
+    cpu::hlt_loop();
+}
+
+// 机器检查架构（MCA）的 MSR
+const IA32_MCG_CAP: u32 = 0x179; // 位 0~7：错误报告寄存器组（bank）的数量
+const IA32_MCG_STATUS: u32 = 0x17A; // 位 0 RIPV：RIP 可以用来重新开始；位 2 MCIP：正在处理机器检查
+const IA32_MC0_STATUS: u32 = 0x401; // 第 n 组的 STATUS 是 0x401 + 4n，ADDR 是 0x402 + 4n
+
+// MCi_STATUS 中的标志位
+const MCI_STATUS_VAL: u64 = 1 << 63; // 这一组记录了有效的错误
+const MCI_STATUS_ADDRV: u64 = 1 << 58; // MCi_ADDR 中有出错的地址
+
+/// 机器检查处理器（中断号 18）
+///
+/// CPU 或芯片组检测到无法纠正的硬件错误（内存 ECC、总线错误等）时触发；
+/// 这是中止，现场通常已经损坏：尽量输出 MCA 寄存器和通用寄存器，然后停机
+extern "C" fn machine_check_handler(regs: &Registers) -> ! {
+    record(MACHINE_CHECK_VECTOR);
+    report_fault("Machine Check", regs, false);
+
+    if cpuid::has_feature(cpuid::Feature::Mca) {
+        // SAFETY: CPU 支持 MCA，这些 MSR 都存在
+        let (cap, status) = unsafe { (cpu::rdmsr(IA32_MCG_CAP), cpu::rdmsr(IA32_MCG_STATUS)) };
+        serial_println!("  MCG_STATUS:          {:#x}", status);
+        for bank in 0..(cap & 0xFF) as u32 {
+            let status_msr = IA32_MC0_STATUS + 4 * bank;
+            // SAFETY: 组号小于 MCG_CAP 报告的数量
+            let bank_status = unsafe { cpu::rdmsr(status_msr) };
+            if bank_status & MCI_STATUS_VAL == 0 {
+                continue;
+            }
+            serial_println!("  MC{}_STATUS:          {:#018x}", bank, bank_status);
+            if bank_status & MCI_STATUS_ADDRV != 0 {
+                // SAFETY: ADDRV 置位时 MCi_ADDR 存在
+                let addr = unsafe { cpu::rdmsr(status_msr + 1) };
+                serial_println!("  MC{}_ADDR:            {:#018x}", bank, addr);
+            }
+        }
+    } else {
+        serial::write_line("  (CPU does not support machine check architecture)");
+    }
+
+    cpu::hlt_loop();
+}
+
+/// 把不可恢复异常的名称、错误码和全部寄存器输出到串口
+///
+/// # 参数
+/// - `name`: 异常名称
+/// - `regs`: 汇编入口保存的寄存器（含错误码和栈帧）
+/// - `is_selector`: 错误码是否是段选择子错误码（需要解码）
+fn report_fault(name: &str, regs: &Registers, is_selector: bool) {
+    serial::write_line("");
+    serial::write_line("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+    serial_println!("  EXCEPTION: {}", name);
+    serial::write_line("!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!");
+    serial::write_line("");
+
+    serial_println!("  Error Code:          {:#x}", regs.error_code);
+    if is_selector {
+        print_selector_error(regs.error_code);
     }
+    regs.dump_serial();
+    log_symbol(regs.rip);
+    serial::write_line("");
+}
+
+/// 解码段选择子错误码
+///
+/// - 位 0（EXT）：异常由外部事件（硬件中断）引起
+/// - 位 1~2（TBL）：描述符所在的表，00 = GDT，01/11 = IDT，10 = LDT
+/// - 位 3~15：描述符在表中的索引
+fn print_selector_error(error_code: u64) {
+    let external = error_code & 0b1 != 0;
+    let table = match (error_code >> 1) & 0b11 {
+        0b00 => "GDT",
+        0b10 => "LDT",
+        _ => "IDT",
+    };
+    let index = (error_code >> 3) & 0x1FFF;
+    serial_println!(
+        "  Selector:            {} index {} (external: {})",
+        table,
+        index,
+        external
+    );
+}
+
+// =============================================================================
+// 硬件中断处理函数
+// =============================================================================
+
+/// 时钟中断处理器（IRQ0）
+///
+/// PIT 每秒触发 TICK_HZ 次，推进 tick 计数和软件光标的闪烁
+extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::Timer.as_u8());
+    timer::tick();
+    vga::on_timer_tick();
+    pic::end_of_interrupt(timer::TIMER_IRQ);
+}
+
+/// 本地 APIC 定时器中断处理器
+///
+/// `apic::init()` 成功后代替 PIT 推进 tick，EOI 发给本地 APIC 而不是 PIC
+extern "x86-interrupt" fn apic_timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::ApicTimer.as_u8());
+    timer::tick();
+    vga::on_timer_tick();
+    apic::end_of_interrupt();
+}
+
+/// 本地 APIC 伪中断处理器
+///
+/// 中断在送达 CPU 前被撤销时产生，只需计数，不能发送 EOI
+extern "x86-interrupt" fn apic_spurious_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::ApicSpurious.as_u8());
+}
+
+/// 键盘中断处理器（IRQ1）
+///
+/// 每次按下或松开按键触发，必须读取扫描码，否则控制器不会再发送新的中断
+extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::Keyboard.as_u8());
+    keyboard::handle_interrupt();
+    pic::end_of_interrupt(keyboard::KEYBOARD_IRQ);
+}
+
+/// COM1 串口中断处理器（IRQ4）
+///
+/// 收到数据或发送保持寄存器空时触发：收到的字节放进接收队列，
+/// 软件缓冲区中待发送的数据继续送进 UART
+extern "x86-interrupt" fn com1_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::Com1.as_u8());
+    serial::handle_interrupt();
+    pic::end_of_interrupt(serial::COM1_IRQ);
+}
+
+/// RTC 周期中断处理器（IRQ8）
+///
+/// 必须读取 RTC 状态寄存器 C，否则不会再有下一个中断；
+/// IRQ8 在从 PIC 上，EOI 要同时发给两片 PIC
+extern "x86-interrupt" fn rtc_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::Rtc.as_u8());
+    cmos::handle_interrupt();
+    pic::end_of_interrupt(cmos::RTC_IRQ);
+}
+
+/// 主 PIC IRQ7 处理器
+///
+/// IRQ7（LPT1）没有驱动，这里收到的几乎都是伪中断；`pic::handle_maybe_spurious`
+/// 读 ISR 判断真假，伪中断不发送 EOI
+extern "x86-interrupt" fn pic_irq7_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::PicSpuriousMaster.as_u8());
+    pic::handle_maybe_spurious(pic::SPURIOUS_MASTER_IRQ);
+}
+
+/// 从 PIC IRQ15 处理器
+///
+/// 伪中断只给主 PIC 发送 EOI（主 PIC 的级联线 IRQ2 确实在服务）
+extern "x86-interrupt" fn pic_irq15_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    record(InterruptIndex::PicSpuriousSlave.as_u8());
+    pic::handle_maybe_spurious(pic::SPURIOUS_SLAVE_IRQ);
 }
 
 // =============================================================================
 // IDT 初始化
 // =============================================================================
 
+/// IDT 是否已经加载
+static IDT_LOADED: AtomicBool = AtomicBool::new(false);
+
+/// IDT 是否已经加载（之后 CPU 异常和中断才有处理函数）
+pub fn is_initialized() -> bool {
+    IDT_LOADED.load(Ordering::SeqCst)
+}
+
 /// 初始化中断描述符表（IDT）
 ///
 /// 这个函数加载预先配置好的 IDT 到 CPU
 /// IDT 的配置在 lazy_static 块中完成
+///
+/// 重复调用只输出警告，不会在运行中重新加载 IDT
 pub fn init() {
-    serial::write_line("[DEBUG] Initializing IDT...");
+    if is_initialized() {
+        serial::write_line("[WARN] interrupts::init called twice, ignored");
+        return;
+    }
+    serial_debug!("Initializing IDT...");
 
     // 加载 IDT 到 CPU
     // lidt 指令告诉 CPU IDT 的位置
     // 由于使用 lazy_static，这里第一次访问 IDT 会触发初始化
     IDT.load();
+    IDT_LOADED.store(true, Ordering::SeqCst);
 
-    serial::write_line("[DEBUG] IDT initialized successfully!");
+    // 打开 CR4.MCE：不打开时机器检查不会进入处理函数，而是直接让 CPU 关机（表现为复位）
+    if cpuid::has_feature(cpuid::Feature::Mce) {
+        // SAFETY: CPU 支持 MCE，#MC 的处理函数已经在 IDT 中
+        unsafe { Cr4::update(|flags| flags.insert(Cr4Flags::MACHINE_CHECK_EXCEPTION)) };
+    }
+
+    serial_debug!("IDT initialized successfully!");
+}
+
+/// 确认 IDT 已加载、PIC 已重映射之后才开启硬件中断（sti）
+///
+/// 任何一步没完成就开中断，第一个时钟中断就会落在空的 IDT 项或 CPU 异常的向量上，
+/// 最终三重故障、机器复位，串口上什么都看不到。检查不通过时在串口说明原因，
+/// 保持关中断并返回 `false`；单元测试中直接以失败退出 QEMU
+pub fn enable_checked() -> bool {
+    let idt_loaded = is_initialized();
+    let pic_remapped = pic::is_initialized();
+    if idt_loaded && pic_remapped {
+        cpu::enable_interrupts();
+        return true;
+    }
+
+    serial_println!(
+        "[WARN] Refusing to enable interrupts: IDT loaded: {}, PIC remapped: {}",
+        idt_loaded,
+        pic_remapped
+    );
+    if cfg!(test) {
+        serial_println!("[failed]");
+        serial_println!();
+        serial_println!("Error: interrupts enabled before the IDT and PIC were ready");
+        qemu::exit_qemu(QemuExitCode::Failed);
+    }
+    false
 }
 
 // =============================================================================
@@ -124,10 +1131,130 @@ pub fn init() {
 ///
 /// 执行 `int3` 指令来测试断点处理器是否正常工作
 pub fn test_breakpoint() {
-    serial::write_line("[DEBUG] Triggering breakpoint exception...");
+    serial_debug!("Triggering breakpoint exception...");
 
     // int3 指令触发断点异常
     x86_64::instructions::interrupts::int3();
 
-    serial::write_line("[DEBUG] Returned from breakpoint exception!");
+    serial_debug!("Returned from breakpoint exception!");
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn disable_guard_restores_interrupts() {
+        assert!(cpu::interrupts_enabled());
+        {
+            let _guard: InterruptGuard = disable_guard();
+            assert!(!cpu::interrupts_enabled());
+        }
+        assert!(cpu::interrupts_enabled());
+    }
+
+    #[test_case]
+    fn second_init_is_ignored() {
+        assert!(is_initialized());
+        init();
+        // IDT 仍然有效：断点异常照常返回
+        let before = count(BREAKPOINT_VECTOR);
+        x86_64::instructions::interrupts::int3();
+        assert_eq!(count(BREAKPOINT_VECTOR), before + 1);
+    }
+
+    #[test_case]
+    fn overflow_and_bound_range_return() {
+        let overflows = count(OVERFLOW_VECTOR);
+        let bounds = count(BOUND_RANGE_VECTOR);
+        // SAFETY: 两个处理函数都只记录日志，然后返回到下一条指令
+        unsafe {
+            core::arch::asm!("int 4");
+            core::arch::asm!("int 5");
+        }
+        assert_eq!(count(OVERFLOW_VECTOR), overflows + 1);
+        assert_eq!(count(BOUND_RANGE_VECTOR), bounds + 1);
+    }
+
+    #[test_case]
+    fn enable_checked_enables_after_init() {
+        cpu::disable_interrupts();
+        assert!(enable_checked());
+        assert!(cpu::interrupts_enabled());
+    }
+
+    #[test_case]
+    fn registered_resolver_names_addresses() {
+        fn resolver(addr: u64) -> Option<(&'static str, u64)> {
+            (0x1000..0x2000)
+                .contains(&addr)
+                .then(|| ("kernel_main", addr - 0x1000))
+        }
+
+        let mut buf = [0u8; 64];
+        let mut text = crate::format::ByteMutWriter::new(&mut buf);
+        set_symbol_resolver(resolver);
+        let _ = fmt::write(
+            &mut text,
+            format_args!("{} {}", Symbolized::new(0x101c), Symbolized::new(0x10)),
+        );
+        clear_symbol_resolver();
+
+        assert_eq!(
+            text.as_str(),
+            "0x000000000000101c (kernel_main+0x1c) 0x0000000000000010"
+        );
+        assert_eq!(symbolize(0x101c), None);
+    }
+
+    #[test_case]
+    fn dr6_decodes_debug_cause() {
+        let step = DebugCause::from_dr6(0xFFFF_0FF0 | DR6_SINGLE_STEP);
+        assert_eq!(
+            step,
+            DebugCause {
+                breakpoints: 0,
+                single_step: true
+            }
+        );
+        let breakpoints = DebugCause::from_dr6(0xFFFF_0FF0 | 0b0101);
+        assert_eq!(breakpoints.breakpoints, 0b0101);
+        assert!(!breakpoints.single_step);
+
+        let mut buf = [0u8; 48];
+        let mut text = crate::format::ByteMutWriter::new(&mut buf);
+        let _ = fmt::write(&mut text, format_args!("{}", breakpoints));
+        assert_eq!(text.as_str(), "hardware breakpoint 0 2");
+    }
+
+    #[test_case]
+    fn single_step_trap_returns() {
+        let before = count(DEBUG_VECTOR);
+        // SAFETY: 置位 TF 后执行一条 nop 触发单步陷阱，处理函数清除 TF 后返回
+        unsafe {
+            core::arch::asm!("pushfq", "or qword ptr [rsp], 0x100", "popfq", "nop");
+        }
+        assert_eq!(count(DEBUG_VECTOR), before + 1);
+    }
+
+    #[test_case]
+    fn unregistered_vector_returns_and_is_counted() {
+        let before = count(0x50);
+        // SAFETY: 0x50 号向量没有注册处理器，兜底入口记录后返回
+        unsafe { core::arch::asm!("int 0x50") };
+        assert_eq!(count(0x50), before + 1);
+    }
+
+    #[test_case]
+    fn software_irq7_is_treated_as_spurious() {
+        // 软件中断不经过 PIC，ISR 中没有 IRQ7：处理函数应当判为伪中断，不发送 EOI
+        let spurious = pic::spurious_count();
+        // SAFETY: 处理函数只读取 ISR 并计数
+        unsafe { core::arch::asm!("int 0x27") };
+        assert_eq!(pic::spurious_count(), spurious + 1);
+    }
 }
@@ -0,0 +1,859 @@
+//! GwenOS PS/2 键盘驱动模块
+//!
+//! 在 IRQ1 中读取 8042 控制器送来的扫描码（默认第 1 套，也可以配置成第 2 套），
+//! 翻译成按键事件（`KeyEvent`）后放入输入队列，
+//! 由主循环通过 `read_event`（或只关心字符的 `read_char`）取出
+//!
+//! PageUp/PageDown 直接在中断里控制 VGA 回滚，不进入输入队列；
+//! Caps Lock 切换时同步更新键盘上的指示灯。
+//! 同时接受键盘和串口终端输入的 `read_line` 在 `input` 模块中
+
+use crate::sync::IrqMutex;
+use crate::{pic, ps2, serial_println, vga};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// =============================================================================
+// 端口与扫描码定义
+// =============================================================================
+
+/// 键盘使用的 IRQ 线
+pub const KEYBOARD_IRQ: u8 = 1;
+
+// 键盘命令（端口读写和应答见 `ps2` 模块）
+const CMD_SET_LEDS: u8 = 0xED; // 设置指示灯，后跟一个 LED 位掩码
+const RESPONSE_RESEND: u8 = 0xFE; // 键盘要求重发上一个字节
+
+// LED 位掩码
+const LED_SCROLL_LOCK: u8 = 0x01;
+const LED_NUM_LOCK: u8 = 0x02;
+const LED_CAPS_LOCK: u8 = 0x04;
+
+/// 扩展扫描码前缀：下一个字节属于扩展键（方向键、PageUp 等）
+const SCANCODE_EXTENDED: u8 = 0xE0;
+
+/// 断码（松开按键）标志位：断码 = 通码 | 0x80
+const SCANCODE_RELEASE: u8 = 0x80;
+
+/// 第 2 套扫描码的断码前缀：松开按键时先发 0xF0，再发通码
+const SET2_RELEASE_PREFIX: u8 = 0xF0;
+
+// 修饰键的通码
+const SCANCODE_LEFT_SHIFT: u8 = 0x2A;
+const SCANCODE_RIGHT_SHIFT: u8 = 0x36;
+const SCANCODE_CAPS_LOCK: u8 = 0x3A;
+
+// 功能键的通码
+const SCANCODE_F1: u8 = 0x3B; // F1~F10 连续：0x3B~0x44
+const SCANCODE_F10: u8 = 0x44;
+const SCANCODE_F11: u8 = 0x57;
+const SCANCODE_F12: u8 = 0x58;
+
+// 扩展键的通码（前缀 0xE0 之后的字节）
+const SCANCODE_KEYPAD_ENTER: u8 = 0x1C;
+const SCANCODE_UP: u8 = 0x48;
+const SCANCODE_PAGE_UP: u8 = 0x49;
+const SCANCODE_LEFT: u8 = 0x4B;
+const SCANCODE_RIGHT: u8 = 0x4D;
+const SCANCODE_DOWN: u8 = 0x50;
+const SCANCODE_PAGE_DOWN: u8 = 0x51;
+
+/// 第 1 套扫描码到字符的映射表（美式键盘）：(普通, 按住 Shift)
+///
+/// 下标是通码，0 表示该键不产生字符
+#[rustfmt::skip]
+const SCANCODE_MAP: [(u8, u8); 0x3A] = [
+    (0, 0),       (0x1B, 0x1B), (b'1', b'!'), (b'2', b'@'), (b'3', b'#'), (b'4', b'$'), (b'5', b'%'), (b'6', b'^'),
+    (b'7', b'&'), (b'8', b'*'), (b'9', b'('), (b'0', b')'), (b'-', b'_'), (b'=', b'+'), (0x08, 0x08), (b'\t', b'\t'),
+    (b'q', b'Q'), (b'w', b'W'), (b'e', b'E'), (b'r', b'R'), (b't', b'T'), (b'y', b'Y'), (b'u', b'U'), (b'i', b'I'),
+    (b'o', b'O'), (b'p', b'P'), (b'[', b'{'), (b']', b'}'), (b'\n', b'\n'), (0, 0),     (b'a', b'A'), (b's', b'S'),
+    (b'd', b'D'), (b'f', b'F'), (b'g', b'G'), (b'h', b'H'), (b'j', b'J'), (b'k', b'K'), (b'l', b'L'), (b';', b':'),
+    (b'\'', b'"'), (b'`', b'~'), (0, 0),      (b'\\', b'|'), (b'z', b'Z'), (b'x', b'X'), (b'c', b'C'), (b'v', b'V'),
+    (b'b', b'B'), (b'n', b'N'), (b'm', b'M'), (b',', b'<'), (b'.', b'>'), (b'/', b'?'), (0, 0),       (b'*', b'*'),
+    (0, 0),       (b' ', b' '),
+];
+
+/// 把第 2 套的通码换算成第 1 套的通码，之后统一按第 1 套处理
+///
+/// 只覆盖驱动用得到的键（主键盘区、功能键、修饰键和小键盘 `*`），其他键返回 None
+fn set2_to_set1(code: u8) -> Option<u8> {
+    Some(match code {
+        0x76 => 0x01, // Esc
+        0x16 => 0x02, // 1
+        0x1E => 0x03, // 2
+        0x26 => 0x04, // 3
+        0x25 => 0x05, // 4
+        0x2E => 0x06, // 5
+        0x36 => 0x07, // 6
+        0x3D => 0x08, // 7
+        0x3E => 0x09, // 8
+        0x46 => 0x0A, // 9
+        0x45 => 0x0B, // 0
+        0x4E => 0x0C, // -
+        0x55 => 0x0D, // =
+        0x66 => 0x0E, // Backspace
+        0x0D => 0x0F, // Tab
+        0x15 => 0x10, // Q
+        0x1D => 0x11, // W
+        0x24 => 0x12, // E
+        0x2D => 0x13, // R
+        0x2C => 0x14, // T
+        0x35 => 0x15, // Y
+        0x3C => 0x16, // U
+        0x43 => 0x17, // I
+        0x44 => 0x18, // O
+        0x4D => 0x19, // P
+        0x54 => 0x1A, // [
+        0x5B => 0x1B, // ]
+        0x5A => 0x1C, // Enter
+        0x14 => 0x1D, // 左 Ctrl
+        0x1C => 0x1E, // A
+        0x1B => 0x1F, // S
+        0x23 => 0x20, // D
+        0x2B => 0x21, // F
+        0x34 => 0x22, // G
+        0x33 => 0x23, // H
+        0x3B => 0x24, // J
+        0x42 => 0x25, // K
+        0x4B => 0x26, // L
+        0x4C => 0x27, // ;
+        0x52 => 0x28, // '
+        0x0E => 0x29, // `
+        0x12 => SCANCODE_LEFT_SHIFT,
+        0x5D => 0x2B, // \
+        0x1A => 0x2C, // Z
+        0x22 => 0x2D, // X
+        0x21 => 0x2E, // C
+        0x2A => 0x2F, // V
+        0x32 => 0x30, // B
+        0x31 => 0x31, // N
+        0x3A => 0x32, // M
+        0x41 => 0x33, // ,
+        0x49 => 0x34, // .
+        0x4A => 0x35, // /
+        0x59 => SCANCODE_RIGHT_SHIFT,
+        0x7C => 0x37, // 小键盘 *
+        0x11 => 0x38, // 左 Alt
+        0x29 => 0x39, // 空格
+        0x58 => SCANCODE_CAPS_LOCK,
+        0x05 => 0x3B, // F1
+        0x06 => 0x3C, // F2
+        0x04 => 0x3D, // F3
+        0x0C => 0x3E, // F4
+        0x03 => 0x3F, // F5
+        0x0B => 0x40, // F6
+        0x83 => 0x41, // F7
+        0x0A => 0x42, // F8
+        0x01 => 0x43, // F9
+        0x09 => SCANCODE_F10,
+        0x78 => SCANCODE_F11,
+        0x07 => SCANCODE_F12,
+        _ => return None,
+    })
+}
+
+/// 把第 2 套的扩展键（0xE0 之后的字节）换算成第 1 套的扩展键
+fn set2_extended_to_set1(code: u8) -> Option<u8> {
+    Some(match code {
+        0x5A => SCANCODE_KEYPAD_ENTER,
+        0x75 => SCANCODE_UP,
+        0x7D => SCANCODE_PAGE_UP,
+        0x6B => SCANCODE_LEFT,
+        0x74 => SCANCODE_RIGHT,
+        0x72 => SCANCODE_DOWN,
+        0x7A => SCANCODE_PAGE_DOWN,
+        _ => return None,
+    })
+}
+
+// =============================================================================
+// 扫描码与键码
+// =============================================================================
+
+/// 控制器送来的一个扫描码字节
+///
+/// 把前缀和通码/断码的判断集中在这里，驱动的其他部分不直接做位运算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanCode(pub u8);
+
+impl From<u8> for ScanCode {
+    fn from(byte: u8) -> Self {
+        ScanCode(byte)
+    }
+}
+
+impl From<ScanCode> for u8 {
+    fn from(scancode: ScanCode) -> Self {
+        scancode.0
+    }
+}
+
+impl ScanCode {
+    /// 是否是 0xE0 扩展前缀（两套扫描码相同）
+    pub const fn is_extended_prefix(self) -> bool {
+        self.0 == SCANCODE_EXTENDED
+    }
+
+    /// 是否是第 2 套的 0xF0 断码前缀
+    pub const fn is_set2_release_prefix(self) -> bool {
+        self.0 == SET2_RELEASE_PREFIX
+    }
+
+    /// 第 1 套：是否是断码（松开按键，最高位为 1）；扩展前缀不算断码
+    pub const fn is_break(self) -> bool {
+        self.0 & SCANCODE_RELEASE != 0 && !self.is_extended_prefix()
+    }
+
+    /// 第 1 套：是否是通码（按下按键）
+    pub const fn is_make(self) -> bool {
+        self.0 & SCANCODE_RELEASE == 0
+    }
+
+    /// 第 1 套：去掉断码标志位后的通码
+    pub const fn make_code(self) -> ScanCode {
+        ScanCode(self.0 & !SCANCODE_RELEASE)
+    }
+}
+
+/// 驱动认识的按键（由第 1 套通码换算而来）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCode {
+    /// 主键盘区产生字符的键（值为第 1 套通码，即字符映射表的下标）
+    Printable(u8),
+    /// 左 Shift
+    LeftShift,
+    /// 右 Shift
+    RightShift,
+    /// Caps Lock
+    CapsLock,
+    /// 功能键 F1~F12（值为 1~12）
+    Function(u8),
+    /// 小键盘回车（扩展键）
+    KeypadEnter,
+    /// 方向键（扩展键）
+    Arrow(ArrowKey),
+    /// PageUp（扩展键）
+    PageUp,
+    /// PageDown（扩展键）
+    PageDown,
+}
+
+/// 没有对应 `KeyCode` 的通码（Ctrl、Alt、小键盘等驱动不处理的键）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownScanCode(pub ScanCode);
+
+/// 第 1 套普通键（没有 0xE0 前缀）的通码换算成键码
+impl TryFrom<ScanCode> for KeyCode {
+    type Error = UnknownScanCode;
+
+    fn try_from(code: ScanCode) -> Result<Self, Self::Error> {
+        Ok(match code.0 {
+            SCANCODE_LEFT_SHIFT => KeyCode::LeftShift,
+            SCANCODE_RIGHT_SHIFT => KeyCode::RightShift,
+            SCANCODE_CAPS_LOCK => KeyCode::CapsLock,
+            SCANCODE_F1..=SCANCODE_F10 => KeyCode::Function(code.0 - SCANCODE_F1 + 1),
+            SCANCODE_F11 => KeyCode::Function(11),
+            SCANCODE_F12 => KeyCode::Function(12),
+            byte => match SCANCODE_MAP.get(usize::from(byte)) {
+                Some(&(normal, _)) if normal != 0 => KeyCode::Printable(byte),
+                _ => return Err(UnknownScanCode(code)),
+            },
+        })
+    }
+}
+
+impl KeyCode {
+    /// 第 1 套扩展键（0xE0 之后的通码）换算成键码
+    ///
+    /// 其他扩展键（右 Ctrl/Alt、PrintScreen 的假 Shift 等）返回 None
+    pub const fn extended(code: ScanCode) -> Option<KeyCode> {
+        Some(match code.0 {
+            SCANCODE_KEYPAD_ENTER => KeyCode::KeypadEnter,
+            SCANCODE_UP => KeyCode::Arrow(ArrowKey::Up),
+            SCANCODE_DOWN => KeyCode::Arrow(ArrowKey::Down),
+            SCANCODE_LEFT => KeyCode::Arrow(ArrowKey::Left),
+            SCANCODE_RIGHT => KeyCode::Arrow(ArrowKey::Right),
+            SCANCODE_PAGE_UP => KeyCode::PageUp,
+            SCANCODE_PAGE_DOWN => KeyCode::PageDown,
+            _ => return None,
+        })
+    }
+}
+
+/// 输入队列容量（事件数）
+const QUEUE_SIZE: usize = 128;
+
+// =============================================================================
+// 键盘配置
+// =============================================================================
+
+/// 键盘发送的扫描码集
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSet {
+    /// 第 1 套：断码 = 通码 | 0x80（8042 默认把键盘的第 2 套翻译成这一套）
+    Set1,
+    /// 第 2 套：断码 = 0xF0 + 通码（需要关闭 8042 的扫描码翻译）
+    Set2,
+}
+
+/// 键盘驱动配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyboardConfig {
+    /// 控制器送来的扫描码集
+    pub scancode_set: ScancodeSet,
+}
+
+impl Default for KeyboardConfig {
+    fn default() -> Self {
+        KeyboardConfig {
+            scancode_set: ScancodeSet::Set1,
+        }
+    }
+}
+
+// =============================================================================
+// 键盘命令
+// =============================================================================
+
+/// 向键盘发送命令时的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardError {
+    /// 等待控制器超时（键盘不存在或没有响应）
+    Timeout,
+    /// 键盘没有回 ACK，而是回了其他字节（如 0xFE 要求重发）
+    NoAck(u8),
+}
+
+impl From<ps2::Ps2Error> for KeyboardError {
+    fn from(e: ps2::Ps2Error) -> Self {
+        match e {
+            ps2::Ps2Error::Timeout => KeyboardError::Timeout,
+            ps2::Ps2Error::SelfTestFailed(byte) | ps2::Ps2Error::DeviceResetFailed(byte) => {
+                KeyboardError::NoAck(byte)
+            }
+        }
+    }
+}
+
+/// 指示灯命令进行到哪一步
+///
+/// 键盘的 ACK 和扫描码一样通过 IRQ1 送来，而 Caps Lock 的指示灯就是在 IRQ1 中更新的，
+/// 不能在中断处理函数里忙等 ACK：先只发送 0xED，之后由 `handle_interrupt`
+/// 把收到的 ACK 交给这里，再发送位掩码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LedCommand {
+    /// 没有进行中的命令
+    Idle,
+    /// 已发送 0xED，收到 ACK 后发送位掩码
+    SentCommand(u8),
+    /// 已发送位掩码，收到 ACK 后命令结束
+    SentMask(u8),
+}
+
+/// 键盘送来的一个字节对指示灯命令的意义
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LedStep {
+    /// 不是命令的应答，按扫描码处理
+    NotResponse,
+    /// 应答已经处理；带字节时接着把它发给键盘
+    Consumed(Option<u8>),
+}
+
+/// 指示灯命令的状态：进行中的命令，以及命令进行期间又请求的新位掩码
+struct Leds {
+    command: LedCommand,
+    queued: Option<u8>,
+}
+
+impl Leds {
+    const fn new() -> Self {
+        Leds {
+            command: LedCommand::Idle,
+            queued: None,
+        }
+    }
+
+    /// 请求设置指示灯，返回现在要发给键盘的字节
+    ///
+    /// 已经有命令在进行时只记下位掩码，等那条命令结束后再发
+    fn request(&mut self, mask: u8) -> Option<u8> {
+        if self.command == LedCommand::Idle {
+            self.command = LedCommand::SentCommand(mask);
+            Some(CMD_SET_LEDS)
+        } else {
+            self.queued = Some(mask);
+            None
+        }
+    }
+
+    /// 处理键盘送来的一个字节
+    fn on_byte(&mut self, byte: u8) -> LedStep {
+        let next = match (self.command, byte) {
+            (LedCommand::Idle, _) => return LedStep::NotResponse,
+            (LedCommand::SentCommand(mask), ps2::DEVICE_ACK) => {
+                self.command = LedCommand::SentMask(mask);
+                Some(mask)
+            }
+            (LedCommand::SentMask(_), ps2::DEVICE_ACK) => {
+                self.command = LedCommand::Idle;
+                self.queued.take().and_then(|mask| self.request(mask))
+            }
+            (LedCommand::SentCommand(_), RESPONSE_RESEND) => Some(CMD_SET_LEDS),
+            (LedCommand::SentMask(mask), RESPONSE_RESEND) => Some(mask),
+            _ => return LedStep::NotResponse,
+        };
+        LedStep::Consumed(next)
+    }
+
+    /// 发送失败时放弃进行中的命令
+    fn abort(&mut self) {
+        self.command = LedCommand::Idle;
+        self.queued = None;
+    }
+}
+
+/// 指示灯命令的全局状态
+static LEDS: IrqMutex<Leds> = IrqMutex::new(Leds::new());
+
+/// 设置键盘上的 Caps Lock、Num Lock 和 Scroll Lock 指示灯
+///
+/// 只发送 0xED 命令，不等待 ACK：ACK 由键盘中断处理函数收下，再由它发送 LED 位掩码，
+/// 所以可以在 IRQ1 中调用。上一条命令还没结束时，新的设置在它结束后发出
+///
+/// # Errors
+/// 控制器一直没有取走要发送的字节（键盘不存在或没有响应）
+pub fn set_leds(caps: bool, num: bool, scroll: bool) -> Result<(), KeyboardError> {
+    let mut mask = 0;
+    if caps {
+        mask |= LED_CAPS_LOCK;
+    }
+    if num {
+        mask |= LED_NUM_LOCK;
+    }
+    if scroll {
+        mask |= LED_SCROLL_LOCK;
+    }
+
+    // 持锁发送：ACK 的中断要等解锁后才能进来，那时状态已经更新
+    let mut leds = LEDS.lock();
+    if let Some(byte) = leds.request(mask)
+        && let Err(e) = ps2::send_to_device(byte)
+    {
+        leds.abort();
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+// =============================================================================
+// 按键事件
+// =============================================================================
+
+/// 方向键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrowKey {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// 一次按键（只记录按下，不记录松开）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    /// 可打印字符（已经应用 Shift/Caps Lock）
+    Char(char),
+    /// 回车（主键盘或小键盘）
+    Enter,
+    /// 退格
+    Backspace,
+    /// 制表键
+    Tab,
+    /// Esc
+    Escape,
+    /// 方向键
+    Arrow(ArrowKey),
+    /// 功能键 F1~F12（值为 1~12）
+    Function(u8),
+}
+
+impl KeyEvent {
+    /// 转换为 ASCII 字符，方向键和功能键没有对应字符
+    ///
+    /// 回车为 `'\n'`，退格为 `'\x08'`，制表键为 `'\t'`，Esc 为 `'\x1b'`
+    pub fn to_char(self) -> Option<char> {
+        match self {
+            KeyEvent::Char(c) => Some(c),
+            KeyEvent::Enter => Some('\n'),
+            KeyEvent::Backspace => Some('\x08'),
+            KeyEvent::Tab => Some('\t'),
+            KeyEvent::Escape => Some('\x1b'),
+            KeyEvent::Arrow(_) | KeyEvent::Function(_) => None,
+        }
+    }
+}
+
+// =============================================================================
+// 键盘状态
+// =============================================================================
+
+/// 键盘驱动状态：修饰键、扩展前缀和输入队列
+struct Keyboard {
+    /// 正在解析的扫描码集
+    scancode_set: ScancodeSet,
+    /// 按住的 Shift 键数量（左右 Shift 分别计数）
+    shift_count: u8,
+    /// Caps Lock 是否打开
+    caps_lock: bool,
+    /// 上一个字节是否是 0xE0 扩展前缀
+    extended: bool,
+    /// 第 2 套：是否已经收到 0xF0 断码前缀，等待后面的通码
+    release_pending: bool,
+    /// 输入队列（环形缓冲区）
+    queue: [KeyEvent; QUEUE_SIZE],
+    /// 队首位置
+    head: usize,
+    /// 队列中的事件数
+    len: usize,
+}
+
+impl Keyboard {
+    const fn new() -> Self {
+        Keyboard {
+            scancode_set: ScancodeSet::Set1,
+            shift_count: 0,
+            caps_lock: false,
+            extended: false,
+            release_pending: false,
+            queue: [KeyEvent::Escape; QUEUE_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// 事件入队，队列满时丢弃
+    fn push(&mut self, event: KeyEvent) {
+        if self.len < QUEUE_SIZE {
+            self.queue[(self.head + self.len) % QUEUE_SIZE] = event;
+            self.len += 1;
+        }
+    }
+
+    /// 取出最早的事件
+    fn pop(&mut self) -> Option<KeyEvent> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.queue[self.head];
+        self.head = (self.head + 1) % QUEUE_SIZE;
+        self.len -= 1;
+        Some(event)
+    }
+
+    /// 处理一个扫描码字节
+    ///
+    /// 按配置的扫描码集解析前缀，第 2 套的按键先换算成第 1 套的通码；
+    /// 返回需要在锁外处理的按键（PageUp/PageDown 回滚、Caps Lock 更新指示灯）
+    fn handle_scancode(&mut self, byte: u8) -> Option<KeyCode> {
+        let scancode = ScanCode::from(byte);
+        match self.scancode_set {
+            ScancodeSet::Set1 => self.handle_set1(scancode),
+            ScancodeSet::Set2 => self.handle_set2(scancode),
+        }
+    }
+
+    /// 解析第 1 套扫描码：0xE0 前缀 + 最高位表示松开
+    fn handle_set1(&mut self, scancode: ScanCode) -> Option<KeyCode> {
+        if scancode.is_extended_prefix() {
+            self.extended = true;
+            return None;
+        }
+
+        // 扩展前缀只作用于紧跟着的一个字节（通码或断码），用完立即清除
+        let extended = core::mem::take(&mut self.extended);
+        self.handle_key(scancode.make_code(), scancode.is_break(), extended)
+    }
+
+    /// 解析第 2 套扫描码：0xE0 扩展前缀和 0xF0 断码前缀（顺序为 E0 F0 xx）都只作用于紧跟着的通码
+    fn handle_set2(&mut self, scancode: ScanCode) -> Option<KeyCode> {
+        if scancode.is_extended_prefix() {
+            self.extended = true;
+            return None;
+        }
+        if scancode.is_set2_release_prefix() {
+            self.release_pending = true;
+            return None;
+        }
+
+        let extended = core::mem::take(&mut self.extended);
+        let released = core::mem::take(&mut self.release_pending);
+        let code = if extended {
+            set2_extended_to_set1(scancode.0)
+        } else {
+            set2_to_set1(scancode.0)
+        }?;
+        self.handle_key(ScanCode(code), released, extended)
+    }
+
+    /// 处理一次按下或松开（`code` 为第 1 套通码）
+    fn handle_key(&mut self, code: ScanCode, released: bool, extended: bool) -> Option<KeyCode> {
+        // 驱动不认识的键（Ctrl、Alt、扩展的假 Shift 等）忽略
+        let key = if extended {
+            KeyCode::extended(code)?
+        } else {
+            KeyCode::try_from(code).ok()?
+        };
+
+        match key {
+            KeyCode::LeftShift | KeyCode::RightShift => {
+                if released {
+                    self.shift_count = self.shift_count.saturating_sub(1);
+                } else {
+                    self.shift_count = self.shift_count.saturating_add(1);
+                }
+            }
+            // 其他键只处理按下
+            _ if released => {}
+            KeyCode::CapsLock => {
+                self.caps_lock = !self.caps_lock;
+                return Some(key);
+            }
+            KeyCode::PageUp | KeyCode::PageDown => return Some(key),
+            KeyCode::Function(n) => self.push(KeyEvent::Function(n)),
+            KeyCode::KeypadEnter => self.push(KeyEvent::Enter),
+            KeyCode::Arrow(arrow) => self.push(KeyEvent::Arrow(arrow)),
+            KeyCode::Printable(code) => {
+                if let Some(event) = self.translate(code) {
+                    self.push(event);
+                }
+            }
+        }
+        None
+    }
+
+    /// 把通码翻译成按键事件
+    ///
+    /// Caps Lock 只影响字母，并且和 Shift 同时按下时相互抵消
+    fn translate(&self, code: u8) -> Option<KeyEvent> {
+        let &(normal, shifted) = SCANCODE_MAP.get(usize::from(code))?;
+        if normal == 0 {
+            return None;
+        }
+        let shift = self.shift_count > 0;
+        let upper = if normal.is_ascii_alphabetic() {
+            shift != self.caps_lock
+        } else {
+            shift
+        };
+        let byte = if upper { shifted } else { normal };
+        Some(match byte {
+            b'\n' => KeyEvent::Enter,
+            0x08 => KeyEvent::Backspace,
+            b'\t' => KeyEvent::Tab,
+            0x1B => KeyEvent::Escape,
+            _ => KeyEvent::Char(char::from(byte)),
+        })
+    }
+}
+
+/// 全局键盘状态（中断处理函数和主循环共享）
+static KEYBOARD: IrqMutex<Keyboard> = IrqMutex::new(Keyboard::new());
+
+/// `init` 是否已经调用过
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 以默认配置（第 1 套扫描码）打开键盘中断
+///
+/// 需要在 `pic::init()` 之后调用
+pub fn init() {
+    init_with_config(KeyboardConfig::default());
+}
+
+/// 按指定配置初始化 PS/2 控制器并打开键盘中断
+///
+/// 第 1 套扫描码由控制器翻译得到，选择第 2 套时关闭控制器的扫描码翻译。
+/// 控制器不存在或初始化失败时在串口报警，不打开 IRQ1（`is_initialized` 仍为 false）。
+/// 重复调用（包括 `init`）只输出警告，不会改变已有的配置
+pub fn init_with_config(config: KeyboardConfig) {
+    if is_initialized() {
+        serial_println!("[WARN] keyboard::init called twice, ignored");
+        return;
+    }
+    if let Err(e) = ps2::init(config.scancode_set == ScancodeSet::Set1) {
+        serial_println!(
+            "[WARN] PS/2 controller unavailable ({:?}), keyboard disabled",
+            e
+        );
+        return;
+    }
+    INITIALIZED.store(true, Ordering::SeqCst);
+    {
+        let mut keyboard = KEYBOARD.lock();
+        keyboard.scancode_set = config.scancode_set;
+        keyboard.extended = false;
+        keyboard.release_pending = false;
+    }
+    pic::unmask(KEYBOARD_IRQ);
+}
+
+/// 键盘中断是否已经打开
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
+
+/// 键盘中断调用：读取并处理一个扫描码
+pub fn handle_interrupt() {
+    let scancode = ps2::read_irq_data();
+
+    // 指示灯命令的应答不是扫描码：收下它，需要时发送命令的下一个字节
+    {
+        let mut leds = LEDS.lock();
+        if let LedStep::Consumed(next) = leds.on_byte(scancode) {
+            if let Some(byte) = next
+                && ps2::send_to_device(byte).is_err()
+            {
+                leds.abort();
+            }
+            return;
+        }
+    }
+    let (key, caps_lock) = {
+        let mut keyboard = KEYBOARD.lock();
+        (keyboard.handle_scancode(scancode), keyboard.caps_lock)
+    };
+
+    match key {
+        // Caps Lock 指示灯跟随状态；没有响应的键盘只是不亮灯
+        Some(KeyCode::CapsLock) => {
+            let _ = set_leds(caps_lock, false, false);
+        }
+        // PageUp/PageDown 控制 VGA 回滚
+        // 被打断的代码可能正持有 WRITER 锁，这时只能丢弃这次按键，不能等待
+        Some(key) => {
+            if let Some(mut writer) = vga::WRITER.try_lock() {
+                let page = writer.page_lines();
+                match key {
+                    KeyCode::PageUp => writer.scroll_back(page),
+                    KeyCode::PageDown => writer.scroll_forward(page),
+                    _ => {}
+                }
+            }
+        }
+        None => {}
+    }
+}
+
+/// 取出一个按键事件，没有输入时返回 None
+pub fn read_event() -> Option<KeyEvent> {
+    KEYBOARD.lock().pop()
+}
+
+/// 取出一个已输入的字符，没有输入时返回 None
+///
+/// 回车返回 `'\n'`，退格返回 `'\x08'`；方向键、功能键等没有字符的事件被跳过
+pub fn read_char() -> Option<char> {
+    while let Some(event) = read_event() {
+        if let Some(c) = event.to_char() {
+            return Some(c);
+        }
+    }
+    None
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn set2_prefixes_pair_with_following_byte() {
+        let mut keyboard = Keyboard::new();
+        keyboard.scancode_set = ScancodeSet::Set2;
+
+        // Shift 按下，A 按下再松开，Shift 松开，然后 A
+        for byte in [0x12, 0x1C, 0xF0, 0x1C, 0xF0, 0x12, 0x1C] {
+            keyboard.handle_scancode(byte);
+        }
+        // 扩展键：上箭头按下再松开（E0 F0 75）
+        for byte in [0xE0, 0x75, 0xE0, 0xF0, 0x75] {
+            keyboard.handle_scancode(byte);
+        }
+        assert_eq!(keyboard.handle_scancode(0xE0), None);
+        assert_eq!(keyboard.handle_scancode(0x7D), Some(KeyCode::PageUp));
+
+        assert_eq!(keyboard.pop(), Some(KeyEvent::Char('A')));
+        assert_eq!(keyboard.pop(), Some(KeyEvent::Char('a')));
+        assert_eq!(keyboard.pop(), Some(KeyEvent::Arrow(ArrowKey::Up)));
+        assert_eq!(keyboard.pop(), None);
+        assert_eq!(keyboard.shift_count, 0);
+    }
+
+    #[test_case]
+    fn set1_make_and_break_decoding() {
+        let make = ScanCode::from(0x2A);
+        let release = ScanCode::from(0xAA);
+        let prefix = ScanCode::from(0xE0);
+
+        assert!(make.is_make() && !make.is_break());
+        assert!(release.is_break() && !release.is_make());
+        assert_eq!(release.make_code(), make);
+        assert!(prefix.is_extended_prefix() && !prefix.is_break() && !prefix.is_make());
+        assert_eq!(u8::from(release.make_code()), SCANCODE_LEFT_SHIFT);
+    }
+
+    #[test_case]
+    fn led_command_is_driven_by_acks() {
+        let mut leds = Leds::new();
+        // 没有命令时 0xFA 不是应答
+        assert_eq!(leds.on_byte(ps2::DEVICE_ACK), LedStep::NotResponse);
+
+        assert_eq!(leds.request(LED_CAPS_LOCK), Some(CMD_SET_LEDS));
+        // 命令进行中再次请求：等这条命令结束
+        assert_eq!(leds.request(0), None);
+        // 命令进行中的扫描码照常处理
+        assert_eq!(leds.on_byte(0x1E), LedStep::NotResponse);
+        assert_eq!(
+            leds.on_byte(ps2::DEVICE_ACK),
+            LedStep::Consumed(Some(LED_CAPS_LOCK))
+        );
+        assert_eq!(
+            leds.on_byte(RESPONSE_RESEND),
+            LedStep::Consumed(Some(LED_CAPS_LOCK))
+        );
+        // 第一条命令结束，立即开始排队的那一条
+        assert_eq!(
+            leds.on_byte(ps2::DEVICE_ACK),
+            LedStep::Consumed(Some(CMD_SET_LEDS))
+        );
+        assert_eq!(leds.on_byte(ps2::DEVICE_ACK), LedStep::Consumed(Some(0)));
+        assert_eq!(leds.on_byte(ps2::DEVICE_ACK), LedStep::Consumed(None));
+        assert_eq!(leds.command, LedCommand::Idle);
+    }
+
+    #[test_case]
+    fn scancodes_convert_to_key_codes() {
+        assert_eq!(KeyCode::try_from(ScanCode(0x2A)), Ok(KeyCode::LeftShift));
+        assert_eq!(
+            KeyCode::try_from(ScanCode(0x1E)),
+            Ok(KeyCode::Printable(0x1E))
+        );
+        assert_eq!(KeyCode::try_from(ScanCode(0x44)), Ok(KeyCode::Function(10)));
+        // 左 Ctrl 没有对应的键码
+        assert_eq!(
+            KeyCode::try_from(ScanCode(0x1D)),
+            Err(UnknownScanCode(ScanCode(0x1D)))
+        );
+        // 同一个通码加上扩展前缀是另一个键
+        assert_eq!(
+            KeyCode::extended(ScanCode(0x48)),
+            Some(KeyCode::Arrow(ArrowKey::Up))
+        );
+        assert_eq!(KeyCode::extended(ScanCode(0x2A)), None);
+    }
+}
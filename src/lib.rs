@@ -0,0 +1,283 @@
+//! GwenOS 内核库
+//!
+//! 内核的各个模块都在这里声明，内核二进制（`main.rs`）和 `tests/` 下的集成测试
+//! 链接同一份代码；测试框架（`Testable`、`test_runner`）也放在这里供它们共用
+
+#![no_std] // 不链接 Rust 标准库（std），因为标准库依赖操作系统功能
+#![cfg_attr(test, no_main)] // 单元测试时由下面的 _start 作为入口
+#![feature(abi_x86_interrupt)] // 启用 x86 中断调用约定（实验性特性）
+#![feature(alloc_error_handler)] // 自定义堆耗尽时的处理函数
+#![feature(custom_test_frameworks)] // 自定义测试框架（no_std 下无法使用内置的 test crate）
+#![test_runner(crate::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc; // 堆分配（Box、Vec、String 等）
+
+pub mod allocator; // 堆分配器
+pub mod apic; // 本地 APIC 定时器
+pub mod cmos; // CMOS 实时时钟
+pub mod console; // 串口 + VGA 同时输出
+pub mod cpu; // CPU 控制（停机、中断开关）
+pub mod cpuid; // CPU 信息查询
+pub mod format; // 栈上格式化
+pub mod gdbstub; // GDB 远程调试桩
+pub mod input; // 键盘和串口合并的输入
+pub mod interrupts; // 中断处理
+pub mod keyboard; // PS/2 键盘
+pub mod log; // 日志级别
+pub mod memory; // 物理内存布局
+pub mod panic; // 内核 panic 处理
+pub mod pic; // 8259 中断控制器
+pub mod port; // 端口 I/O
+pub mod ps2; // 8042 PS/2 控制器
+pub mod qemu; // QEMU 调试退出
+pub mod ramfs; // 内存文件系统
+pub mod registers; // 异常时的寄存器快照
+pub mod serial; // 串口输出
+pub mod shell; // 内置命令行
+pub mod sync; // 同步原语
+pub mod system; // 重启与关机
+pub mod text_layout; // 文本排版（换行、制表位、滚动）
+pub mod timer; // PIT 定时器
+pub mod vga; // VGA 文本模式输出
+
+use bootloader::BootInfo;
+use core::fmt;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// ============================================================================
+// 内核初始化
+// ============================================================================
+
+/// 时钟看门狗窗口：1 秒内没有任何时钟中断就在串口报警
+const WATCHDOG_TICKS: u64 = timer::TICK_HZ as u64;
+
+/// 安静启动：把日志级别提高到 `Info`，不在串口输出 `[DEBUG]` 调试日志
+///
+/// 在 `init` 之前调用才能关掉初始化过程中的日志；编译时开启 `quiet-boot` 特性等同于
+/// 一开始就调用 `set_quiet_boot(true)`。`set_quiet_boot(false)` 恢复到 `Debug`
+pub fn set_quiet_boot(quiet: bool) {
+    log::set_max_level(if quiet {
+        log::Level::Info
+    } else {
+        log::Level::Debug
+    });
+}
+
+/// 按正确的顺序初始化内核的各个子系统，每一步都在串口输出日志（安静启动时不输出）
+///
+/// # 顺序
+/// 1. 串口：后面每一步都要输出日志（串口不存在时继续启动，只是看不到日志）
+/// 2. IDT：必须在开中断之前加载，否则第一个时钟中断就会导致三重故障
+/// 3. 重映射 PIC，启动 PIT、RTC 周期中断、键盘和串口中断：IRQ 不能落在 CPU 异常的向量上
+/// 4. 开启中断：先检查 IDT 和 PIC 都已就绪
+/// 5. 校准 TSC、启动看门狗：需要时钟中断已经在计时
+/// 6. 物理内存、页表和堆
+/// 7. 本地 APIC 定时器：需要 TSC 和页表；不可用时继续使用 PIT
+///
+/// VGA 不在这里初始化，由调用者决定何时清屏和显示内容
+///
+/// # Panics
+/// 依赖的步骤没有完成（例如开中断时 PIC 还没有重映射），或者堆映射失败
+pub fn init(boot_info: &'static BootInfo) {
+    // 1. 串口
+    let _ = serial::init();
+    serial_debug!("Serial port initialized!");
+    serial_debug!("GwenOS kernel starting...");
+
+    // 打印 CPU 信息，确认 QEMU 模拟的是哪款 CPU
+    serial_debug!(
+        "CPU vendor: {}",
+        cpuid::bytes_as_str(&cpuid::vendor_string())
+    );
+    if let Some(brand) = cpuid::brand_string() {
+        serial_debug!("CPU brand: {}", cpuid::bytes_as_str(&brand));
+    }
+
+    // 2. 中断描述符表
+    interrupts::init();
+
+    // 3. 重映射 PIC，打开各个设备的中断
+    pic::init();
+    timer::init();
+    cmos::enable_periodic_interrupt(cmos::RATE_1024_HZ);
+    keyboard::init();
+    serial::enable_tx_interrupt();
+
+    // 4. 开启硬件中断（后面校准 TSC 要等时钟中断，检查不通过时无法继续启动）
+    assert!(
+        interrupts::enable_checked(),
+        "interrupts enabled before the IDT and PIC were ready"
+    );
+    serial_debug!(
+        "PIC remapped, timer running at {} Hz, RTC at {} Hz",
+        timer::TICK_HZ,
+        cmos::periodic_frequency_hz(cmos::RATE_1024_HZ).unwrap_or(0)
+    );
+
+    // 5. 用 PIT 校准 TSC，之后可以做微秒级的延时和计时
+    cpu::calibrate_tsc();
+    if let Some(khz) = cpu::tsc_khz() {
+        serial_debug!("TSC frequency: {} MHz", khz / 1000);
+    }
+    timer::set_watchdog(WATCHDOG_TICKS);
+
+    // 6. 物理内存布局、页表和堆
+    memory::init(boot_info);
+    serial_debug!(
+        "Memory map: {} regions, {} KiB usable",
+        memory::regions(memory::memory_map()).count(),
+        memory::usable_bytes(memory::memory_map()) / 1024
+    );
+    unsafe { memory::init_paging() };
+    let mut frame_allocator = unsafe { memory::BootInfoFrameAllocator::init(memory::memory_map()) };
+    allocator::init_heap(&mut frame_allocator).expect("heap initialization failed");
+    serial_debug!(
+        "Heap mapped at {:#x} ({} KiB)",
+        allocator::HEAP_START,
+        allocator::HEAP_SIZE / 1024
+    );
+
+    // 7. 有本地 APIC 时改用 APIC 定时器产生时钟中断，否则继续使用 PIT
+    match apic::init(&mut frame_allocator) {
+        Ok(()) => serial_debug!("Local APIC timer running, PIT masked"),
+        Err(e) => serial_debug!("Local APIC timer unavailable ({:?}), using PIT", e),
+    }
+}
+
+// ============================================================================
+// 测试框架
+// ============================================================================
+
+/// 可以被测试运行器执行的测试
+///
+/// 为所有 `Fn()` 实现，运行时在串口打印测试名和结果
+pub trait Testable {
+    fn run(&self);
+}
+
+impl<T: Fn()> Testable for T {
+    fn run(&self) {
+        serial_print!("{}...\t", core::any::type_name::<T>());
+        self();
+        serial_println!("[ok]");
+    }
+}
+
+/// 测试运行器：依次运行所有 `#[test_case]`，全部通过后以成功退出 QEMU
+///
+/// 测试失败时 panic 处理函数会以失败退出，不会回到这里
+pub fn test_runner(tests: &[&dyn Testable]) {
+    serial_println!("Running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    qemu::exit_qemu(qemu::QemuExitCode::Success);
+}
+
+/// 下一次 panic 是否是测试预期的
+static EXPECTED_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// 声明接下来的 panic 是预期的：panic 处理函数会把当前测试记为通过并以成功退出 QEMU
+///
+/// 用于断言某个操作会 panic（类似 `#[should_panic]`）；堆耗尽的处理函数也遵守这个标记。
+/// panic 后无法回到测试运行器，所以这样的测试应该放在单独的集成测试二进制中，
+/// 并且是其中最后一个测试
+pub fn set_expected_panic() {
+    EXPECTED_PANIC.store(true, Ordering::SeqCst);
+}
+
+/// 如果 panic 是预期的：打印 `[ok]` 并以成功退出 QEMU，否则直接返回
+fn exit_if_expected_panic() {
+    if EXPECTED_PANIC.swap(false, Ordering::SeqCst) {
+        serial_println!("[ok]");
+        qemu::exit_qemu(qemu::QemuExitCode::Success);
+        cpu::hlt_loop();
+    }
+}
+
+/// 测试模式的 panic 处理：测试失败，打印原因后以失败退出 QEMU
+///
+/// 调用过 `set_expected_panic` 时改为打印 `[ok]` 并以成功退出；
+/// 各个测试二进制的 `#[panic_handler]` 直接调用它
+pub fn test_panic_handler(info: &PanicInfo) -> ! {
+    exit_if_expected_panic();
+    serial_println!("[failed]");
+    serial_println!();
+    serial_println!("Error: {}", info);
+    qemu::exit_qemu(qemu::QemuExitCode::Failed);
+    cpu::hlt_loop();
+}
+
+/// `serial_assert!` 和 `serial_assert_eq!` 失败时调用：在串口输出失败信息后以失败退出 QEMU
+///
+/// 输出格式固定，方便宿主机脚本解析：
+/// `[failed]`、空行、`Error: assertion failed at 文件:行: 表达式`，之后是可选的详细信息
+#[doc(hidden)]
+pub fn _serial_assert_failed(
+    file: &str,
+    line: u32,
+    expr: &str,
+    details: Option<fmt::Arguments>,
+) -> ! {
+    serial_println!("[failed]");
+    serial_println!();
+    serial_println!("Error: assertion failed at {}:{}: {}", file, line, expr);
+    if let Some(details) = details {
+        serial_println!("{}", details);
+    }
+    qemu::exit_qemu(qemu::QemuExitCode::Failed);
+    cpu::hlt_loop();
+}
+
+/// 测试中的断言：条件不成立时在串口输出文件、行号和表达式，然后以失败退出 QEMU
+///
+/// 和 `assert!` 不同，失败时不经过 panic 处理函数，输出格式固定，便于宿主机解析
+#[macro_export]
+macro_rules! serial_assert {
+    ($cond:expr $(,)?) => {
+        if !$cond {
+            $crate::_serial_assert_failed(file!(), line!(), stringify!($cond), None);
+        }
+    };
+}
+
+/// 测试中的相等断言：不相等时在串口输出文件、行号、两个表达式和它们的值（`Debug` 格式），
+/// 然后以失败退出 QEMU
+#[macro_export]
+macro_rules! serial_assert_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::_serial_assert_failed(
+                        file!(),
+                        line!(),
+                        concat!(stringify!($left), " == ", stringify!($right)),
+                        Some(format_args!("  left: {:?}\n right: {:?}", left, right)),
+                    );
+                }
+            }
+        }
+    };
+}
+
+// ============================================================================
+// 单元测试入口
+// ============================================================================
+
+/// `cargo test --lib` 的入口：和内核一样初始化所有子系统，然后运行测试
+#[cfg(test)]
+#[unsafe(no_mangle)]
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    init(boot_info);
+    test_main();
+    cpu::hlt_loop();
+}
+
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
@@ -0,0 +1,116 @@
+//! GwenOS 日志级别模块
+//!
+//! 记录当前允许输出的最详细日志级别，输出日志的代码先用 `enabled` 检查，
+//! 级别可以在运行时修改（例如串口的 `!loglevel` 命令）
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// =============================================================================
+// 日志级别
+// =============================================================================
+
+/// 日志级别，越往后越详细
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    /// 错误
+    Error = 1,
+    /// 警告
+    Warn = 2,
+    /// 一般信息
+    Info = 3,
+    /// 调试信息
+    Debug = 4,
+}
+
+impl Level {
+    /// 所有级别，从最简略到最详细
+    pub const ALL: [Level; 4] = [Level::Error, Level::Warn, Level::Info, Level::Debug];
+
+    /// 级别的小写名称
+    pub const fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    /// 由小写名称解析级别，不认识的名称返回 None
+    pub fn from_name(name: &[u8]) -> Option<Level> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.name().as_bytes() == name)
+    }
+
+    /// 由 `MAX_LEVEL` 中保存的值还原级别
+    const fn from_u8(value: u8) -> Level {
+        match value {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+/// 启动时的日志级别：开启 `quiet-boot` 特性时为 `Info`，不输出调试日志
+const DEFAULT_LEVEL: Level = if cfg!(feature = "quiet-boot") {
+    Level::Info
+} else {
+    Level::Debug
+};
+
+/// 当前允许输出的最详细级别
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(DEFAULT_LEVEL as u8);
+
+/// 设置允许输出的最详细级别，更详细的日志被丢弃
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// 当前允许输出的最详细级别
+pub fn max_level() -> Level {
+    Level::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+/// 这个级别的日志当前是否应该输出
+pub fn enabled(level: Level) -> bool {
+    level <= max_level()
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn max_level_filters_more_verbose_levels() {
+        let saved = max_level();
+
+        set_max_level(Level::Warn);
+        assert_eq!(max_level(), Level::Warn);
+        assert!(enabled(Level::Error));
+        assert!(enabled(Level::Warn));
+        assert!(!enabled(Level::Info));
+        assert!(!enabled(Level::Debug));
+
+        set_max_level(Level::Debug);
+        assert!(Level::ALL.into_iter().all(enabled));
+
+        set_max_level(saved);
+    }
+
+    #[test_case]
+    fn level_names_round_trip() {
+        for level in Level::ALL {
+            assert_eq!(Level::from_name(level.name().as_bytes()), Some(level));
+        }
+        assert_eq!(Level::from_name(b"DEBUG"), None);
+        assert_eq!(Level::from_name(b"loud"), None);
+    }
+}
@@ -1,69 +1,84 @@
 //! GwenOS - 一个用 Rust 编写的 Mini 操作系统
 //!
-//! 这是内核的入口点文件
+//! 这是内核的入口点文件，各个模块在 `lib.rs` 中声明
 
 #![no_std] // 不链接 Rust 标准库（std），因为标准库依赖操作系统功能
 #![no_main] // 禁用常规的 main 入口点，自定义入口
-#![feature(abi_x86_interrupt)] // 启用 x86 中断调用约定（实验性特性）
-
-// 引入模块
-mod interrupts; // 中断处理
-mod serial; // 串口输出
-mod vga; // VGA 文本模式输出
+#![feature(custom_test_frameworks)] // 自定义测试框架（no_std 下无法使用内置的 test crate）
+#![test_runner(gwen_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
+use bootloader::BootInfo;
 use core::panic::PanicInfo;
+use gwen_os::{cmos, interrupts, println, serial, serial_debug, serial_println, shell, timer, vga};
 
 // ============================================================================
 // 内核入口点
 // ============================================================================
 
-/// 内核入口函数
+// 开启 `entry-point-macro` 特性时由 bootloader 的宏生成 `_start`，
+// 它检查 `kernel_main` 的签名后把 `BootInfo` 原样传进来
+#[cfg(feature = "entry-point-macro")]
+bootloader::entry_point!(kernel_main);
+
+/// 内核入口函数（不使用 `entry_point!` 时的手写版本）
 ///
 /// 这是 bootloader 加载内核后跳转到的第一个函数
 /// 使用 `#[unsafe(no_mangle)]` 确保函数名不被修改，以便链接器能找到它
 /// 使用 `extern "C"` 确保使用 C 调用约定
+///
+/// # 参数
+/// - `boot_info`: bootloader 传入的启动信息（物理内存布局等）
+#[cfg(not(feature = "entry-point-macro"))]
 #[unsafe(no_mangle)]
-pub extern "C" fn _start() -> ! {
-    // =========================================
-    // 1. 初始化串口（用于调试输出）
-    // =========================================
-    serial::init();
-    serial_println!("[DEBUG] Serial port initialized!");
-    serial_println!("[DEBUG] GwenOS kernel starting...");
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    kernel_main(boot_info)
+}
 
+/// 内核主函数：两种入口最终都调用它
+///
+/// `BootInfo` 中的物理内存偏移和内存布局由 `gwen_os::init` 交给 `memory` 模块保存，
+/// 之后页表和帧分配器都从那里取
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
     // =========================================
-    // 2. 初始化中断处理（IDT）
+    // 1. 按顺序初始化串口、中断、时钟、内存和堆
     // =========================================
-    interrupts::init();
+    gwen_os::init(boot_info);
 
     // 测试断点异常
     interrupts::test_breakpoint();
+    serial_debug!("RTC time: {}", cmos::read_time());
+
+    // 测试模式：基础设施初始化完成后运行所有测试，结束后退出 QEMU
+    #[cfg(test)]
+    test_main();
 
     // =========================================
-    // 3. 清空屏幕
+    // 2. 初始化 VGA 并清空屏幕
     // =========================================
+    vga::init();
     vga::clear_screen();
-    serial_println!("[DEBUG] Screen cleared");
+    if !serial::is_initialized() {
+        println!("[WARN] No serial port detected, logging to VGA only");
+    }
+    serial_debug!("Screen cleared");
 
     // =========================================
-    // 4. 在屏幕中央显示欢迎信息
+    // 3. 在屏幕中央显示欢迎信息
     // =========================================
     let welcome = "Hello, GwenOS!";
-    let col = (vga::VGA_WIDTH - welcome.len()) / 2;
-    let row = vga::VGA_HEIGHT / 2;
-
-    // 使用新的 VGA 模块（绿色文字 0x0a）
-    vga::write_string_at(welcome, row, col, 0x0a);
-    serial_println!("[DEBUG] Displayed: {}", welcome);
-
-    // 显示版本信息（灰色文字 0x07）
     let version = "Version 0.1.0 - Made with Rust";
-    let version_col = (vga::VGA_WIDTH - version.len()) / 2;
-    vga::write_string_at(version, row + 2, version_col, 0x07);
-    serial_println!("[DEBUG] Displayed version info");
+    vga::print_box_centered(
+        &[welcome, "", version],
+        vga::ColorCode::LIGHT_GREEN_ON_BLACK,
+    );
+    serial_debug!("Displayed: {}", welcome);
+
+    // 底部状态栏（浅灰底黑字，文字颜色由 set_status_on 按背景自动选择），不会被 println! 的滚动覆盖
+    vga::set_status_on(" GwenOS 0.1.0 | status: running", vga::Color::LightGray);
 
     // =========================================
-    // 5. 演示格式化输出功能
+    // 4. 演示格式化输出功能
     // =========================================
     serial_println!();
     serial_println!("=================================");
@@ -72,68 +87,40 @@ pub extern "C" fn _start() -> ! {
     serial_println!("=================================");
     serial_println!();
 
+    // ANSI 颜色：串口终端直接解释，VGA 上换算为对应颜色
+    serial::ansi::write_mirrored("\x1b[32mANSI colors mirrored to VGA\x1b[0m\n");
+
     // 使用 VGA println 宏测试
     println!(); // 换行
     println!("Kernel loaded successfully!");
     println!("Format test: 0x{:x}", 0xDEADBEEF_u32);
 
-    serial_println!("[DEBUG] Entering main loop...");
+    // 等待几个时钟周期，确认定时器在计时
+    timer::sleep_ms(50);
+    interrupts::dump_stats();
+    serial_debug!("Entering main loop...");
 
     // =========================================
-    // 6. 内核主循环
+    // 5. 内核主循环：运行命令行
+    //    PageUp/PageDown 查看滚出屏幕的历史输出
     // =========================================
-    loop {
-        x86_64_hlt();
-    }
-}
-
-/// 执行 x86_64 的 HLT 指令
-///
-/// HLT 指令让 CPU 暂停执行，直到下一个中断
-/// 这比空循环更节能
-#[inline(always)]
-fn x86_64_hlt() {
-    // 使用内联汇编执行 hlt 指令
-    unsafe {
-        core::arch::asm!("hlt", options(nomem, nostack));
-    }
+    shell::run();
 }
 
 // ============================================================================
 // Panic 处理
 // ============================================================================
 
-/// Panic 处理函数
-///
-/// 当内核发生 panic 时，这个函数会被调用
-/// 由于我们没有操作系统支持，只能将错误信息打印到屏幕和串口
-///
-/// # 参数
-/// - `info`: 包含 panic 信息的结构体
+/// Panic 处理函数：在屏幕和串口上显示 panic 信息后停机
+#[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    // 输出到串口（方便调试）
-    serial_println!();
-    serial_println!("!!! KERNEL PANIC !!!");
-    serial_println!("{}", info);
-
-    // 在屏幕顶部显示红色的 PANIC 信息
-    vga::write_string_at("!!! KERNEL PANIC !!!", 0, 0, 0x4f); // 红底白字
-
-    // 如果有位置信息，显示出来
-    if let Some(location) = info.location() {
-        vga::write_string_at("At: ", 1, 0, 0x0c); // 红色文字
-        vga::write_string_at(location.file(), 1, 4, 0x0c);
-    }
-
-    // 如果有消息，显示出来
-    if let Some(message) = info.message().as_str() {
-        vga::write_string_at("Msg: ", 2, 0, 0x0c);
-        vga::write_string_at(message, 2, 5, 0x0c);
-    }
+    gwen_os::panic::kernel_panic_handler(info)
+}
 
-    // panic 后进入无限循环
-    loop {
-        x86_64_hlt();
-    }
+/// 测试模式的 Panic 处理函数：测试失败，打印原因后以失败退出 QEMU
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    gwen_os::test_panic_handler(info)
 }
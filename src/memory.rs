@@ -0,0 +1,413 @@
+//! GwenOS 内存管理模块
+//!
+//! 解析 bootloader 通过 `BootInfo` 传入的物理内存布局（由 BIOS E820 得到），
+//! 并在可用区域上分配物理页帧，供堆分配器和分页使用
+//!
+//! bootloader 把全部物理内存映射到虚拟地址 `physical_memory_offset` 处，
+//! 内核通过这个映射读写页表（`OffsetPageTable`）
+
+use crate::allocator::{HEAP_SIZE, HEAP_START};
+use crate::serial;
+use bootloader::BootInfo;
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use core::fmt;
+use spin::{Mutex, Once};
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::mapper::{MapToError, TranslateResult};
+use x86_64::structures::paging::{
+    FrameAllocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, PhysFrame, Size4KiB,
+    Translate,
+};
+use x86_64::{PhysAddr, VirtAddr};
+
+/// 物理页帧大小（4 KiB）
+pub const FRAME_SIZE: u64 = 4096;
+
+// =============================================================================
+// 物理内存区域
+// =============================================================================
+
+/// 一段连续的物理内存
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// 起始物理地址
+    pub start: u64,
+    /// 长度（字节）
+    pub len: u64,
+    /// 是否可以自由使用（不是保留区、ACPI 表，也没有被内核或 bootloader 占用）
+    pub usable: bool,
+}
+
+impl MemoryRegion {
+    /// 结束物理地址（不包含）
+    pub fn end(&self) -> u64 {
+        self.start + self.len
+    }
+}
+
+/// bootloader 提供的内存布局，由 `init` 保存
+static MEMORY_MAP: Once<&'static MemoryMap> = Once::new();
+
+/// 物理内存映射的起始虚拟地址，由 `init` 保存
+static PHYSICAL_MEMORY_OFFSET: Once<VirtAddr> = Once::new();
+
+/// 当前页表的映射器，由 `init_paging` 创建
+static MAPPER: Mutex<Option<OffsetPageTable<'static>>> = Mutex::new(None);
+
+// =============================================================================
+// 物理页帧分配器
+// =============================================================================
+
+/// 基于 bootloader 内存布局的物理页帧分配器
+///
+/// 简单的线性（bump）分配：按地址从低到高依次交出可用区域中的 4 KiB 页帧，
+/// 不支持释放。交出的地址严格递增，所以不会重复分配同一个页帧
+pub struct BootInfoFrameAllocator {
+    memory_map: &'static MemoryMap,
+    /// 下一次分配从这个物理地址开始查找
+    next_addr: u64,
+}
+
+impl BootInfoFrameAllocator {
+    /// 创建页帧分配器
+    ///
+    /// # Safety
+    /// 调用者必须保证内存布局是正确的：标记为可用的页帧确实没有被使用。
+    /// 另外只能创建一个分配器，否则不同分配器会交出相同的页帧
+    pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
+        BootInfoFrameAllocator {
+            memory_map,
+            next_addr: 0,
+        }
+    }
+
+    /// 分配下一个可用的物理页帧，可用内存耗尽时返回 None
+    pub fn next_frame(&mut self) -> Option<PhysFrame> {
+        for region in usable_regions(self.memory_map) {
+            // 区域起点可能没有对齐到页帧，向上对齐
+            let start = region
+                .start
+                .max(self.next_addr)
+                .next_multiple_of(FRAME_SIZE);
+            if start + FRAME_SIZE <= region.end() {
+                self.next_addr = start + FRAME_SIZE;
+                return Some(PhysFrame::containing_address(PhysAddr::new(start)));
+            }
+        }
+        None
+    }
+}
+
+/// 实现 x86_64 的 FrameAllocator trait，供页表映射使用
+unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        self.next_frame()
+    }
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 保存 bootloader 传入的内存布局和物理内存映射偏移
+pub fn init(boot_info: &'static BootInfo) {
+    MEMORY_MAP.call_once(|| &boot_info.memory_map);
+    PHYSICAL_MEMORY_OFFSET.call_once(|| VirtAddr::new(boot_info.physical_memory_offset));
+}
+
+/// 获取物理内存映射的起始虚拟地址
+///
+/// # Panics
+/// 在 `init` 之前调用会 panic
+pub fn physical_memory_offset() -> VirtAddr {
+    *PHYSICAL_MEMORY_OFFSET
+        .r#try()
+        .expect("memory::init has not been called")
+}
+
+/// 通过 bootloader 的物理内存映射接管当前的四级页表
+///
+/// 需要在 `init` 之后调用
+///
+/// # Safety
+/// 只能调用一次：重复调用会创建指向同一个页表的多个可变引用
+pub unsafe fn init_paging() {
+    let offset = physical_memory_offset();
+    let level_4_table = unsafe { active_level_4_table(offset) };
+    *MAPPER.lock() = Some(unsafe { OffsetPageTable::new(level_4_table, offset) });
+}
+
+/// 获取 CR3 指向的四级页表（通过物理内存映射访问）
+///
+/// # Safety
+/// `offset` 必须是正确的物理内存映射偏移，并且只能调用一次
+unsafe fn active_level_4_table(offset: VirtAddr) -> &'static mut PageTable {
+    let (level_4_frame, _) = Cr3::read();
+    let virt = offset + level_4_frame.start_address().as_u64();
+    unsafe { &mut *virt.as_mut_ptr() }
+}
+
+/// 为堆映射 `HEAP_START..HEAP_START + HEAP_SIZE` 的虚拟地址
+///
+/// 每个页面分配一个新的物理页帧，标记为存在且可写，并刷新对应的 TLB 项
+///
+/// # 参数
+/// - `frame_allocator`: 提供物理页帧（以及新建页表所需的页帧）
+///
+/// # Panics
+/// 在 `init_paging` 之前调用会 panic
+pub fn map_heap(
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), MapToError<Size4KiB>> {
+    let mut mapper = MAPPER.lock();
+    let mapper = mapper
+        .as_mut()
+        .expect("memory::init_paging has not been called");
+
+    let heap_start = VirtAddr::new(HEAP_START as u64);
+    let heap_end = heap_start + HEAP_SIZE as u64 - 1u64;
+    let pages = Page::range_inclusive(
+        Page::<Size4KiB>::containing_address(heap_start),
+        Page::containing_address(heap_end),
+    );
+
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    for page in pages {
+        let frame = frame_allocator
+            .allocate_frame()
+            .ok_or(MapToError::FrameAllocationFailed)?;
+        unsafe { mapper.map_to(page, frame, flags, frame_allocator)?.flush() };
+    }
+    Ok(())
+}
+
+/// 把一个物理页恒等映射（虚拟地址 = 物理地址），用于访问 MMIO 寄存器
+///
+/// 物理内存映射只覆盖 RAM，本地 APIC 这类设备寄存器需要单独映射；
+/// 这个页已经恒等映射时直接返回成功
+///
+/// # 参数
+/// - `phys`: 要映射的物理地址（向下对齐到页）
+/// - `flags`: 页表项标志，MMIO 通常需要 `NO_CACHE`
+/// - `frame_allocator`: 提供新建页表所需的页帧
+///
+/// # Panics
+/// 在 `init_paging` 之前调用会 panic
+pub fn identity_map(
+    phys: PhysAddr,
+    flags: PageTableFlags,
+    frame_allocator: &mut impl FrameAllocator<Size4KiB>,
+) -> Result<(), MapToError<Size4KiB>> {
+    let mut mapper = MAPPER.lock();
+    let mapper = mapper
+        .as_mut()
+        .expect("memory::init_paging has not been called");
+
+    let frame = PhysFrame::<Size4KiB>::containing_address(phys);
+    match unsafe { mapper.identity_map(frame, flags, frame_allocator) } {
+        Ok(flush) => {
+            flush.flush();
+            Ok(())
+        }
+        Err(MapToError::PageAlreadyMapped(mapped)) if mapped == frame => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// 查询虚拟地址所在页的页表标志，没有映射时返回 None
+///
+/// 页表正被其他代码修改（锁被占用）或 `init_paging` 之前也返回 None，
+/// 所以可以在异常处理函数中调用
+pub fn page_flags(addr: VirtAddr) -> Option<PageTableFlags> {
+    let mapper = MAPPER.try_lock()?;
+    match mapper.as_ref()?.translate(addr) {
+        TranslateResult::Mapped { flags, .. } => Some(flags),
+        _ => None,
+    }
+}
+
+/// 获取 bootloader 提供的内存布局
+///
+/// # Panics
+/// 在 `init` 之前调用会 panic
+pub fn memory_map() -> &'static MemoryMap {
+    MEMORY_MAP
+        .r#try()
+        .expect("memory::init has not been called")
+}
+
+/// 遍历 bootloader 提供的所有物理内存区域
+///
+/// # 参数
+/// - `memory_map`: `BootInfo` 中的内存布局
+pub fn regions(memory_map: &'static MemoryMap) -> impl Iterator<Item = MemoryRegion> {
+    memory_map.iter().map(|region| MemoryRegion {
+        start: region.range.start_addr(),
+        len: region.range.end_addr() - region.range.start_addr(),
+        usable: region.region_type == MemoryRegionType::Usable,
+    })
+}
+
+/// 遍历可用的物理内存区域
+pub fn usable_regions(memory_map: &'static MemoryMap) -> impl Iterator<Item = MemoryRegion> {
+    regions(memory_map).filter(|region| region.usable)
+}
+
+/// 可用物理内存的总字节数
+pub fn usable_bytes(memory_map: &'static MemoryMap) -> u64 {
+    usable_regions(memory_map).map(|region| region.len).sum()
+}
+
+// =============================================================================
+// 内存读写调试（peek / poke）
+// =============================================================================
+
+/// `peek` 一次最多转储的字节数
+pub const MAX_PEEK_LEN: usize = 4096;
+
+/// 调试读写的地址检查失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessError {
+    /// 空指针
+    Null,
+    /// 不是规范地址（第 48~63 位不是第 47 位的符号扩展），或者范围越过了地址空间末尾
+    NonCanonical(u64),
+    /// 所在页没有映射（或者页表还没有初始化）
+    Unmapped(u64),
+    /// 所在页只读，不能写入
+    ReadOnly(u64),
+    /// 长度超过 `MAX_PEEK_LEN`
+    TooLong(usize),
+}
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessError::Null => write!(f, "null address"),
+            AccessError::NonCanonical(addr) => write!(f, "{:#x} is not canonical", addr),
+            AccessError::Unmapped(addr) => write!(f, "{:#x} is not mapped", addr),
+            AccessError::ReadOnly(addr) => write!(f, "{:#x} is read-only", addr),
+            AccessError::TooLong(len) => {
+                write!(f, "{} bytes is more than {}", len, MAX_PEEK_LEN)
+            }
+        }
+    }
+}
+
+/// 检查 `[addr, addr + len)` 能否安全访问：规范地址、全部已映射，写入时还要求可写
+///
+/// 访问未映射的地址会触发缺页，而内核没有缺页处理函数，所以调试读写前必须检查；
+/// 不获取页表锁，可以在异常处理函数中调用（锁被占用时当作未映射）
+///
+/// # Errors
+/// 返回第一个不能访问的原因和地址
+pub fn check_access(addr: u64, len: usize, write: bool) -> Result<(), AccessError> {
+    if addr == 0 {
+        return Err(AccessError::Null);
+    }
+    if len == 0 {
+        return Ok(());
+    }
+    let last = addr
+        .checked_add(len as u64 - 1)
+        .ok_or(AccessError::NonCanonical(addr))?;
+    let first = VirtAddr::try_new(addr).map_err(|_| AccessError::NonCanonical(addr))?;
+    let last = VirtAddr::try_new(last).map_err(|_| AccessError::NonCanonical(last))?;
+
+    let mut page = first.align_down(FRAME_SIZE);
+    while page <= last {
+        match page_flags(page) {
+            Some(flags) if write && !flags.contains(PageTableFlags::WRITABLE) => {
+                return Err(AccessError::ReadOnly(page.as_u64().max(addr)));
+            }
+            Some(_) => {}
+            None => return Err(AccessError::Unmapped(page.as_u64().max(addr))),
+        }
+        page += FRAME_SIZE;
+    }
+    Ok(())
+}
+
+/// 检查地址后，以十六进制转储的格式把 `[addr, addr + len)` 输出到串口
+///
+/// 读取使用 volatile，可以查看 MMIO 寄存器；长度不能超过 `MAX_PEEK_LEN`
+///
+/// # Errors
+/// 地址为空、不是规范地址、没有映射，或者长度太大
+pub fn peek(addr: u64, len: usize) -> Result<(), AccessError> {
+    if len > MAX_PEEK_LEN {
+        return Err(AccessError::TooLong(len));
+    }
+    check_access(addr, len, false)?;
+    unsafe { serial::hex_dump(addr as usize, len) };
+    Ok(())
+}
+
+/// 检查地址后向 `addr` 写入一个字节（volatile 写入）
+///
+/// 只检查地址能否访问，不检查写入的意义：改写内核自己的代码或数据结构后果自负
+///
+/// # Errors
+/// 地址为空、不是规范地址、没有映射，或者所在页只读
+pub fn poke(addr: u64, value: u8) -> Result<(), AccessError> {
+    check_access(addr, 1, true)?;
+    unsafe { core::ptr::write_volatile(addr as *mut u8, value) };
+    Ok(())
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn frame_allocator_returns_aligned_distinct_usable_frames() {
+        let memory_map = memory_map();
+        let mut allocator = unsafe { BootInfoFrameAllocator::init(memory_map) };
+
+        let mut previous: Option<u64> = None;
+        for _ in 0..4096 {
+            let frame = allocator.next_frame().expect("ran out of physical frames");
+            let addr = frame.start_address().as_u64();
+
+            assert_eq!(addr % FRAME_SIZE, 0);
+            assert!(
+                usable_regions(memory_map)
+                    .any(|region| region.start <= addr && addr + FRAME_SIZE <= region.end())
+            );
+            // 地址严格递增，因此所有页帧互不相同
+            if let Some(previous) = previous {
+                assert!(addr > previous);
+            }
+            previous = Some(addr);
+        }
+    }
+
+    #[test_case]
+    fn check_access_rejects_bad_addresses() {
+        assert_eq!(check_access(0, 1, false), Err(AccessError::Null));
+        assert_eq!(
+            check_access(0x0000_8000_0000_0000, 1, false),
+            Err(AccessError::NonCanonical(0x0000_8000_0000_0000))
+        );
+        assert_eq!(
+            check_access(u64::MAX, 2, false),
+            Err(AccessError::NonCanonical(u64::MAX))
+        );
+        assert_eq!(
+            peek(HEAP_START as u64, MAX_PEEK_LEN + 1),
+            Err(AccessError::TooLong(MAX_PEEK_LEN + 1))
+        );
+    }
+
+    #[test_case]
+    fn poke_writes_heap_byte() {
+        let mut byte = alloc::boxed::Box::new(0u8);
+        let addr = &mut *byte as *mut u8 as u64;
+        assert_eq!(poke(addr, 0x5A), Ok(()));
+        assert_eq!(*byte, 0x5A);
+    }
+}
@@ -0,0 +1,299 @@
+//! GwenOS 内核 panic 处理模块
+//!
+//! 内核二进制的 `#[panic_handler]` 调用这里的 `kernel_panic_handler`：
+//! 把 panic 信息显示在屏幕顶部和串口上，并输出一行结构化记录供宿主机的测试脚本提取。
+//! 测试二进制使用 `test_panic_handler`，失败时直接退出 QEMU
+//!
+//! 堆耗尽不经过 panic，由分配器的 `alloc_error_handler` 调用这里的 `out_of_memory`
+//!
+//! panic 时还会通过 `panic_with_dump` 在串口输出一份崩溃报告（运行时间、堆、中断统计和
+//! 崩溃时的屏幕内容），没有显示器时也能还原当时的画面
+//!
+//! 报告输出完之后做什么由 `set_panic_action` 决定：停机（交互调试）、退出 QEMU（CI），
+//! 或者倒计时后重启（无人值守的部署），同一个内核镜像不用重新编译就能用在这些场合
+
+use crate::format::{ByteMutWriter, FieldEscaper};
+use crate::{allocator, cpu, interrupts, qemu, serial, serial_println, system, timer, vga};
+use core::alloc::Layout;
+use core::fmt::Write;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicU8, AtomicU64, AtomicUsize, Ordering};
+
+/// 串口上结构化 panic 记录的最大长度（字节），超出部分被截断
+const PANIC_RECORD_SIZE: usize = 512;
+
+/// panic 时屏幕上原来的内容（画 panic 信息之前保存），
+/// 调试工具可以从这里恢复崩溃前的画面
+static PANIC_SCREEN: spin::Mutex<vga::Snapshot> = spin::Mutex::new(vga::EMPTY_SNAPSHOT);
+
+/// 进入 panic 处理函数的次数，大于 1 说明 panic 处理过程中又发生了 panic
+static PANIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 嵌套 panic 时显示在屏幕左上角的固定文本
+const DOUBLE_PANIC_MSG: &str = "DOUBLE PANIC";
+
+// =============================================================================
+// panic 之后的动作
+// =============================================================================
+
+/// panic 信息输出完之后内核做什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicAction {
+    /// 停机，保留屏幕内容供查看（默认）
+    Halt,
+    /// 以失败退出 QEMU（单元测试中的默认值）；不在 QEMU 中运行时等同于停机
+    QemuExit,
+    /// 在屏幕上倒计时指定毫秒数后重启
+    RebootAfter(u64),
+}
+
+// `PANIC_ACTION` 中保存的动作种类
+const ACTION_HALT: u8 = 0;
+const ACTION_QEMU_EXIT: u8 = 1;
+const ACTION_REBOOT: u8 = 2;
+
+/// panic 之后的动作种类，`RebootAfter` 的毫秒数单独保存在 `REBOOT_DELAY_MS`
+///
+/// 用原子变量而不是锁：panic 可能发生在任何地方，读取时不能等锁
+static PANIC_ACTION: AtomicU8 = AtomicU8::new(if cfg!(test) {
+    ACTION_QEMU_EXIT
+} else {
+    ACTION_HALT
+});
+
+/// `PanicAction::RebootAfter` 的倒计时（毫秒）
+static REBOOT_DELAY_MS: AtomicU64 = AtomicU64::new(0);
+
+/// 重启倒计时显示在屏幕上的行（panic 信息占第 0~2 行）
+const REBOOT_COUNTDOWN_ROW: usize = 4;
+
+/// 设置 panic 之后的动作
+#[allow(dead_code)]
+pub fn set_panic_action(action: PanicAction) {
+    let kind = match action {
+        PanicAction::Halt => ACTION_HALT,
+        PanicAction::QemuExit => ACTION_QEMU_EXIT,
+        PanicAction::RebootAfter(ms) => {
+            REBOOT_DELAY_MS.store(ms, Ordering::SeqCst);
+            ACTION_REBOOT
+        }
+    };
+    PANIC_ACTION.store(kind, Ordering::SeqCst);
+}
+
+/// 当前设置的 panic 之后的动作
+pub fn panic_action() -> PanicAction {
+    match PANIC_ACTION.load(Ordering::SeqCst) {
+        ACTION_QEMU_EXIT => PanicAction::QemuExit,
+        ACTION_REBOOT => PanicAction::RebootAfter(REBOOT_DELAY_MS.load(Ordering::SeqCst)),
+        _ => PanicAction::Halt,
+    }
+}
+
+/// 执行 `panic_action()` 指定的动作
+///
+/// panic、堆耗尽和嵌套 panic 最后都走到这里；嵌套 panic 也会调用，所以这里和倒计时都不等任何锁
+fn run_panic_action() -> ! {
+    match panic_action() {
+        PanicAction::Halt => {}
+        PanicAction::QemuExit => qemu::exit_qemu(qemu::QemuExitCode::Failed),
+        PanicAction::RebootAfter(ms) => reboot_countdown(ms),
+    }
+    cpu::hlt_loop();
+}
+
+/// 在屏幕上每秒更新一次剩余时间，倒计时结束后重启
+///
+/// 用 TSC 忙等，不依赖时钟中断（panic 时中断可能是关着的）；
+/// TSC 还没有校准（启动早期）时等待退回到时钟中断，关中断的情况下会立即重启。
+/// 串口提示不加锁直接发送，串口正被占用时也不等它把之前的输出发完
+fn reboot_countdown(ms: u64) -> ! {
+    let mut line = [0u8; 64];
+    let mut w = ByteMutWriter::new(&mut line);
+    let _ = writeln!(w, "[PANIC] rebooting in {} ms", ms);
+    if let Some(com1) = serial::SERIAL1.try_lock() {
+        com1.flush();
+    }
+    serial::force_write_raw(w.as_str());
+    let mut remaining = ms;
+    loop {
+        let mut buf = [0u8; vga::VGA_WIDTH];
+        let mut w = ByteMutWriter::new(&mut buf);
+        let _ = write!(w, "Rebooting in {} s... ", remaining.div_ceil(1000));
+        vga::force_write_string_at(
+            w.as_str(),
+            REBOOT_COUNTDOWN_ROW,
+            0,
+            vga::ColorCode::YELLOW_ON_BLACK,
+        );
+        if remaining == 0 {
+            system::reboot();
+        }
+        let step = remaining.min(1000);
+        cpu::delay_us(step * 1000);
+        remaining -= step;
+    }
+}
+
+/// 内核的 panic 处理，内核二进制的 `#[panic_handler]` 直接调用它
+///
+/// 由于我们没有操作系统支持，只能将错误信息打印到屏幕和串口；
+/// 串口上最后还会输出一行以 `PANIC|` 开头的结构化记录，供宿主机的测试脚本提取
+///
+/// 处理过程中再次 panic（格式化出错、需要的锁被占用等）时不再做任何格式化，
+/// 只把 "DOUBLE PANIC" 直接写进显存然后停机，避免无限递归或在 WRITER 锁上死锁
+///
+/// # 参数
+/// - `info`: 包含 panic 信息的结构体
+pub fn kernel_panic_handler(info: &PanicInfo) -> ! {
+    if PANIC_COUNT.fetch_add(1, Ordering::SeqCst) > 0 {
+        double_panic();
+    }
+
+    // 输出到串口（方便调试）；panic 信息本身由崩溃报告的第一行输出
+    serial_println!();
+    serial_println!("!!! KERNEL PANIC !!!");
+
+    // 先保存屏幕内容并输出崩溃报告，再在屏幕顶部显示红色的 PANIC 信息
+    panic_with_dump(info);
+    vga::force_write_string_at("!!! KERNEL PANIC !!!", 0, 0, vga::ColorCode::WHITE_ON_RED);
+
+    // 格式化用的栈缓冲区（不依赖堆，也不占用 WRITER 锁）
+    let mut buf = [0u8; vga::VGA_WIDTH];
+
+    // 如果有位置信息，显示出来（文件:行:列）
+    if let Some(location) = info.location() {
+        let mut w = ByteMutWriter::new(&mut buf);
+        let _ = write!(
+            w,
+            "{}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+        vga::force_write_string_at("At: ", 1, 0, vga::ColorCode::LIGHT_RED_ON_BLACK);
+        vga::force_write_string_at(w.as_str(), 1, 4, vga::ColorCode::LIGHT_RED_ON_BLACK);
+    }
+
+    // 显示 panic 消息（包括带格式化参数的消息）
+    let mut w = ByteMutWriter::new(&mut buf);
+    let _ = write!(w, "{}", info.message());
+    vga::force_write_string_at("Msg: ", 2, 0, vga::ColorCode::LIGHT_RED_ON_BLACK);
+    vga::force_write_string_at(w.as_str(), 2, 5, vga::ColorCode::LIGHT_RED_ON_BLACK);
+
+    // 给宿主机工具解析的单行记录：PANIC|file=...|line=...|col=...|msg=...
+    // 文件名和消息中的 `|`、`\`、换行都会被转义；没有位置信息时 file 为空、行列为 0
+    let mut record = [0u8; PANIC_RECORD_SIZE];
+    let mut w = ByteMutWriter::new(&mut record);
+    let (file, line, col) = info
+        .location()
+        .map_or(("", 0, 0), |l| (l.file(), l.line(), l.column()));
+    let _ = w.write_str("PANIC|file=");
+    let _ = FieldEscaper(&mut w).write_str(file);
+    let _ = write!(w, "|line={}|col={}|msg=", line, col);
+    let _ = write!(FieldEscaper(&mut w), "{}", info.message());
+    serial::write_line(w.as_str());
+
+    // 停机前等串口发完，否则最后几个字节可能还留在 UART 的 FIFO 里
+    serial::flush();
+
+    // 停机、退出 QEMU 或倒计时重启
+    run_panic_action();
+}
+
+/// 在串口输出一份完整的崩溃报告，panic 处理函数在修改屏幕之前调用
+///
+/// 依次包括 panic 信息、运行时间、堆使用情况、中断统计和屏幕内容；
+/// 对应的子系统没有初始化（或者堆分配器正被占用）时跳过那一项。
+/// 屏幕内容同时保存到 `PANIC_SCREEN`，按屏幕当前的行数（25 或 50）、每行 80 个字符输出，
+/// 不可打印的字形显示为 `.`，两边用 `|` 标出行的边界
+pub fn panic_with_dump(info: &PanicInfo) {
+    serial_println!("===== CRASH REPORT =====");
+    serial_println!("panic: {}", info);
+
+    if timer::is_initialized() {
+        serial_println!("uptime: {} ms", timer::uptime_ms());
+    }
+
+    match allocator::usage() {
+        Some(heap) => serial_println!(
+            "heap: {} of {} bytes used, {} live allocations",
+            heap.used,
+            heap.size,
+            heap.allocations
+        ),
+        None => serial_println!("heap: unavailable"),
+    }
+
+    if interrupts::is_initialized() {
+        interrupts::report_stats();
+    }
+
+    if vga::is_present()
+        && let Some(mut screen) = PANIC_SCREEN.try_lock()
+    {
+        vga::force_snapshot_raw(&mut screen);
+        serial_println!("screen:");
+        for row in screen.rows() {
+            let mut line = [b'|'; vga::VGA_WIDTH + 2];
+            for (out, cell) in line[1..=vga::VGA_WIDTH].iter_mut().zip(row) {
+                *out = match cell.ascii_character {
+                    0 => b' ',
+                    byte @ 0x20..=0x7e => byte,
+                    _ => b'.',
+                };
+            }
+            // 只包含 ASCII，一定是合法的 UTF-8
+            serial::write_line(core::str::from_utf8(&line).unwrap_or(""));
+        }
+    }
+
+    serial_println!("===== END CRASH REPORT =====");
+}
+
+/// 堆耗尽时的处理：在串口记录请求的大小和对齐，在屏幕顶部显示红色横幅后停机
+///
+/// 调用过 `set_expected_panic` 时以成功退出 QEMU（测试断言堆耗尽会走到这里）；
+/// 否则和 panic 一样执行 `set_panic_action` 设置的动作。
+/// 堆已经不可用，这里只使用栈上的缓冲区
+///
+/// # 参数
+/// - `layout`: 失败的分配请求
+pub fn out_of_memory(layout: Layout) -> ! {
+    serial_println!();
+    serial_println!("!!! KERNEL OUT OF MEMORY !!!");
+    serial_println!(
+        "[ERROR] heap exhausted: requested {} bytes, align {}",
+        layout.size(),
+        layout.align()
+    );
+
+    vga::force_write_string_at(
+        "!!! KERNEL OUT OF MEMORY !!!",
+        0,
+        0,
+        vga::ColorCode::WHITE_ON_RED,
+    );
+    let mut buf = [0u8; vga::VGA_WIDTH];
+    let mut w = ByteMutWriter::new(&mut buf);
+    let _ = write!(
+        w,
+        "Requested {} bytes, align {}",
+        layout.size(),
+        layout.align()
+    );
+    vga::force_write_string_at(w.as_str(), 1, 0, vga::ColorCode::LIGHT_RED_ON_BLACK);
+    serial::flush();
+
+    crate::exit_if_expected_panic();
+    run_panic_action();
+}
+
+/// 嵌套 panic：不加锁、不格式化，直接往显存和串口写固定文本，然后执行 `set_panic_action` 设置的动作
+fn double_panic() -> ! {
+    vga::force_print_raw(DOUBLE_PANIC_MSG, 0, 0, vga::ColorCode::WHITE_ON_RED);
+    serial::force_write_raw("\n");
+    serial::force_write_raw(DOUBLE_PANIC_MSG);
+    serial::force_write_raw("\n");
+    run_panic_action();
+}
@@ -0,0 +1,241 @@
+//! GwenOS 8259 PIC（可编程中断控制器）模块
+//!
+//! 负责把硬件中断（IRQ）重映射到不与 CPU 异常冲突的中断向量上
+//!
+//! # 为什么要重映射？
+//! BIOS 默认把主 PIC 的 IRQ0~7 映射到向量 0x08~0x0F，
+//! 这和 CPU 异常（如双重故障 = 8）重叠了。
+//! 所以我们把主 PIC 移到 0x20~0x27，从 PIC 移到 0x28~0x2F。
+
+use crate::port::{inb, io_wait, outb};
+use crate::serial_println;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// =============================================================================
+// PIC 端口与命令定义
+// =============================================================================
+
+/// 主 PIC 的中断向量起始值（IRQ0 → 0x20）
+pub const PIC_1_OFFSET: u8 = 0x20;
+
+/// 从 PIC 的中断向量起始值（IRQ8 → 0x28）
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+// PIC 端口地址
+const PIC1_COMMAND: u16 = 0x20; // 主 PIC 命令端口
+const PIC1_DATA: u16 = 0x21; // 主 PIC 数据端口（中断屏蔽字）
+const PIC2_COMMAND: u16 = 0xA0; // 从 PIC 命令端口
+const PIC2_DATA: u16 = 0xA1; // 从 PIC 数据端口（中断屏蔽字）
+
+// PIC 命令
+const ICW1_INIT: u8 = 0x11; // 开始初始化，并且后面会发送 ICW4
+const ICW4_8086: u8 = 0x01; // 8086/88 模式
+const CMD_EOI: u8 = 0x20; // 中断结束（End Of Interrupt）
+const OCW3_READ_ISR: u8 = 0x0B; // 下一次读命令端口返回 ISR（正在服务的中断）
+
+/// 从 PIC 级联在主 PIC 的 IRQ2 上
+const CASCADE_IRQ: u8 = 2;
+
+/// 主 PIC 上可能产生伪中断的线（优先级最低的 IRQ7）
+pub const SPURIOUS_MASTER_IRQ: u8 = 7;
+
+/// 从 PIC 上可能产生伪中断的线（优先级最低的 IRQ15）
+pub const SPURIOUS_SLAVE_IRQ: u8 = 15;
+
+/// 已经识别出的伪中断次数
+static SPURIOUS_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// `init` 是否已经完成
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 初始化并重映射两片 PIC
+///
+/// 初始化完成后屏蔽所有 IRQ（级联线 IRQ2 除外），
+/// 由各个驱动在自己的 `init()` 中调用 [`unmask`] 打开需要的中断。
+/// 重复调用只输出警告：再次初始化会把各个驱动已经打开的 IRQ 全部屏蔽
+pub fn init() {
+    if is_initialized() {
+        serial_println!("[WARN] pic::init called twice, ignored");
+        return;
+    }
+
+    // ICW1：开始初始化序列
+    outb(PIC1_COMMAND, ICW1_INIT);
+    io_wait();
+    outb(PIC2_COMMAND, ICW1_INIT);
+    io_wait();
+
+    // ICW2：设置中断向量偏移
+    outb(PIC1_DATA, PIC_1_OFFSET);
+    io_wait();
+    outb(PIC2_DATA, PIC_2_OFFSET);
+    io_wait();
+
+    // ICW3：告诉主 PIC 从 PIC 接在 IRQ2（位掩码 0b100），
+    //       告诉从 PIC 它的级联标识是 2
+    outb(PIC1_DATA, 1 << CASCADE_IRQ);
+    io_wait();
+    outb(PIC2_DATA, CASCADE_IRQ);
+    io_wait();
+
+    // ICW4：使用 8086 模式
+    outb(PIC1_DATA, ICW4_8086);
+    io_wait();
+    outb(PIC2_DATA, ICW4_8086);
+    io_wait();
+
+    // 屏蔽所有中断，只保留级联线
+    outb(PIC1_DATA, !(1 << CASCADE_IRQ));
+    outb(PIC2_DATA, 0xFF);
+
+    INITIALIZED.store(true, Ordering::SeqCst);
+}
+
+/// 是否已经完成重映射（之后才能安全地开启硬件中断）
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
+
+/// 取消屏蔽指定 IRQ（允许该中断到达 CPU）
+///
+/// # 参数
+/// - `irq`: IRQ 编号（0-15）
+pub fn unmask(irq: u8) {
+    let (port, bit) = irq_port(irq);
+    outb(port, inb(port) & !(1 << bit));
+}
+
+/// 屏蔽指定 IRQ
+///
+/// # 参数
+/// - `irq`: IRQ 编号（0-15）
+#[allow(dead_code)]
+pub fn mask(irq: u8) {
+    let (port, bit) = irq_port(irq);
+    outb(port, inb(port) | (1 << bit));
+}
+
+/// 通知 PIC 中断处理完成
+///
+/// 每个硬件中断处理函数结束前都必须调用，否则 PIC 不会再发送同级或更低级的中断
+/// 来自从 PIC 的中断（IRQ8~15）需要同时通知两片 PIC
+///
+/// # 参数
+/// - `irq`: IRQ 编号（0-15）
+pub fn end_of_interrupt(irq: u8) {
+    if irq >= 8 {
+        outb(PIC2_COMMAND, CMD_EOI);
+    }
+    outb(PIC1_COMMAND, CMD_EOI);
+}
+
+// =============================================================================
+// 伪中断
+// =============================================================================
+//
+// IRQ 线上的信号在 PIC 把中断送给 CPU 之前就撤销了（例如电平抖动），PIC 仍然要给 CPU
+// 一个向量，就送出这片 PIC 优先级最低的那条线：主 PIC 是 IRQ7，从 PIC 是 IRQ15。
+// 这时 ISR（In-Service Register）里对应的位没有置位，这是区分真假中断的唯一办法。
+//
+// - 主 PIC 的伪 IRQ7：PIC 没有任何中断在服务，不能发 EOI。非特定 EOI 会清掉 ISR 中
+//   优先级最高的位——如果这时另一个中断正在服务，它就被提前结束了
+// - 从 PIC 的伪 IRQ15：从 PIC 不能发 EOI（同样的原因），但主 PIC 确实把级联线 IRQ2
+//   当成一次真实中断在服务，必须给主 PIC 发 EOI，否则 IRQ2 以及优先级更低的
+//   IRQ3~7 都不会再送达
+
+/// 在 IRQ7 / IRQ15 上收到的中断是真是假，以及应该发送哪些 EOI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrqSource {
+    /// 真实的中断：和其他 IRQ 一样正常发送 EOI
+    Real,
+    /// 主 PIC 的伪中断：不发送 EOI
+    SpuriousMaster,
+    /// 从 PIC 的伪中断：只给主 PIC 发送 EOI
+    SpuriousSlave,
+}
+
+/// 读取两片 PIC 的 ISR：低 8 位是主 PIC（IRQ0~7），高 8 位是从 PIC（IRQ8~15）
+pub fn read_isr() -> u16 {
+    outb(PIC1_COMMAND, OCW3_READ_ISR);
+    outb(PIC2_COMMAND, OCW3_READ_ISR);
+    u16::from_le_bytes([inb(PIC1_COMMAND), inb(PIC2_COMMAND)])
+}
+
+/// 根据 ISR 判断 `irq` 上的中断是不是伪中断
+///
+/// 只有 IRQ7 和 IRQ15 会产生伪中断，其他 IRQ 总是 `Real`
+pub const fn classify(irq: u8, isr: u16) -> IrqSource {
+    if isr & (1 << irq) != 0 {
+        return IrqSource::Real;
+    }
+    match irq {
+        SPURIOUS_MASTER_IRQ => IrqSource::SpuriousMaster,
+        SPURIOUS_SLAVE_IRQ => IrqSource::SpuriousSlave,
+        _ => IrqSource::Real,
+    }
+}
+
+/// IRQ7 / IRQ15 中断处理函数调用：读 ISR 判断真假，并且只发送应该发送的 EOI
+///
+/// 返回 `true` 表示是真实的中断（EOI 已经发送），由调用者决定是否需要处理设备
+pub fn handle_maybe_spurious(irq: u8) -> bool {
+    match classify(irq, read_isr()) {
+        IrqSource::Real => {
+            end_of_interrupt(irq);
+            true
+        }
+        IrqSource::SpuriousMaster => {
+            SPURIOUS_COUNT.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+        IrqSource::SpuriousSlave => {
+            SPURIOUS_COUNT.fetch_add(1, Ordering::Relaxed);
+            outb(PIC1_COMMAND, CMD_EOI);
+            false
+        }
+    }
+}
+
+/// 已经识别出的伪中断次数
+#[allow(dead_code)]
+pub fn spurious_count() -> u64 {
+    SPURIOUS_COUNT.load(Ordering::Relaxed)
+}
+
+/// 计算 IRQ 对应的屏蔽字端口和位序号
+fn irq_port(irq: u8) -> (u16, u8) {
+    if irq < 8 {
+        (PIC1_DATA, irq)
+    } else {
+        (PIC2_DATA, irq - 8)
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn isr_bit_decides_spurious_irqs() {
+        // ISR 中对应的位置位：真实中断
+        assert_eq!(classify(7, 1 << 7), IrqSource::Real);
+        assert_eq!(
+            classify(15, (1 << 15) | (1 << CASCADE_IRQ)),
+            IrqSource::Real
+        );
+        // 没有置位：伪中断，即使其他 IRQ 正在服务
+        assert_eq!(classify(7, 1 << 1), IrqSource::SpuriousMaster);
+        assert_eq!(classify(15, 1 << CASCADE_IRQ), IrqSource::SpuriousSlave);
+        // 其他 IRQ 不会是伪中断
+        assert_eq!(classify(1, 0), IrqSource::Real);
+        assert_eq!(classify(8, 0), IrqSource::Real);
+    }
+}
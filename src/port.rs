@@ -0,0 +1,143 @@
+//! GwenOS 端口 I/O 模块
+//!
+//! 封装 x86 的 `in`/`out` 指令，供串口、PIC、PIT 等驱动共用；
+//! 寄存器较多的设备可以用 `Port` 给每个寄存器一个带类型的名字
+
+use core::marker::PhantomData;
+
+// =============================================================================
+// 端口 I/O 操作（x86 汇编）
+// =============================================================================
+
+/// 向指定 I/O 端口写入一个字节
+///
+/// # 参数
+/// - `port`: I/O 端口地址
+/// - `value`: 要写入的字节值
+///
+/// # 说明
+/// 使用 x86 的 `out` 指令，这和内存写入不同！
+/// 内存：直接写地址  →  *ptr = value
+/// I/O：通过端口写   →  out(port, value)
+#[inline(always)]
+pub fn outb(port: u16, value: u8) {
+    unsafe {
+        // out 指令：将 value 写入 port 端口
+        // "out dx, al" 的意思是：把 al 寄存器的值写到 dx 寄存器指定的端口
+        core::arch::asm!(
+            "out dx,al",
+            in("dx") port,
+            in("al") value,
+            // 编译器优化选项
+            options(nomem, nostack, preserves_flags)
+        )
+    }
+}
+
+/// 从指定 I/O 端口读取一个字节
+///
+/// # 参数
+/// - `port`: I/O 端口地址
+///
+/// # 返回
+/// 从端口读取的字节值
+#[inline(always)]
+pub fn inb(port: u16) -> u8 {
+    let value: u8;
+    unsafe {
+        // in 指令：从 port 端口读取值到 al
+        core::arch::asm!(
+            "in al, dx",
+            in("dx") port,    // dx = 端口地址
+            out("al") value,  // al = 读取到的值
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    value
+}
+
+/// 向指定 I/O 端口写入一个字（16 位）
+///
+/// # 参数
+/// - `port`: I/O 端口地址
+/// - `value`: 要写入的 16 位值
+#[inline(always)]
+pub fn outw(port: u16, value: u16) {
+    unsafe {
+        core::arch::asm!(
+            "out dx, ax",
+            in("dx") port,
+            in("ax") value,
+            options(nomem, nostack, preserves_flags)
+        )
+    }
+}
+
+/// 向指定 I/O 端口写入一个双字（32 位）
+///
+/// # 参数
+/// - `port`: I/O 端口地址
+/// - `value`: 要写入的 32 位值
+#[inline(always)]
+pub fn outl(port: u16, value: u32) {
+    unsafe {
+        core::arch::asm!(
+            "out dx, eax",
+            in("dx") port,
+            in("eax") value,
+            options(nomem, nostack, preserves_flags)
+        )
+    }
+}
+
+/// 短暂等待一次 I/O 操作完成
+///
+/// 向未使用的 0x80 端口（POST 诊断端口）写入一个字节，
+/// 大约耗时 1~4 微秒，给老式设备（如 PIC）留出处理时间
+#[inline(always)]
+pub fn io_wait() {
+    outb(0x80, 0);
+}
+
+// =============================================================================
+// 带类型的端口
+// =============================================================================
+
+/// 一个 I/O 端口，`T` 是每次读写的宽度
+///
+/// 设备的寄存器映射用它做字段，在构造时从基地址算好每个端口，
+/// 之后按名字访问寄存器，不必在每次读写时重复 `基地址 + 偏移`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Port<T> {
+    number: u16,
+    _width: PhantomData<T>,
+}
+
+impl<T> Port<T> {
+    /// 端口号为 `number` 的端口
+    pub const fn new(number: u16) -> Self {
+        Port {
+            number,
+            _width: PhantomData,
+        }
+    }
+
+    /// 端口号
+    pub const fn number(self) -> u16 {
+        self.number
+    }
+}
+
+impl Port<u8> {
+    /// 读取一个字节
+    #[inline(always)]
+    pub fn read(self) -> u8 {
+        inb(self.number)
+    }
+
+    /// 写入一个字节
+    #[inline(always)]
+    pub fn write(self, value: u8) {
+        outb(self.number, value);
+    }
+}
@@ -0,0 +1,221 @@
+//! GwenOS 8042 PS/2 控制器模块
+//!
+//! 键盘通过 8042 控制器接入：控制器有两个端口（第一个接键盘，第二个接鼠标），
+//! 一个配置字节决定端口中断和扫描码翻译是否打开。固件不一定把控制器配置好，
+//! 所以在打开键盘中断之前按标准流程重新初始化一遍：
+//!
+//! 1. 关闭两个端口，清空输出缓冲区中残留的字节
+//! 2. 写配置字节：先关闭端口中断，按需要打开或关闭扫描码翻译
+//! 3. 控制器自检（0xAA，期望 0x55），之后重新写一次配置字节（有的控制器自检时会复位它）
+//! 4. 打开第一个端口，复位键盘（0xFF，期望 ACK 后跟 0xAA）
+//! 5. 打开第一个端口的中断
+//!
+//! 任何一步失败都返回错误，键盘驱动据此放弃打开 IRQ1，而不是接收一堆无意义的扫描码
+
+use crate::cpu;
+use crate::port::{inb, outb};
+
+// =============================================================================
+// 端口与命令定义
+// =============================================================================
+
+/// 数据端口：读取设备或控制器的响应，写入发给设备的字节
+const DATA_PORT: u16 = 0x60;
+
+/// 读：状态寄存器；写：控制器命令
+const STATUS_COMMAND_PORT: u16 = 0x64;
+
+// 状态寄存器位
+const STATUS_OUTPUT_FULL: u8 = 0x01; // 输出缓冲区满：数据端口有字节可读
+const STATUS_INPUT_FULL: u8 = 0x02; // 输入缓冲区满：控制器还没取走上一个字节
+
+// 控制器命令
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_SECOND_PORT: u8 = 0xA7;
+const CMD_SELF_TEST: u8 = 0xAA;
+const CMD_DISABLE_FIRST_PORT: u8 = 0xAD;
+const CMD_ENABLE_FIRST_PORT: u8 = 0xAE;
+
+// 配置字节位
+const CONFIG_FIRST_IRQ: u8 = 0x01; // 第一个端口的中断（IRQ1）
+const CONFIG_SECOND_IRQ: u8 = 0x02; // 第二个端口的中断（IRQ12）
+const CONFIG_FIRST_CLOCK_DISABLED: u8 = 0x10; // 第一个端口的时钟关闭
+const CONFIG_TRANSLATION: u8 = 0x40; // 把键盘的第 2 套扫描码翻译成第 1 套
+
+// 响应
+const SELF_TEST_PASSED: u8 = 0x55;
+/// 设备确认收到一个字节
+pub const DEVICE_ACK: u8 = 0xFA;
+const DEVICE_SELF_TEST_PASSED: u8 = 0xAA;
+
+/// 发给键盘的复位命令
+const DEVICE_RESET: u8 = 0xFF;
+
+/// 普通命令的轮询次数上限
+const IO_TIMEOUT_SPINS: u32 = 100_000;
+
+/// 键盘复位自检可能要几百毫秒，等待时间放宽到普通命令的 10 倍
+const RESET_TIMEOUT_SPINS: u32 = IO_TIMEOUT_SPINS * 10;
+
+/// 清空输出缓冲区时最多丢弃的字节数（控制器的缓冲区只有 1 字节，多读几次足够）
+const FLUSH_LIMIT: usize = 16;
+
+// =============================================================================
+// 错误类型
+// =============================================================================
+
+/// 控制器初始化失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Error {
+    /// 等待控制器超时（没有 8042 控制器，或者它没有响应）
+    Timeout,
+    /// 控制器自检没有返回 0x55（值为实际返回的字节）
+    SelfTestFailed(u8),
+    /// 键盘复位没有返回 ACK + 0xAA（值为实际返回的字节）
+    DeviceResetFailed(u8),
+}
+
+// =============================================================================
+// 底层读写
+// =============================================================================
+
+/// 轮询状态寄存器，直到 `mask` 位变成 `set` 指定的状态
+fn wait_status(mask: u8, set: bool, spins: u32) -> Result<(), Ps2Error> {
+    for _ in 0..spins {
+        if (inb(STATUS_COMMAND_PORT) & mask != 0) == set {
+            return Ok(());
+        }
+        cpu::pause();
+    }
+    Err(Ps2Error::Timeout)
+}
+
+/// 发送控制器命令
+fn send_command(command: u8) -> Result<(), Ps2Error> {
+    wait_status(STATUS_INPUT_FULL, false, IO_TIMEOUT_SPINS)?;
+    outb(STATUS_COMMAND_PORT, command);
+    Ok(())
+}
+
+/// 向数据端口写一个字节（命令的参数，或者发给设备的字节）
+fn write_data(byte: u8) -> Result<(), Ps2Error> {
+    wait_status(STATUS_INPUT_FULL, false, IO_TIMEOUT_SPINS)?;
+    outb(DATA_PORT, byte);
+    Ok(())
+}
+
+/// 等待并读取数据端口的一个字节
+fn read_data(spins: u32) -> Result<u8, Ps2Error> {
+    wait_status(STATUS_OUTPUT_FULL, true, spins)?;
+    Ok(inb(DATA_PORT))
+}
+
+/// 丢弃输出缓冲区中残留的字节
+fn flush_output() {
+    for _ in 0..FLUSH_LIMIT {
+        if inb(STATUS_COMMAND_PORT) & STATUS_OUTPUT_FULL == 0 {
+            return;
+        }
+        inb(DATA_PORT);
+    }
+}
+
+/// 写配置字节
+fn write_config(config: u8) -> Result<(), Ps2Error> {
+    send_command(CMD_WRITE_CONFIG)?;
+    write_data(config)
+}
+
+/// 初始化期间使用的配置字节：两个端口的中断都关闭，第一个端口的时钟打开，
+/// 扫描码翻译按 `translation` 设置，其余位保持固件的设置
+const fn setup_config(current: u8, translation: bool) -> u8 {
+    let config = current & !(CONFIG_FIRST_IRQ | CONFIG_SECOND_IRQ | CONFIG_FIRST_CLOCK_DISABLED);
+    if translation {
+        config | CONFIG_TRANSLATION
+    } else {
+        config & !CONFIG_TRANSLATION
+    }
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 初始化 PS/2 控制器并复位键盘，成功后第一个端口的中断（IRQ1）已在控制器中打开
+///
+/// `translation` 决定控制器是否把键盘的第 2 套扫描码翻译成第 1 套。
+/// 在关中断的情况下进行，避免控制器的响应被键盘中断处理函数当作扫描码读走；
+/// 只配置控制器，PIC 上的 IRQ1 由键盘驱动自己打开
+///
+/// # Errors
+/// 控制器不存在或不响应、自检失败、键盘复位失败
+pub fn init(translation: bool) -> Result<(), Ps2Error> {
+    cpu::without_interrupts(|| {
+        // 1. 关闭两个端口，防止初始化期间设备发来的字节混进响应
+        send_command(CMD_DISABLE_FIRST_PORT)?;
+        send_command(CMD_DISABLE_SECOND_PORT)?;
+        flush_output();
+
+        // 2. 配置字节：关闭中断，设置扫描码翻译
+        send_command(CMD_READ_CONFIG)?;
+        let config = setup_config(read_data(IO_TIMEOUT_SPINS)?, translation);
+        write_config(config)?;
+
+        // 3. 控制器自检
+        send_command(CMD_SELF_TEST)?;
+        match read_data(IO_TIMEOUT_SPINS)? {
+            SELF_TEST_PASSED => {}
+            other => return Err(Ps2Error::SelfTestFailed(other)),
+        }
+        write_config(config)?;
+
+        // 4. 打开第一个端口，复位键盘
+        send_command(CMD_ENABLE_FIRST_PORT)?;
+        write_data(DEVICE_RESET)?;
+        match read_data(RESET_TIMEOUT_SPINS)? {
+            DEVICE_ACK => {}
+            other => return Err(Ps2Error::DeviceResetFailed(other)),
+        }
+        match read_data(RESET_TIMEOUT_SPINS)? {
+            DEVICE_SELF_TEST_PASSED => {}
+            other => return Err(Ps2Error::DeviceResetFailed(other)),
+        }
+
+        // 5. 打开第一个端口的中断
+        flush_output();
+        write_config(config | CONFIG_FIRST_IRQ)
+    })
+}
+
+/// 读取数据端口的一个字节，不等待
+///
+/// 用于 IRQ1 处理函数：中断触发时输出缓冲区里已经有字节
+pub fn read_irq_data() -> u8 {
+    inb(DATA_PORT)
+}
+
+/// 向第一个端口的设备（键盘）发送一个字节，只等待控制器取走上一个字节
+///
+/// # Errors
+/// 控制器的输入缓冲区一直不空
+pub fn send_to_device(byte: u8) -> Result<(), Ps2Error> {
+    write_data(byte)
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn setup_config_masks_irqs_and_sets_translation() {
+        // 固件打开了两个端口的中断、关闭了第一个端口的时钟、开着翻译
+        let firmware = 0x47 | CONFIG_FIRST_CLOCK_DISABLED;
+        assert_eq!(setup_config(firmware, true), 0x44);
+        assert_eq!(setup_config(firmware, false), 0x04);
+    }
+}
@@ -0,0 +1,48 @@
+//! GwenOS QEMU 调试退出模块
+//!
+//! QEMU 启动时加上 `-device isa-debug-exit,iobase=0xf4,iosize=0x04`，
+//! 内核向 0xf4 端口写入一个值即可让 QEMU 退出，
+//! 退出码为 `(value << 1) | 1`，用于让测试把结果报告给宿主机
+
+use crate::port::outl;
+use crate::serial;
+
+/// isa-debug-exit 设备的 I/O 端口
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+/// 写入 isa-debug-exit 端口的退出码
+///
+/// 不使用 0 和 1，避免和 QEMU 自身的退出码（0 正常退出、1 出错）混淆
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+#[allow(dead_code)]
+pub enum QemuExitCode {
+    /// 测试通过（QEMU 退出码 33）
+    Success = 0x10,
+    /// 测试失败（QEMU 退出码 35）
+    Failed = 0x11,
+}
+
+impl QemuExitCode {
+    /// 由写入端口的原始值还原退出码，不是合法退出码时返回 None
+    pub const fn from_u32(value: u32) -> Option<QemuExitCode> {
+        match value {
+            0x10 => Some(QemuExitCode::Success),
+            0x11 => Some(QemuExitCode::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// 退出 QEMU
+///
+/// 先等串口把已经写入的内容全部发出，避免测试结果的最后一行被截断
+/// （串口正被占用时跳过，例如在持有串口锁时发生的 panic 中，不能在这里死锁）；
+/// 没有 isa-debug-exit 设备时（例如普通运行或真实硬件）写端口不起作用，函数会返回
+#[allow(dead_code)]
+pub fn exit_qemu(exit_code: QemuExitCode) {
+    if let Some(serial) = serial::SERIAL1.try_lock() {
+        serial.flush();
+    }
+    outl(ISA_DEBUG_EXIT_PORT, exit_code as u32);
+}
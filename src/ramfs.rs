@@ -0,0 +1,161 @@
+//! GwenOS 内存文件系统（ramfs）
+//!
+//! 按名字保存字节数据，没有目录，也不做持久化，重启后内容全部丢失；
+//! 文件内容放在堆上，所以需要在 `allocator::init_heap` 之后使用
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use spin::Mutex;
+
+// =============================================================================
+// 错误类型
+// =============================================================================
+
+/// ramfs 操作错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RamfsError {
+    /// 文件不存在
+    NotFound,
+    /// 创建的文件已经存在
+    AlreadyExists,
+}
+
+impl fmt::Display for RamfsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RamfsError::NotFound => write!(f, "no such file"),
+            RamfsError::AlreadyExists => write!(f, "file exists"),
+        }
+    }
+}
+
+// =============================================================================
+// Ramfs 结构体
+// =============================================================================
+
+/// 内存文件系统：文件名 → 文件内容
+///
+/// 使用 `BTreeMap`，`list` 按文件名的字节顺序返回
+pub struct Ramfs {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Ramfs {
+    /// 创建空的文件系统（不分配内存，可以用于 `static`）
+    pub const fn new() -> Self {
+        Ramfs {
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// 创建一个空文件
+    ///
+    /// # Errors
+    /// 同名文件已经存在
+    pub fn create(&mut self, name: &str) -> Result<(), RamfsError> {
+        if self.files.contains_key(name) {
+            return Err(RamfsError::AlreadyExists);
+        }
+        self.files.insert(String::from(name), Vec::new());
+        Ok(())
+    }
+
+    /// 用 `data` 替换文件的全部内容，文件不存在时创建
+    pub fn write(&mut self, name: &str, data: &[u8]) {
+        match self.files.get_mut(name) {
+            Some(contents) => {
+                contents.clear();
+                contents.extend_from_slice(data);
+            }
+            None => {
+                self.files.insert(String::from(name), Vec::from(data));
+            }
+        }
+    }
+
+    /// 读取文件的全部内容
+    ///
+    /// # Errors
+    /// 文件不存在
+    pub fn read(&self, name: &str) -> Result<&[u8], RamfsError> {
+        self.files
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or(RamfsError::NotFound)
+    }
+
+    /// 按文件名顺序列出所有文件（文件名，字节数）
+    pub fn list(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.files
+            .iter()
+            .map(|(name, contents)| (name.as_str(), contents.len()))
+    }
+
+    /// 删除文件
+    ///
+    /// # Errors
+    /// 文件不存在
+    pub fn remove(&mut self, name: &str) -> Result<(), RamfsError> {
+        self.files
+            .remove(name)
+            .map(|_| ())
+            .ok_or(RamfsError::NotFound)
+    }
+}
+
+impl Default for Ramfs {
+    fn default() -> Self {
+        Ramfs::new()
+    }
+}
+
+// =============================================================================
+// 全局实例
+// =============================================================================
+
+/// 命令行使用的全局文件系统
+pub static RAMFS: Mutex<Ramfs> = Mutex::new(Ramfs::new());
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn write_overwrites_existing_contents() {
+        let mut fs = Ramfs::new();
+        fs.write("notes", b"first version");
+        fs.write("notes", b"second");
+
+        assert_eq!(fs.read("notes"), Ok(&b"second"[..]));
+        assert_eq!(fs.create("notes"), Err(RamfsError::AlreadyExists));
+    }
+
+    #[test_case]
+    fn missing_files_are_reported() {
+        let mut fs = Ramfs::new();
+        fs.create("empty").unwrap();
+
+        assert_eq!(fs.read("empty"), Ok(&b""[..]));
+        assert_eq!(fs.read("missing"), Err(RamfsError::NotFound));
+        assert_eq!(fs.remove("missing"), Err(RamfsError::NotFound));
+        assert_eq!(fs.remove("empty"), Ok(()));
+        assert_eq!(fs.read("empty"), Err(RamfsError::NotFound));
+    }
+
+    #[test_case]
+    fn list_is_sorted_by_name() {
+        let mut fs = Ramfs::new();
+        fs.write("zeta", b"z");
+        fs.write("alpha", b"aaa");
+        fs.write("Mid", b"");
+
+        let names: Vec<(&str, usize)> = fs.list().collect();
+        assert_eq!(names, [("Mid", 0), ("alpha", 3), ("zeta", 1)]);
+    }
+}
@@ -0,0 +1,155 @@
+//! GwenOS 寄存器快照模块
+//!
+//! `x86-interrupt` 调用约定的处理函数只能看到 CPU 压入的栈帧（RIP、RSP 等），
+//! 通用寄存器在进入 Rust 代码前就可能被改写。需要完整寄存器的异常改用汇编入口：
+//! 入口先把所有通用寄存器压栈，和 CPU 压入的错误码、栈帧拼成一个 `Registers`，
+//! 再把它的地址交给 Rust 处理函数
+//!
+//! # 栈布局（从低地址到高地址）
+//! ```text
+//! r15 .. r8, rbp, rdi, rsi, rdx, rcx, rbx, rax   ← 入口依次压入（rax 最先）
+//! vector                                         ← 入口压入的向量号
+//! error_code, rip, cs, rflags, rsp, ss           ← CPU 压入
+//! ```
+
+use crate::serial_println;
+use core::fmt;
+
+/// 异常发生时的寄存器状态
+///
+/// 字段顺序必须和 `interrupts` 中汇编入口的压栈顺序一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Registers {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    /// 异常的向量号
+    pub vector: u64,
+    /// CPU 压入的错误码
+    pub error_code: u64,
+    /// 出错指令的地址
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    /// 异常发生时的栈指针
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+/// 每行显示的寄存器个数
+const REGS_PER_LINE: usize = 3;
+
+impl Registers {
+    /// 把全部寄存器输出到串口
+    pub fn dump_serial(&self) {
+        serial_println!("{}", self);
+    }
+}
+
+/// 多行输出，每行三个 64 位寄存器，最后一行是段寄存器，例如：
+///
+/// ```text
+///   RAX=0000000000000000  RBX=0000000000000001  RCX=0000000000000002
+///   ...
+///   CS=0x0008  SS=0x0000
+/// ```
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let regs = [
+            ("RAX", self.rax),
+            ("RBX", self.rbx),
+            ("RCX", self.rcx),
+            ("RDX", self.rdx),
+            ("RSI", self.rsi),
+            ("RDI", self.rdi),
+            ("RBP", self.rbp),
+            ("RSP", self.rsp),
+            ("R8", self.r8),
+            ("R9", self.r9),
+            ("R10", self.r10),
+            ("R11", self.r11),
+            ("R12", self.r12),
+            ("R13", self.r13),
+            ("R14", self.r14),
+            ("R15", self.r15),
+            ("RIP", self.rip),
+            ("RFL", self.rflags),
+        ];
+        for line in regs.chunks(REGS_PER_LINE) {
+            write!(f, " ")?;
+            for (name, value) in line {
+                write!(f, " {:<3}={:016x} ", name, value)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "  CS={:#06x}  SS={:#06x}", self.cs, self.ss)
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::ByteMutWriter;
+    use core::fmt::Write;
+
+    #[test_case]
+    fn layout_matches_entry_stub() {
+        // 15 个通用寄存器 + 向量号 + 错误码 + CPU 压入的 5 个栈帧字段
+        assert_eq!(core::mem::size_of::<Registers>(), 22 * 8);
+        assert_eq!(core::mem::offset_of!(Registers, rax), 14 * 8);
+        assert_eq!(core::mem::offset_of!(Registers, rip), 17 * 8);
+    }
+
+    #[test_case]
+    fn display_lists_every_register() {
+        let regs = Registers {
+            r15: 15,
+            r14: 14,
+            r13: 13,
+            r12: 12,
+            r11: 11,
+            r10: 10,
+            r9: 9,
+            r8: 8,
+            rbp: 0,
+            rdi: 0,
+            rsi: 0,
+            rdx: 0,
+            rcx: 0,
+            rbx: 0,
+            rax: 0xdead_beef,
+            vector: 8,
+            error_code: 0,
+            rip: 0x20_1000,
+            cs: 0x08,
+            rflags: 0x202,
+            rsp: 0,
+            ss: 0,
+        };
+        let mut buf = [0u8; 512];
+        let mut w = ByteMutWriter::new(&mut buf);
+        write!(w, "{}", regs).unwrap();
+
+        assert!(!w.is_truncated());
+        assert!(w.as_str().contains("RAX=00000000deadbeef"));
+        assert!(w.as_str().contains("R15=000000000000000f"));
+        assert!(w.as_str().ends_with("CS=0x0008  SS=0x0000"));
+    }
+}
@@ -3,7 +3,13 @@
 //! 提供通过 COM1 串口输出调试信息的功能
 //! 串口输出会显示在运行 QEMU 的终端窗口中
 
+pub mod ansi;
+
+use crate::port::Port;
+use crate::sync::IrqMutex;
+use crate::{cpu, format, log, pic};
 use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 
@@ -15,79 +21,422 @@ use spin::Mutex;
 /// 这是 PC 标准的 COM1 端口地址
 const COM1_PORT: u16 = 0x3F8;
 
-// 串口寄存器偏移量（相对于基地址）
+// 串口寄存器偏移量（相对于基地址），只在 `Uart::new` 中使用
 const DATA_REG: u16 = 0; // 数据寄存器：发送/接收数据
 const INT_ENABLE_REG: u16 = 1; // 中断使能寄存器
-const FIFO_CTRL_REG: u16 = 2; // FIFO 控制寄存器
+const FIFO_CTRL_REG: u16 = 2; // FIFO 控制寄存器（写）
+const INT_ID_REG: u16 = 2; // 中断标识寄存器（读，和 FIFO 控制寄存器同一端口）
 const LINE_CTRL_REG: u16 = 3; // 线路控制寄存器
 const MODEM_CTRL_REG: u16 = 4; // Modem 控制寄存器
 const LINE_STATUS_REG: u16 = 5; // 线路状态寄存器（检查是否可以发送）
+const MODEM_STATUS_REG: u16 = 6; // Modem 状态寄存器
+
+/// 一个 16550 UART 的寄存器
+///
+/// 由基地址一次算好每个寄存器的端口，之后按名字访问，不会把偏移量弄混
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Uart {
+    /// 数据寄存器（DLAB 置位时是波特率因子低字节）
+    data: Port<u8>,
+    /// 中断使能寄存器（DLAB 置位时是波特率因子高字节）
+    int_enable: Port<u8>,
+    /// FIFO 控制寄存器（只写）
+    fifo_ctrl: Port<u8>,
+    /// 中断标识寄存器（只读，和 FIFO 控制寄存器同一个端口）
+    int_id: Port<u8>,
+    /// 线路控制寄存器
+    line_ctrl: Port<u8>,
+    /// Modem 控制寄存器
+    modem_ctrl: Port<u8>,
+    /// 线路状态寄存器
+    line_status: Port<u8>,
+    /// Modem 状态寄存器
+    #[allow(dead_code)]
+    modem_status: Port<u8>,
+}
+
+impl Uart {
+    /// 基地址为 `base` 的 UART
+    const fn new(base: u16) -> Self {
+        Uart {
+            data: Port::new(base + DATA_REG),
+            int_enable: Port::new(base + INT_ENABLE_REG),
+            fifo_ctrl: Port::new(base + FIFO_CTRL_REG),
+            int_id: Port::new(base + INT_ID_REG),
+            line_ctrl: Port::new(base + LINE_CTRL_REG),
+            modem_ctrl: Port::new(base + MODEM_CTRL_REG),
+            line_status: Port::new(base + LINE_STATUS_REG),
+            modem_status: Port::new(base + MODEM_STATUS_REG),
+        }
+    }
+}
+
+/// COM1 的寄存器
+const COM1: Uart = Uart::new(COM1_PORT);
+
+// 线路控制寄存器的位
+const LINE_CTRL_DLAB: u8 = 0x80; // 除数锁存访问位：置位时数据/中断使能寄存器变成波特率因子
+const LINE_CTRL_TWO_STOP_BITS: u8 = 0x04; // 位 2：2 个停止位（5 位数据时是 1.5 个）
+const LINE_CTRL_PARITY_ODD: u8 = 0x08; // 位 3~5：奇偶校验方式
+const LINE_CTRL_PARITY_EVEN: u8 = 0x18;
+const LINE_CTRL_PARITY_MARK: u8 = 0x28;
+const LINE_CTRL_PARITY_SPACE: u8 = 0x38;
+
+// Modem 控制寄存器的值
+const MODEM_NORMAL: u8 = 0x0B; // DTR + RTS + OUT2（OUT2 把 UART 中断接到 PIC）
+const MODEM_LOOPBACK: u8 = 0x1E; // 回环模式 + RTS + OUT1 + OUT2
+
+/// 回环自检时发送的测试字节
+const LOOPBACK_TEST_BYTE: u8 = 0xAE;
+
+// FIFO 控制寄存器的位
+const FIFO_ENABLE_AND_CLEAR: u8 = 0x07; // 启用 FIFO，清空接收和发送 FIFO
+const FIFO_TRIGGER_1: u8 = 0x00; // 接收中断触发阈值：1 字节
+const FIFO_TRIGGER_4: u8 = 0x40; // 4 字节
+const FIFO_TRIGGER_8: u8 = 0x80; // 8 字节
+const FIFO_TRIGGER_14: u8 = 0xC0; // 14 字节
+
+// 中断使能寄存器的位
+const INT_RX_AVAILABLE: u8 = 0x01; // 接收到数据中断
+const INT_TX_EMPTY: u8 = 0x02; // 发送保持寄存器空（THRE）中断
+
+// 中断标识寄存器的值
+const INT_ID_NONE_PENDING: u8 = 0x01; // 位 0：没有待处理的中断
+const INT_ID_MASK: u8 = 0x0E; // 位 1~3：中断来源
+const INT_ID_TX_EMPTY: u8 = 0x02; // 发送保持寄存器空
+const INT_ID_RX_AVAILABLE: u8 = 0x04; // 接收到数据（达到 FIFO 触发阈值）
+const INT_ID_RX_TIMEOUT: u8 = 0x0C; // 接收 FIFO 中有数据但一段时间没有新字节
+
+// 线路状态寄存器的位
+const LINE_DATA_READY: u8 = 0x01; // 接收缓冲区有数据可读
+const LINE_OVERRUN: u8 = 0x02; // 接收溢出：新字节到达时旧字节还没被读走，旧字节丢失
+const LINE_PARITY_ERROR: u8 = 0x04; // 奇偶校验错误
+const LINE_FRAMING_ERROR: u8 = 0x08; // 帧错误：没有收到停止位
+const LINE_BREAK: u8 = 0x10; // 线路中断：数据线保持低电平超过一个字符的时间
+const LINE_TX_EMPTY: u8 = 0x20; // 发送保持寄存器（FIFO）为空，可以写入
+const LINE_TX_IDLE: u8 = 0x40; // 发送器完全空闲（移位寄存器也已发完）
+const LINE_FIFO_ERROR: u8 = 0x80; // 接收 FIFO 中至少有一个字节有错误
+
+/// COM1 使用的 IRQ 线
+pub const COM1_IRQ: u8 = 4;
+
+/// 16550 的发送 FIFO 深度：每次 THRE 中断最多可以连续写入的字节数
+const TX_FIFO_SIZE: usize = 16;
+
+/// 等待发送保持寄存器变空的最大轮询次数
+///
+/// 正常的 UART（包括 QEMU）几乎立刻就绪；超过这个次数说明串口已经失去响应，放弃这个字节
+const TX_TIMEOUT_SPINS: usize = 100_000;
+
+/// 软件发送缓冲区的容量（字节）
+const TX_BUFFER_SIZE: usize = 1024;
+
+/// 接收队列的容量（字节）
+const RX_BUFFER_SIZE: usize = 256;
 
 // =============================================================================
-// 端口 I/O 操作（x86 汇编）
+// FIFO 配置
 // =============================================================================
 
-/// 向指定 I/O 端口写入一个字节
+/// 16550 的 FIFO 配置
 ///
-/// # 参数
-/// - `port`: I/O 端口地址
-/// - `value`: 要写入的字节值
-///
-/// # 说明
-/// 使用 x86 的 `out` 指令，这和内存写入不同！
-/// 内存：直接写地址  →  *ptr = value
-/// I/O：通过端口写   →  out(port, value)
-#[inline(always)]
-fn outb(port: u16, value: u8) {
-    unsafe {
-        // out 指令：将 value 写入 port 端口
-        // "out dx, al" 的意思是：把 al 寄存器的值写到 dx 寄存器指定的端口
-        core::arch::asm!(
-            "out dx,al",
-            in("dx") port,
-            in("al") value,
-            // 编译器优化选项
-            options(nomem, nostack, preserves_flags)
-        )
+/// 触发阈值是接收 FIFO 积累多少字节后才产生接收中断；
+/// 关闭 FIFO 后 UART 退化为 16450 行为，每次只能收发一个字节，便于排查丢字节问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum FifoConfig {
+    /// 关闭 FIFO
+    Disabled,
+    /// 启用 FIFO，1 字节触发
+    Trigger1,
+    /// 启用 FIFO，4 字节触发
+    Trigger4,
+    /// 启用 FIFO，8 字节触发
+    Trigger8,
+    /// 启用 FIFO，14 字节触发（默认）
+    Trigger14,
+}
+
+impl FifoConfig {
+    /// 写入 FIFO 控制寄存器的值（启用时同时清空两个 FIFO）
+    pub const fn fcr_value(self) -> u8 {
+        match self {
+            FifoConfig::Disabled => 0x00,
+            FifoConfig::Trigger1 => FIFO_ENABLE_AND_CLEAR | FIFO_TRIGGER_1,
+            FifoConfig::Trigger4 => FIFO_ENABLE_AND_CLEAR | FIFO_TRIGGER_4,
+            FifoConfig::Trigger8 => FIFO_ENABLE_AND_CLEAR | FIFO_TRIGGER_8,
+            FifoConfig::Trigger14 => FIFO_ENABLE_AND_CLEAR | FIFO_TRIGGER_14,
+        }
+    }
+
+    /// 发送保持寄存器空时可以连续写入的字节数
+    const fn tx_burst(self) -> usize {
+        match self {
+            FifoConfig::Disabled => 1,
+            _ => TX_FIFO_SIZE,
+        }
     }
 }
 
-/// 从指定 I/O 端口读取一个字节
+/// COM1 每次 THRE 中断最多写入的字节数，由 init 按 FIFO 配置设置
+static COM1_TX_BURST: AtomicUsize = AtomicUsize::new(TX_FIFO_SIZE);
+
+// =============================================================================
+// 帧格式
+// =============================================================================
+
+/// 每个字符的数据位数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum DataBits {
+    Five,
+    Six,
+    Seven,
+    Eight,
+}
+
+/// 奇偶校验方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Parity {
+    /// 无校验
+    None,
+    /// 奇校验
+    Odd,
+    /// 偶校验
+    Even,
+    /// 校验位固定为 1
+    Mark,
+    /// 校验位固定为 0
+    Space,
+}
+
+/// 停止位个数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum StopBits {
+    One,
+    /// 1.5 个停止位，只能和 5 位数据搭配
+    OneAndHalf,
+    /// 2 个停止位，不能和 5 位数据搭配
+    Two,
+}
+
+/// 串口的帧格式（数据位、奇偶校验、停止位），例如 8N1、7E1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineFormat {
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl LineFormat {
+    /// 8 位数据、无校验、1 个停止位（`init` 使用的格式）
+    pub const EIGHT_N_ONE: LineFormat =
+        LineFormat::new(DataBits::Eight, Parity::None, StopBits::One);
+
+    /// 指定三个参数的帧格式（组合是否有效在 `lcr_value` 中检查）
+    pub const fn new(data_bits: DataBits, parity: Parity, stop_bits: StopBits) -> Self {
+        LineFormat {
+            data_bits,
+            parity,
+            stop_bits,
+        }
+    }
+
+    /// 写入线路控制寄存器的值：位 0~1 是数据位数减 5，位 2 是停止位，位 3~5 是奇偶校验
+    ///
+    /// 16550 用同一个位表示"多于 1 个停止位"：5 位数据时是 1.5 个，其他时候是 2 个，
+    /// 所以 1.5 个停止位只能配 5 位数据，2 个停止位不能配 5 位数据
+    ///
+    /// # Errors
+    /// 停止位和数据位的组合无法表示时返回 `SerialError::InvalidFormat`
+    pub const fn lcr_value(self) -> Result<u8, SerialError> {
+        let data = match self.data_bits {
+            DataBits::Five => 0x00,
+            DataBits::Six => 0x01,
+            DataBits::Seven => 0x02,
+            DataBits::Eight => 0x03,
+        };
+        let five_bits = matches!(self.data_bits, DataBits::Five);
+        let stop = match self.stop_bits {
+            StopBits::One => 0,
+            StopBits::OneAndHalf if five_bits => LINE_CTRL_TWO_STOP_BITS,
+            StopBits::Two if !five_bits => LINE_CTRL_TWO_STOP_BITS,
+            StopBits::OneAndHalf | StopBits::Two => return Err(SerialError::InvalidFormat),
+        };
+        let parity = match self.parity {
+            Parity::None => 0,
+            Parity::Odd => LINE_CTRL_PARITY_ODD,
+            Parity::Even => LINE_CTRL_PARITY_EVEN,
+            Parity::Mark => LINE_CTRL_PARITY_MARK,
+            Parity::Space => LINE_CTRL_PARITY_SPACE,
+        };
+        Ok(data | stop | parity)
+    }
+}
+
+// =============================================================================
+// 软件收发缓冲区
+// =============================================================================
+
+/// 固定容量的字节环形队列
+struct ByteQueue<const N: usize> {
+    bytes: [u8; N],
+    /// 队首位置
+    head: usize,
+    /// 队列中的字节数
+    len: usize,
+}
+
+impl<const N: usize> ByteQueue<N> {
+    const fn new() -> Self {
+        ByteQueue {
+            bytes: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// 字节入队（调用者保证没有满）
+    fn push(&mut self, byte: u8) {
+        self.bytes[(self.head + self.len) % N] = byte;
+        self.len += 1;
+    }
+
+    /// 取出最早的字节
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let byte = self.bytes[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// COM1 的发送缓冲区
 ///
-/// # 参数
-/// - `port`: I/O 端口地址
-///
-/// # 返回
-/// 从端口读取的字节值
-#[inline(always)]
-fn inb(port: u16) -> u8 {
-    let value: u8;
-    unsafe {
-        // in 指令：从 port 端口读取值到 al
-        core::arch::asm!(
-            "in al, dx",
-            in("dx") port,    // dx = 端口地址
-            out("al") value,  // al = 读取到的值
-            options(nomem, nostack, preserves_flags)
-        );
+/// 启用发送中断后，write_byte 只把字节放进这里，由 THRE 中断在 UART 空闲时取出发送。
+/// 中断处理函数只访问这里而不碰 SERIAL1，避免和持有 SERIAL1 的代码死锁
+static TX_BUFFER: IrqMutex<ByteQueue<TX_BUFFER_SIZE>> = IrqMutex::new(ByteQueue::new());
+
+/// COM1 的接收队列：接收中断把收到的字节放进这里，由 `read_char` 取出
+static RX_BUFFER: IrqMutex<ByteQueue<RX_BUFFER_SIZE>> = IrqMutex::new(ByteQueue::new());
+
+/// 因为 UART 一直没有准备好而丢弃的字节数
+static DROPPED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// 接收中断发现的接收溢出次数（每次至少丢失一个字节）
+static RX_OVERRUNS: AtomicU64 = AtomicU64::new(0);
+
+// =============================================================================
+// 线路状态
+// =============================================================================
+
+/// 线路状态寄存器的解码结果
+///
+/// 错误位（溢出、奇偶校验、帧错误、线路中断）在读取寄存器时被清除，
+/// 所以每次读取只能看到上次读取之后发生的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineStatus {
+    /// 接收缓冲区有数据可读
+    pub data_ready: bool,
+    /// 接收溢出，有字节丢失
+    pub overrun_error: bool,
+    /// 奇偶校验错误
+    pub parity_error: bool,
+    /// 帧错误（波特率或数据格式不匹配时常见）
+    pub framing_error: bool,
+    /// 线路中断（对端发送了 break，或者线缆断开）
+    pub break_interrupt: bool,
+    /// 发送保持寄存器为空（THRE），可以写入
+    pub thr_empty: bool,
+    /// 发送器完全空闲
+    pub transmitter_empty: bool,
+    /// 接收 FIFO 中有出错的字节
+    pub fifo_error: bool,
+}
+
+impl LineStatus {
+    /// 解码线路状态寄存器的值
+    pub const fn from_bits(bits: u8) -> Self {
+        LineStatus {
+            data_ready: bits & LINE_DATA_READY != 0,
+            overrun_error: bits & LINE_OVERRUN != 0,
+            parity_error: bits & LINE_PARITY_ERROR != 0,
+            framing_error: bits & LINE_FRAMING_ERROR != 0,
+            break_interrupt: bits & LINE_BREAK != 0,
+            thr_empty: bits & LINE_TX_EMPTY != 0,
+            transmitter_empty: bits & LINE_TX_IDLE != 0,
+            fifo_error: bits & LINE_FIFO_ERROR != 0,
+        }
+    }
+
+    /// 是否有任何一个接收错误位
+    pub const fn has_error(&self) -> bool {
+        self.overrun_error
+            || self.parity_error
+            || self.framing_error
+            || self.break_interrupt
+            || self.fifo_error
+    }
+}
+
+impl fmt::Display for LineStatus {
+    /// 每一位显示为 `名称=0/1`，按寄存器位从低到高排列
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DR={} OE={} PE={} FE={} BI={} THRE={} TEMT={} FIFOERR={}",
+            u8::from(self.data_ready),
+            u8::from(self.overrun_error),
+            u8::from(self.parity_error),
+            u8::from(self.framing_error),
+            u8::from(self.break_interrupt),
+            u8::from(self.thr_empty),
+            u8::from(self.transmitter_empty),
+            u8::from(self.fifo_error)
+        )
     }
-    value
 }
 
 // =============================================================================
 // 串口 Writer 结构
 // =============================================================================
 
+/// 串口初始化错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialError {
+    /// 回环自检失败：读回的字节和发送的不一致（串口不存在或已损坏）
+    LoopbackFailed,
+    /// 帧格式中停止位和数据位的组合无效（见 `LineFormat::lcr_value`）
+    InvalidFormat,
+}
+
 /// 串口写入器
 /// 封装串口操作
 pub struct SerialWriter {
-    port: u16,
+    uart: Uart,
+    /// 是否通过软件缓冲区和发送中断发送（否则忙等发送）
+    tx_buffered: bool,
+    /// 是否已通过自检并完成初始化；没有初始化时所有写入都被丢弃
+    initialized: AtomicBool,
 }
 
 impl SerialWriter {
     /// 创建新的串口写入器
     const fn new(port: u16) -> Self {
-        SerialWriter { port }
+        SerialWriter {
+            uart: Uart::new(port),
+            tx_buffered: false,
+            initialized: AtomicBool::new(false),
+        }
     }
 
     /// 初始化串口
@@ -97,25 +446,105 @@ impl SerialWriter {
     /// - 数据位：8 位
     /// - 停止位：1 位
     /// - 无奇偶校验
-    pub fn init(&self) {
+    ///
+    /// 配置完成后做一次回环自检，失败时返回错误，之后的写入都被丢弃
+    ///
+    /// FIFO 使用 14 字节触发阈值，需要其他配置时使用 `init_with_fifo`；
+    /// 需要其他帧格式时使用 `init_with_format`
+    pub fn init(&self) -> Result<(), SerialError> {
+        self.init_with_fifo(FifoConfig::Trigger14)
+    }
+
+    /// 初始化串口，并使用指定的 FIFO 配置
+    ///
+    /// 除 FIFO 外的配置和 `init` 相同
+    pub fn init_with_fifo(&self, fifo: FifoConfig) -> Result<(), SerialError> {
+        self.configure(fifo, LineFormat::EIGHT_N_ONE)
+    }
+
+    /// 初始化串口，并使用指定的帧格式
+    ///
+    /// 除帧格式外的配置和 `init` 相同；格式无效时不改动串口，直接返回错误
+    pub fn init_with_format(&self, format: LineFormat) -> Result<(), SerialError> {
+        self.configure(FifoConfig::Trigger14, format)
+    }
+
+    /// 修改已初始化的串口的帧格式，波特率和 FIFO 配置不变
+    ///
+    /// # Errors
+    /// 格式无效时返回 `SerialError::InvalidFormat`，串口保持原来的格式
+    pub fn set_format(&self, format: LineFormat) -> Result<(), SerialError> {
+        self.uart.line_ctrl.write(format.lcr_value()?);
+        Ok(())
+    }
+
+    /// 按 FIFO 配置和帧格式初始化串口
+    fn configure(&self, fifo: FifoConfig, format: LineFormat) -> Result<(), SerialError> {
+        let line_ctrl = format.lcr_value()?;
+        self.initialized.store(false, Ordering::Relaxed);
+
         // 1. 禁用所有中断
-        outb(self.port + INT_ENABLE_REG, 0x00);
+        self.uart.int_enable.write(0x00);
 
         // 2. 设置波特率为 115200
         //    波特率因子 = 115200 / 目标波特率
         //    115200 baud → 因子 = 1
-        outb(self.port + LINE_CTRL_REG, 0x80); // 启用 DLAB（访问波特率寄存器）
-        outb(self.port + DATA_REG, 0x01); // 波特率因子低字节
-        outb(self.port + INT_ENABLE_REG, 0x00); // 波特率因子高字节
+        self.uart.line_ctrl.write(LINE_CTRL_DLAB); // 启用 DLAB（访问波特率寄存器）
+        self.uart.data.write(0x01); // 波特率因子低字节
+        self.uart.int_enable.write(0x00); // 波特率因子高字节
 
-        // 3. 配置线路：8位数据，1位停止，无奇偶校验
-        outb(self.port + LINE_CTRL_REG, 0x03);
+        // 3. 配置帧格式（同时清除 DLAB），默认 8 位数据、1 位停止、无奇偶校验
+        self.uart.line_ctrl.write(line_ctrl);
 
-        // 4. 启用 FIFO，清空缓冲区，设置 14 字节触发阈值
-        outb(self.port + FIFO_CTRL_REG, 0xC7);
+        // 4. 配置 FIFO（启用时同时清空缓冲区并设置触发阈值）
+        self.uart.fifo_ctrl.write(fifo.fcr_value());
+        if self.uart == COM1 {
+            COM1_TX_BURST.store(fifo.tx_burst(), Ordering::Relaxed);
+        }
 
-        // 5. 设置 Modem：启用 DTR, RTS, OUT2
-        outb(self.port + MODEM_CTRL_REG, 0x0B);
+        // 5. 回环自检：发出去的字节直接回到接收端，读回来应该一致
+        self.uart.modem_ctrl.write(MODEM_LOOPBACK);
+        self.uart.data.write(LOOPBACK_TEST_BYTE);
+        if self.uart.data.read() != LOOPBACK_TEST_BYTE {
+            return Err(SerialError::LoopbackFailed);
+        }
+
+        // 6. 退出回环，设置 Modem：启用 DTR, RTS, OUT2
+        self.uart.modem_ctrl.write(MODEM_NORMAL);
+
+        // 7. COM1 打开接收中断（PIC 上的 IRQ4 由 enable_tx_interrupt 打开）
+        if self.uart == COM1 {
+            self.uart.int_enable.write(INT_RX_AVAILABLE);
+        }
+
+        self.initialized.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 串口是否存在并已初始化
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Relaxed)
+    }
+
+    /// 启用中断驱动的发送
+    ///
+    /// 之后 write_byte 会把字节放进软件缓冲区后立即返回，
+    /// 由 THRE 中断负责发送。只支持 COM1，需要在 `pic::init()` 之后调用；
+    /// 打开 IRQ4 后接收中断也开始工作
+    pub fn enable_tx_interrupt(&mut self) {
+        if !self.is_initialized() {
+            return;
+        }
+        self.tx_buffered = true;
+        pic::unmask(COM1_IRQ);
+    }
+
+    /// 读取并解码线路状态寄存器
+    ///
+    /// 读取会清除寄存器中的错误位；COM1 的接收中断也会读取这个寄存器，
+    /// 接收溢出的累计次数见 `rx_overruns`
+    pub fn line_status(&self) -> LineStatus {
+        LineStatus::from_bits(self.uart.line_status.read())
     }
 
     /// 检查串口是否可以发送数据
@@ -123,24 +552,126 @@ impl SerialWriter {
     fn is_transmit_empty(&self) -> bool {
         // 读取线路状态寄存器，检查第5位
         // 如果第5位为1，表示发送缓冲区为空，可以发送
-        (inb(self.port + LINE_STATUS_REG) & 0x20) != 0
+        (self.uart.line_status.read() & LINE_TX_EMPTY) != 0
     }
 
-    /// 发送一个字节
-    pub fn write_byte(&self, byte: u8) {
+    /// 忙等发送一个字节
+    ///
+    /// 最多等待 `TX_TIMEOUT_SPINS` 次；发送保持寄存器一直不空时丢弃这个字节并计数，
+    /// 避免串口失去响应时整个内核卡死在这里
+    fn write_byte_blocking(&self, byte: u8) {
         // 等待发送缓冲区为空
-        while !self.is_transmit_empty() {
-            // 忙等待（自旋）
+        if !(0..TX_TIMEOUT_SPINS).any(|_| self.is_transmit_empty()) {
+            DROPPED_BYTES.fetch_add(1, Ordering::Relaxed);
+            return;
         }
         // 发送字节
-        outb(self.port + DATA_REG, byte);
+        self.uart.data.write(byte);
+    }
+
+    /// 发送一个字节
+    ///
+    /// 启用发送中断时，字节放进软件缓冲区后立即返回；
+    /// 缓冲区满时退回忙等，先把缓冲区里最早的字节直接发出去腾出空间，不会丢字节
+    ///
+    /// 当前关闭了中断时（异常处理、panic 等），THRE 中断无法送达，
+    /// 先把缓冲区发完再忙等发送这个字节
+    pub fn write_byte(&self, byte: u8) {
+        if !self.is_initialized() {
+            return;
+        }
+        if !self.tx_buffered {
+            self.write_byte_blocking(byte);
+            return;
+        }
+        if !cpu::interrupts_enabled() {
+            self.drain_blocking();
+            self.write_byte_blocking(byte);
+            return;
+        }
+
+        {
+            let mut buffer = TX_BUFFER.lock();
+            while buffer.is_full() {
+                if let Some(oldest) = buffer.pop() {
+                    self.write_byte_blocking(oldest);
+                }
+            }
+            buffer.push(byte);
+        }
+
+        // 打开 THRE 中断：如果发送器正空闲，会立刻触发一次中断开始发送
+        self.uart.int_enable.write(INT_RX_AVAILABLE | INT_TX_EMPTY);
+    }
+
+    /// 批量发送字节
+    ///
+    /// 忙等发送时，确认发送 FIFO 为空后一次连续写入最多一个 FIFO 深度（16 字节），
+    /// 再重新检查线路状态，不必每个字节都读一次线路状态寄存器；
+    /// 启用发送中断且中断开着时和 `write_byte` 一样放进软件缓冲区
+    pub fn write_bytes(&self, bytes: &[u8]) {
+        if !self.is_initialized() {
+            return;
+        }
+        if self.tx_buffered && cpu::interrupts_enabled() {
+            for &byte in bytes {
+                self.write_byte(byte);
+            }
+            return;
+        }
+
+        self.drain_blocking();
+        for chunk in bytes.chunks(self.tx_burst()) {
+            // 等待发送 FIFO 完全空出来，超时时丢弃这一批并计数
+            if !(0..TX_TIMEOUT_SPINS).any(|_| self.is_transmit_empty()) {
+                DROPPED_BYTES.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                continue;
+            }
+            for &byte in chunk {
+                self.uart.data.write(byte);
+            }
+        }
+    }
+
+    /// 发送 FIFO 为空时可以连续写入的字节数（只有 COM1 知道自己的 FIFO 配置）
+    fn tx_burst(&self) -> usize {
+        if self.uart == COM1 {
+            COM1_TX_BURST.load(Ordering::Relaxed)
+        } else {
+            1
+        }
+    }
+
+    /// 忙等把软件缓冲区里的字节全部送进 UART（保持原有顺序）
+    fn drain_blocking(&self) {
+        if !self.tx_buffered {
+            return;
+        }
+        let mut buffer = TX_BUFFER.lock();
+        while let Some(byte) = buffer.pop() {
+            self.write_byte_blocking(byte);
+        }
+    }
+
+    /// 等待所有已写入的字节发送完毕
+    ///
+    /// 关闭中断时也能使用（例如 panic 处理中）：直接忙等把缓冲区发完
+    pub fn flush(&self) {
+        if !self.is_initialized() {
+            return;
+        }
+        self.drain_blocking();
+        // 等待 UART 把 FIFO 和移位寄存器里的数据全部发出（同样有上限，串口失去响应时直接返回）
+        for _ in 0..TX_TIMEOUT_SPINS {
+            if self.uart.line_status.read() & LINE_TX_IDLE != 0 {
+                break;
+            }
+        }
     }
 
     /// 发送字符串
     pub fn write_string(&self, s: &str) {
-        for byte in s.bytes() {
-            self.write_byte(byte);
-        }
+        self.write_bytes(s.as_bytes());
     }
 
     /// 发送一行（自动添加换行符）
@@ -148,9 +679,47 @@ impl SerialWriter {
         self.write_string(s);
         self.write_byte(b'\n');
     }
+
+    /// 发送字符串，把控制字符转换成可见的 `^X` 形式（和 `cat -v` 一样）
+    ///
+    /// `\n`、`\r`、`\t` 原样发送；其他 0x20 以下的字节写成 `^` 加上 `字节 + 0x40`
+    /// （如 NUL 为 `^@`、ESC 为 `^[`），DEL（0x7F）写成 `^?`。
+    /// 用于输出可能含有二进制数据的内容，避免干扰对面的终端；需要逐字节原样输出时用 `write_string`
+    pub fn write_string_sanitized(&self, s: &str) {
+        for byte in s.bytes() {
+            match byte {
+                b'\n' | b'\r' | b'\t' => self.write_byte(byte),
+                0x00..=0x1F => {
+                    self.write_byte(b'^');
+                    self.write_byte(byte + 0x40);
+                }
+                0x7F => {
+                    self.write_byte(b'^');
+                    self.write_byte(b'?');
+                }
+                _ => self.write_byte(byte),
+            }
+        }
+    }
+
+    /// 以小写十六进制发送数字（不带 `0x` 前缀），不足 `width` 位时补 0
+    ///
+    /// 手工转换数字，不经过 `core::fmt`，适合早期启动和异常处理
+    pub fn write_hex(&self, value: u64, width: usize) {
+        let mut buf = [0u8; format::HEX_DIGITS_MAX];
+        self.write_bytes(format::hex_digits(value, width, &mut buf));
+    }
+
+    /// 以十进制发送数字，同样不经过 `core::fmt`
+    pub fn write_dec(&self, value: u64) {
+        let mut buf = [0u8; format::DEC_DIGITS_MAX];
+        self.write_bytes(format::dec_digits(value, &mut buf));
+    }
 }
 
 /// 实现 fmt::Write trait，支持格式化输出
+///
+/// 串口发送只会忙等，不会失败，因此 `write_str` 总是返回 `Ok(())`
 impl fmt::Write for SerialWriter {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         self.write_string(s);
@@ -173,8 +742,255 @@ lazy_static! {
 // =============================================================================
 
 /// 初始化 COM1 串口
-pub fn init() {
-    SERIAL1.lock().init();
+///
+/// 串口不存在（回环自检失败）时返回错误，之后的串口输出都会被丢弃，
+/// 内核可以只使用 VGA 输出
+pub fn init() -> Result<(), SerialError> {
+    init_with_fifo(FifoConfig::Trigger14)
+}
+
+/// 使用指定的 FIFO 配置初始化 COM1
+///
+/// 已经初始化过时只输出警告：重新初始化会关掉已经打开的发送中断，丢失缓冲区中的输出
+pub fn init_with_fifo(fifo: FifoConfig) -> Result<(), SerialError> {
+    let serial = SERIAL1.lock();
+    if serial.is_initialized() {
+        serial.write_line("[WARN] serial::init called twice, ignored");
+        return Ok(());
+    }
+    serial.init_with_fifo(fifo)
+}
+
+/// 使用指定的帧格式初始化 COM1（例如 7E1），其余配置和 `init` 相同
+///
+/// 已经初始化过时只输出警告，和 `init_with_fifo` 相同；要修改已初始化串口的格式用 `set_format`
+#[allow(dead_code)]
+pub fn init_with_format(format: LineFormat) -> Result<(), SerialError> {
+    let serial = SERIAL1.lock();
+    if serial.is_initialized() {
+        serial.write_line("[WARN] serial::init called twice, ignored");
+        return Ok(());
+    }
+    serial.init_with_format(format)
+}
+
+/// 修改 COM1 的帧格式（见 `SerialWriter::set_format`）
+#[allow(dead_code)]
+pub fn set_format(format: LineFormat) -> Result<(), SerialError> {
+    SERIAL1.lock().set_format(format)
+}
+
+/// COM1 是否存在并已初始化
+pub fn is_initialized() -> bool {
+    SERIAL1.lock().is_initialized()
+}
+
+/// 因为串口失去响应（发送保持寄存器一直不空）而丢弃的字节数
+///
+/// 正常情况下始终为 0，不断增长说明 UART 已经失效
+#[allow(dead_code)]
+pub fn dropped_bytes() -> u64 {
+    DROPPED_BYTES.load(Ordering::Relaxed)
+}
+
+/// 读取并解码 COM1 的线路状态寄存器（读取会清除错误位）
+pub fn line_status() -> LineStatus {
+    SERIAL1.lock().line_status()
+}
+
+/// 接收中断发现的接收溢出次数
+///
+/// 接收中断读取线路状态时会清除溢出位，`line_status` 很少能直接看到它，
+/// 所以在这里累计；不断增长说明接收中断处理得太慢
+pub fn rx_overruns() -> u64 {
+    RX_OVERRUNS.load(Ordering::Relaxed)
+}
+
+/// 让 COM1 改用中断驱动的发送，需要在 `pic::init()` 之后调用
+pub fn enable_tx_interrupt() {
+    SERIAL1.lock().enable_tx_interrupt();
+}
+
+/// 等待 COM1 已写入的字节全部发送完毕
+///
+/// 一直等到发送器完全空闲（线路状态位 6），而不只是发送保持寄存器空（位 5）：
+/// 启用 FIFO 时最后几个字节可能还在 FIFO 或移位寄存器里。
+/// 停机或退出 QEMU 前调用，保证最后的输出不会被截断
+pub fn flush() {
+    SERIAL1.lock().flush();
+}
+
+/// COM1 中断调用：按中断标识寄存器依次处理所有待处理的中断来源
+///
+/// - 接收到数据：把 UART 中的字节全部读进接收队列
+/// - 发送保持寄存器空：从软件缓冲区取出字节填满发送 FIFO
+pub fn handle_interrupt() {
+    loop {
+        // 读取中断标识寄存器；THRE 中断在读取时被确认（清除）
+        let id = COM1.int_id.read();
+        if id & INT_ID_NONE_PENDING != 0 {
+            break;
+        }
+        match id & INT_ID_MASK {
+            INT_ID_RX_AVAILABLE | INT_ID_RX_TIMEOUT => receive_pending(),
+            INT_ID_TX_EMPTY => transmit_pending(),
+            // 没有启用的来源（线路状态等）：读线路状态寄存器清除后退出
+            _ => {
+                COM1.line_status.read();
+                break;
+            }
+        }
+    }
+}
+
+/// 把 UART 中已收到的字节全部放进接收队列，队列满时丢弃新字节
+///
+/// 线路状态中的溢出位记入 `RX_OVERRUNS`，否则读取后就被清除，丢字节无从察觉
+fn receive_pending() {
+    let mut buffer = RX_BUFFER.lock();
+    loop {
+        let status = COM1.line_status.read();
+        if status & LINE_OVERRUN != 0 {
+            RX_OVERRUNS.fetch_add(1, Ordering::Relaxed);
+        }
+        if status & LINE_DATA_READY == 0 {
+            break;
+        }
+        let byte = COM1.data.read();
+        if !buffer.is_full() {
+            buffer.push(byte);
+        }
+        CONTROL_LINE.lock().feed(byte);
+    }
+}
+
+/// 发送保持寄存器空时，从软件缓冲区取出字节填满发送 FIFO
+///
+/// 关闭 FIFO 时每次只写一个字节；缓冲区发完后关闭 THRE 中断，直到 write_byte 再次写入
+fn transmit_pending() {
+    if COM1.line_status.read() & LINE_TX_EMPTY == 0 {
+        return;
+    }
+
+    let mut buffer = TX_BUFFER.lock();
+    for _ in 0..COM1_TX_BURST.load(Ordering::Relaxed) {
+        match buffer.pop() {
+            Some(byte) => COM1.data.write(byte),
+            None => {
+                COM1.int_enable.write(INT_RX_AVAILABLE);
+                break;
+            }
+        }
+    }
+}
+
+// =============================================================================
+// 串口控制命令
+// =============================================================================
+//
+// 接收中断同时把收到的字节按行收集起来，以 `!loglevel ` 开头的完整行是给内核本身的控制命令，
+// 在中断里直接执行，不依赖命令行是否在运行：
+// - `!loglevel debug|info|warn|error`：调用 `log::set_max_level` 设置日志级别
+// 这些字节仍然会进入接收队列，命令行用 `is_control_command` 跳过已经执行过的行
+
+/// 控制命令的前缀
+const LOGLEVEL_PREFIX: &[u8] = b"!loglevel ";
+
+/// 控制命令行的最大长度，更长的行不可能是控制命令，直接丢弃
+const CONTROL_LINE_MAX: usize = 32;
+
+/// `!loglevel` 命令的解析结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogLevelCommand {
+    /// 设置最详细的日志级别
+    Set(log::Level),
+    /// 不认识的级别名称
+    Invalid,
+}
+
+impl LogLevelCommand {
+    /// 解析一行输入（不含换行符），不是 `!loglevel` 命令时返回 None
+    fn parse(line: &[u8]) -> Option<Self> {
+        let arg = line.strip_prefix(LOGLEVEL_PREFIX)?.trim_ascii();
+        Some(match log::Level::from_name(arg) {
+            Some(level) => LogLevelCommand::Set(level),
+            None => LogLevelCommand::Invalid,
+        })
+    }
+
+    /// 执行命令，返回回显的确认行
+    fn apply(self) -> &'static str {
+        match self {
+            LogLevelCommand::Set(level) => {
+                log::set_max_level(level);
+                match level {
+                    log::Level::Error => "[loglevel] max level: error\r\n",
+                    log::Level::Warn => "[loglevel] max level: warn\r\n",
+                    log::Level::Info => "[loglevel] max level: info\r\n",
+                    log::Level::Debug => "[loglevel] max level: debug\r\n",
+                }
+            }
+            LogLevelCommand::Invalid => "[loglevel] usage: !loglevel debug|info|warn|error\r\n",
+        }
+    }
+}
+
+/// 接收中断收集的当前输入行
+struct ControlLine {
+    bytes: [u8; CONTROL_LINE_MAX],
+    len: usize,
+    /// 这一行超出了 `CONTROL_LINE_MAX`，到行尾之前的字节都丢弃
+    overflow: bool,
+}
+
+impl ControlLine {
+    const fn new() -> Self {
+        ControlLine {
+            bytes: [0; CONTROL_LINE_MAX],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// 收集一个字节；收到行尾时执行其中的控制命令，并在串口回显确认
+    ///
+    /// 在中断中运行，`SERIAL1` 可能正被被打断的代码持有，所以回显不加锁直接轮询发送
+    fn feed(&mut self, byte: u8) {
+        match byte {
+            b'\r' | b'\n' => {
+                if !self.overflow
+                    && let Some(command) = LogLevelCommand::parse(&self.bytes[..self.len])
+                {
+                    force_write_raw(command.apply());
+                }
+                self.len = 0;
+                self.overflow = false;
+            }
+            0x7F | 0x08 => self.len = self.len.saturating_sub(1),
+            _ if self.len < CONTROL_LINE_MAX => {
+                self.bytes[self.len] = byte;
+                self.len += 1;
+            }
+            _ => self.overflow = true,
+        }
+    }
+}
+
+/// COM1 的控制命令行
+static CONTROL_LINE: IrqMutex<ControlLine> = IrqMutex::new(ControlLine::new());
+
+/// 这一行是否是接收中断已经执行过的控制命令
+///
+/// 控制命令的字节同样进入接收队列，命令行读到这样的行时应该跳过，而不是当作未知命令
+pub fn is_control_command(line: &str) -> bool {
+    LogLevelCommand::parse(line.as_bytes()).is_some()
+}
+
+/// 取出一个从 COM1 收到的字节，没有输入时返回 None
+///
+/// 需要先调用 `enable_tx_interrupt` 打开 IRQ4
+pub fn read_char() -> Option<u8> {
+    RX_BUFFER.lock().pop()
 }
 
 /// 通过串口发送一个字节
@@ -193,11 +1009,176 @@ pub fn write_line(s: &str) {
     SERIAL1.lock().write_line(s);
 }
 
+/// 不加锁、不经过发送缓冲区，直接轮询 COM1 发送字符串
+///
+/// **只用于 panic 和早期启动**：`SERIAL1` 还没有初始化，或者可能正被被打断的代码持有时，
+/// 这是唯一保证不会死锁的输出方式。和正常输出交错时内容可能混在一起；
+/// 每个字节最多等待 `TX_TIMEOUT_SPINS` 次，串口不存在时不会卡住
+pub fn force_write_raw(s: &str) {
+    for byte in s.bytes() {
+        write_byte_polling(byte);
+    }
+}
+
+/// 不加锁、不经过发送缓冲区，直接轮询 COM1 发送一个字节
+///
+/// 和 `force_write_raw` 一样最多等待 `TX_TIMEOUT_SPINS` 次
+pub fn write_byte_polling(byte: u8) {
+    for _ in 0..TX_TIMEOUT_SPINS {
+        if COM1.line_status.read() & LINE_TX_EMPTY != 0 {
+            break;
+        }
+    }
+    COM1.data.write(byte);
+}
+
+/// 不经过接收队列，直接轮询 COM1 等待收到一个字节
+///
+/// 供关中断运行的代码（如 GDB 调试桩）使用；没有超时，一直等到有输入为止
+pub fn read_byte_polling() -> u8 {
+    while COM1.line_status.read() & LINE_DATA_READY == 0 {
+        cpu::pause();
+    }
+    COM1.data.read()
+}
+
+/// 通过串口发送字符串，控制字符显示为 `^X`（见 `SerialWriter::write_string_sanitized`）
+#[allow(dead_code)]
+pub fn write_string_sanitized(s: &str) {
+    SERIAL1.lock().write_string_sanitized(s);
+}
+
+/// 通过串口发送十六进制数字（不带 `0x` 前缀），不足 `width` 位时补 0
+#[allow(dead_code)]
+pub fn write_hex(value: u64, width: usize) {
+    SERIAL1.lock().write_hex(value, width);
+}
+
+/// 通过串口发送十进制数字
+#[allow(dead_code)]
+pub fn write_dec(value: u64) {
+    SERIAL1.lock().write_dec(value);
+}
+
+/// 十六进制转储每行显示的字节数
+const HEX_DUMP_BYTES_PER_LINE: usize = 16;
+
+/// 以经典的"十六进制 + ASCII"格式把一段内存输出到串口
+///
+/// 每行 16 字节：行首是地址，中间是两位十六进制字节，
+/// 最后是 ASCII 列（不可打印字节显示为 `.`），例如：
+///
+/// ```text
+/// 00000000000b8000  48 0f 65 0f 6c 0f 6c 0f  6f 0f 2c 0f 20 0f 47 0f  |H.e.l.l.o.,. .G.|
+/// ```
+///
+/// # 参数
+/// - `addr`: 起始地址
+/// - `len`: 字节数
+///
+/// # Safety
+/// `addr..addr + len` 必须是已映射、可读的内存（包括 MMIO，读取使用 volatile）
+#[allow(dead_code)]
+pub unsafe fn hex_dump(addr: usize, len: usize) {
+    use core::fmt::Write;
+
+    let mut serial = SERIAL1.lock();
+    let mut offset = 0;
+    while offset < len {
+        let line_addr = addr + offset;
+        let count = (len - offset).min(HEX_DUMP_BYTES_PER_LINE);
+
+        let mut bytes = [0u8; HEX_DUMP_BYTES_PER_LINE];
+        for (i, byte) in bytes.iter_mut().take(count).enumerate() {
+            *byte = unsafe { core::ptr::read_volatile((line_addr + i) as *const u8) };
+        }
+
+        // 地址列
+        let _ = write!(serial, "{:016x} ", line_addr);
+        // 十六进制列，第 8 个字节后多空一格；不足一行时用空格补齐
+        for (i, byte) in bytes.iter().enumerate() {
+            if i == HEX_DUMP_BYTES_PER_LINE / 2 {
+                serial.write_byte(b' ');
+            }
+            if i < count {
+                let _ = write!(serial, " {:02x}", byte);
+            } else {
+                serial.write_string("   ");
+            }
+        }
+        // ASCII 列
+        serial.write_string("  |");
+        for &byte in &bytes[..count] {
+            let shown = if (0x20..=0x7e).contains(&byte) {
+                byte
+            } else {
+                b'.'
+            };
+            serial.write_byte(shown);
+        }
+        serial.write_line("|");
+
+        offset += count;
+    }
+}
+
 /// 用于 serial_print! 宏的内部打印函数
+///
+/// 忽略 `write_fmt` 的返回值：`SerialWriter::write_str` 不会失败，
+/// 错误只可能来自参数的 `Display` 实现。panic 处理函数也通过这里输出，
+/// unwrap 会导致 panic 中再次 panic
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
-    SERIAL1.lock().write_fmt(args).unwrap();
+    let _ = SERIAL1.lock().write_fmt(args);
+}
+
+/// serial_log! 时间戳是否显示到微秒
+static LOG_MICROSECONDS: AtomicBool = AtomicBool::new(false);
+
+/// 设置 serial_log! 的时间戳是否显示到微秒（需要 TSC 已经校准）
+///
+/// 开启后时间戳形如 `[    1230.456ms]`；TSC 没有校准时仍然只显示毫秒
+#[allow(dead_code)]
+pub fn set_log_microseconds(on: bool) {
+    LOG_MICROSECONDS.store(on, Ordering::Relaxed);
+}
+
+/// 用于 serial_log! 宏的内部打印函数
+///
+/// 在消息前加上启动以来的毫秒数；定时器还没开始计时（tick 为 0）时显示 `?`
+#[doc(hidden)]
+pub fn _log(args: fmt::Arguments) {
+    let uptime_us = if LOG_MICROSECONDS.load(Ordering::Relaxed) {
+        cpu::uptime_us()
+    } else {
+        None
+    };
+
+    if let Some(us) = uptime_us {
+        _print(format_args!(
+            "[{:>8}.{:03}ms] {}\n",
+            us / 1000,
+            us % 1000,
+            args
+        ));
+    } else if crate::timer::ticks() == 0 {
+        _print(format_args!("[{:>8}ms] {}\n", "?", args));
+    } else {
+        _print(format_args!(
+            "[{:>8}ms] {}\n",
+            crate::timer::uptime_ms(),
+            args
+        ));
+    }
+}
+
+/// 用于 serial_debug! 宏的内部打印函数：日志级别不到 `Level::Debug` 时什么也不做
+#[doc(hidden)]
+pub fn _debug(args: fmt::Arguments) {
+    if log::enabled(log::Level::Debug) {
+        _print(format_args!("[DEBUG] {}\n", args));
+    }
 }
 
 // =============================================================================
@@ -216,3 +1197,82 @@ macro_rules! serial_println {
     () => ($crate::serial_print!("\n"));
     ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
 }
+
+/// 向串口打印一行 `[DEBUG]` 开头的调试日志，属于 `log::Level::Debug`，
+/// `log::set_max_level` 设为更简略的级别后不再输出
+#[macro_export]
+macro_rules! serial_debug {
+    ($($arg:tt)*) => ($crate::serial::_debug(format_args!($($arg)*)));
+}
+
+/// 向串口打印带启动时间前缀的日志行，如 `[    1230ms] message`
+#[macro_export]
+macro_rules! serial_log {
+    ($($arg:tt)*) => ($crate::serial::_log(format_args!($($arg)*)));
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn loglevel_command_sets_max_level() {
+        let saved = log::max_level();
+
+        assert_eq!(
+            LogLevelCommand::parse(b"!loglevel error"),
+            Some(LogLevelCommand::Set(log::Level::Error))
+        );
+        LogLevelCommand::Set(log::Level::Error).apply();
+        assert_eq!(log::max_level(), log::Level::Error);
+
+        assert_eq!(
+            LogLevelCommand::parse(b"!loglevel debug "),
+            Some(LogLevelCommand::Set(log::Level::Debug))
+        );
+        LogLevelCommand::Set(log::Level::Debug).apply();
+        assert_eq!(log::max_level(), log::Level::Debug);
+
+        // 无效参数不改变设置；不是控制命令的行被忽略
+        assert_eq!(
+            LogLevelCommand::parse(b"!loglevel loud"),
+            Some(LogLevelCommand::Invalid)
+        );
+        LogLevelCommand::Invalid.apply();
+        assert_eq!(log::max_level(), log::Level::Debug);
+        assert_eq!(LogLevelCommand::parse(b"echo !loglevel debug"), None);
+
+        log::set_max_level(saved);
+    }
+
+    #[test_case]
+    fn only_loglevel_lines_are_control_commands() {
+        assert!(is_control_command("!loglevel info"));
+        assert!(is_control_command("!loglevel bogus"));
+        assert!(!is_control_command("!reboot"));
+        assert!(!is_control_command("help"));
+    }
+
+    #[test_case]
+    fn line_format_encodes_lcr_and_rejects_invalid_stop_bits() {
+        assert_eq!(LineFormat::EIGHT_N_ONE.lcr_value(), Ok(0x03));
+        let seven_e_one = LineFormat::new(DataBits::Seven, Parity::Even, StopBits::One);
+        assert_eq!(seven_e_one.lcr_value(), Ok(0x1A));
+        let eight_o_two = LineFormat::new(DataBits::Eight, Parity::Odd, StopBits::Two);
+        assert_eq!(eight_o_two.lcr_value(), Ok(0x0F));
+        let five_n_one_half = LineFormat::new(DataBits::Five, Parity::None, StopBits::OneAndHalf);
+        assert_eq!(five_n_one_half.lcr_value(), Ok(0x04));
+
+        let five_n_two = LineFormat::new(DataBits::Five, Parity::None, StopBits::Two);
+        assert_eq!(five_n_two.lcr_value(), Err(SerialError::InvalidFormat));
+        let eight_n_one_half = LineFormat::new(DataBits::Eight, Parity::None, StopBits::OneAndHalf);
+        assert_eq!(
+            eight_n_one_half.lcr_value(),
+            Err(SerialError::InvalidFormat)
+        );
+    }
+}
@@ -0,0 +1,305 @@
+//! ANSI 转义序列解析
+//!
+//! 串口另一端通常是终端模拟器，它使用 ANSI 转义序列表示颜色和方向键：
+//! - 输出方向：`\x1b[31m` 这类 SGR（Select Graphic Rendition）序列设置颜色，
+//!   同一段文本镜像到 VGA 时，需要把它们换算成最接近的 VGA 颜色
+//! - 输入方向：方向键会以 `\x1b[A`（上）、`\x1b[B`（下）、`\x1b[C`（右）、`\x1b[D`（左）的形式到达；
+//!   终端处于应用光标模式时改用 SS3 形式 `\x1bOA`~`\x1bOD`，F1~F4 是 `\x1bOP`~`\x1bOS`，
+//!   单独按下的 Esc 键就是一个孤立的 ESC
+//!
+//! [`Parser`] 是一个逐字节推进的小状态机，状态保存在结构体里，
+//! 所以一个序列被拆成多次读取时也能正确解析
+
+use crate::vga::{self, Color};
+
+// =============================================================================
+// 解析结果
+// =============================================================================
+
+/// ESC 字符
+const ESC: u8 = 0x1b;
+
+/// 一个 CSI 序列最多保存的参数个数，多余的参数被忽略
+const MAX_PARAMS: usize = 8;
+
+/// 方向键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrow {
+    Up,
+    Down,
+    Right,
+    Left,
+}
+
+/// SGR 序列的参数列表（如 `\x1b[1;31m` → [1, 31]）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SgrParams {
+    values: [u16; MAX_PARAMS],
+    len: usize,
+}
+
+impl SgrParams {
+    /// 参数切片；省略的参数按 0 计（`\x1b[m` 等价于 `\x1b[0m`）
+    pub fn as_slice(&self) -> &[u16] {
+        &self.values[..self.len]
+    }
+}
+
+/// 解析器每消费一个字节产生的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// 普通字节，原样输出
+    Print(u8),
+    /// 完整的 SGR 序列（`ESC [ ... m`）
+    Sgr(SgrParams),
+    /// 方向键序列（`ESC [ A/B/C/D` 或 `ESC O A/B/C/D`）
+    Arrow(Arrow),
+    /// 功能键 F1~F4（`ESC O P/Q/R/S`），值为 1~4
+    Function(u8),
+    /// 单独的 Esc 键：ESC 后面跟着的字节不能开始转义序列
+    ///
+    /// 要等下一个字节到达才能和转义序列区分开，所以会晚一个字节才产生；
+    /// 那个字节暂存在解析器中，由 `take_pending` 取出它的动作
+    Escape,
+}
+
+// =============================================================================
+// 状态机
+// =============================================================================
+
+/// 解析器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// 普通文本
+    Ground,
+    /// 刚收到 ESC
+    Escape,
+    /// 收到 `ESC [`，正在读取参数
+    Csi,
+    /// 收到 `ESC O`（SS3），等待一个结束字节
+    Ss3,
+}
+
+/// ANSI 转义序列解析器
+#[derive(Debug, Clone, Copy)]
+pub struct Parser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    /// 产生 `Action::Escape` 时跟在 ESC 后面、还没有处理的字节
+    pending: Option<u8>,
+}
+
+impl Parser {
+    /// 创建处于普通文本状态的解析器
+    pub const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            params: [0; MAX_PARAMS],
+            len: 0,
+            pending: None,
+        }
+    }
+
+    /// 处理 `Action::Escape` 之后暂存的字节，返回它产生的动作
+    ///
+    /// 每次 `advance` 返回 `Action::Escape` 之后都应该调用一次，否则这个字节会丢失
+    pub fn take_pending(&mut self) -> Option<Action> {
+        let byte = self.pending.take()?;
+        self.advance(byte)
+    }
+
+    /// 输入一个字节，推进状态机
+    ///
+    /// # 返回
+    /// 字节完成了一个动作时返回 `Some`；处于序列中间或序列不被支持时返回 `None`
+    pub fn advance(&mut self, byte: u8) -> Option<Action> {
+        match self.state {
+            State::Ground => {
+                if byte == ESC {
+                    self.state = State::Escape;
+                    None
+                } else {
+                    Some(Action::Print(byte))
+                }
+            }
+            State::Escape => match byte {
+                b'[' => {
+                    self.begin_csi();
+                    None
+                }
+                b'O' => {
+                    self.state = State::Ss3;
+                    None
+                }
+                // 连续两个 ESC：前一个是单独的 Esc 键，后一个可能开始新的序列
+                ESC => Some(Action::Escape),
+                // 其他字节不能开始转义序列：ESC 是单独的 Esc 键，当前字节暂存
+                _ => {
+                    self.state = State::Ground;
+                    self.pending = Some(byte);
+                    Some(Action::Escape)
+                }
+            },
+            State::Ss3 => {
+                self.state = State::Ground;
+                match byte {
+                    b'P'..=b'S' => Some(Action::Function(byte - b'P' + 1)),
+                    _ => Self::arrow(byte).map(Action::Arrow),
+                }
+            }
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    self.push_digit(byte - b'0');
+                    None
+                }
+                b';' => {
+                    self.finish_param();
+                    None
+                }
+                // 结束字节
+                0x40..=0x7e => {
+                    self.finish_param();
+                    self.state = State::Ground;
+                    self.dispatch(byte)
+                }
+                // 中间字节等其他内容：继续等待结束字节
+                _ => None,
+            },
+        }
+    }
+
+    /// 进入 CSI 状态，清空参数
+    fn begin_csi(&mut self) {
+        self.state = State::Csi;
+        self.params = [0; MAX_PARAMS];
+        self.len = 0;
+    }
+
+    /// 给当前参数追加一位数字
+    fn push_digit(&mut self, digit: u8) {
+        if self.len < MAX_PARAMS {
+            let param = &mut self.params[self.len];
+            *param = param.saturating_mul(10).saturating_add(digit as u16);
+        }
+    }
+
+    /// 结束当前参数（遇到 `;` 或结束字节）
+    fn finish_param(&mut self) {
+        if self.len < MAX_PARAMS {
+            self.len += 1;
+        }
+    }
+
+    /// 根据结束字节生成动作
+    fn dispatch(&self, final_byte: u8) -> Option<Action> {
+        match final_byte {
+            b'm' => Some(Action::Sgr(SgrParams {
+                values: self.params,
+                len: self.len,
+            })),
+            _ => Self::arrow(final_byte).map(Action::Arrow),
+        }
+    }
+
+    /// 方向键序列的结束字节（CSI 和 SS3 相同）
+    const fn arrow(final_byte: u8) -> Option<Arrow> {
+        match final_byte {
+            b'A' => Some(Arrow::Up),
+            b'B' => Some(Arrow::Down),
+            b'C' => Some(Arrow::Right),
+            b'D' => Some(Arrow::Left),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================
+// SGR → VGA 颜色
+// =============================================================================
+
+/// 把 SGR 参数应用到 VGA Writer 上
+///
+/// 支持：0 重置、1/22 高亮开关、7/27 反色开关、
+/// 30-37/90-97 前景色、39 默认前景、40-47/100-107 背景色、49 默认背景
+///
+/// "默认"指 Writer 的默认颜色（见 `vga::set_default_color`）
+pub fn apply_sgr(writer: &mut vga::Writer, params: &SgrParams) {
+    for &param in params.as_slice() {
+        let color = writer.color();
+        let default = writer.default_color();
+        match param {
+            0 => {
+                writer.set_color(default);
+                writer.set_bright(false);
+                writer.set_inverse(false);
+            }
+            1 => writer.set_bright(true),
+            22 => writer.set_bright(false),
+            7 => writer.set_inverse(true),
+            27 => writer.set_inverse(false),
+            30..=37 | 90..=97 => {
+                if let Some(fg) = Color::from_ansi_fg(param as u8) {
+                    writer.set_color(color.with_foreground(fg));
+                }
+            }
+            39 => writer.set_color(color.with_foreground(default.foreground())),
+            40..=47 | 100..=107 => {
+                if let Some(bg) = Color::from_ansi_bg(param as u8) {
+                    writer.set_color(color.with_background(bg));
+                }
+            }
+            49 => writer.set_color(color.with_background(default.background())),
+            // 其他属性（下划线、斜体等）VGA 无法表现，忽略
+            _ => {}
+        }
+    }
+}
+
+// =============================================================================
+// 镜像输出
+// =============================================================================
+
+/// 把带 ANSI 颜色序列的文本同时输出到串口和 VGA
+///
+/// 串口收到原始文本（由终端解释转义序列）；
+/// VGA 上转义序列被去掉，SGR 颜色换算成最接近的 VGA 颜色
+pub fn write_mirrored(s: &str) {
+    super::write_string(s);
+
+    let mut parser = Parser::new();
+    let mut writer = vga::WRITER.lock();
+    // 连续的普通文本作为一段交给 write_string，保证多字节 UTF-8 字符完整
+    let mut run_start = 0;
+    for (i, byte) in s.bytes().enumerate() {
+        if parser.state == State::Ground && byte != ESC {
+            parser.advance(byte);
+            continue;
+        }
+
+        // 转义序列开始或进行中：先输出之前的文本
+        if run_start < i {
+            writer.write_string(&s[run_start..i]);
+        }
+        run_start = i + 1;
+        match parser.advance(byte) {
+            Some(Action::Sgr(params)) => apply_sgr(&mut writer, &params),
+            // ESC 后面的字节不能开始转义序列：丢弃 ESC，这个字节作为下一段普通文本的开头
+            Some(Action::Escape) => {
+                if parser.take_pending().is_some() {
+                    run_start = i;
+                }
+            }
+            _ => {}
+        }
+    }
+    if run_start < s.len() {
+        writer.write_string(&s[run_start..]);
+    }
+}
@@ -0,0 +1,293 @@
+//! GwenOS 内置命令行（mini-shell）
+//!
+//! 读取一行键盘输入，按空白拆分成命令名和参数，
+//! 然后在命令表中查找并调用对应的处理函数
+
+use crate::{cmos, gdbstub, input, memory, print, println, ramfs, serial, system, timer, vga};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// =============================================================================
+// 命令表
+// =============================================================================
+
+/// 命令提示符
+const PROMPT: &str = "gwen> ";
+
+/// 命令处理函数，参数不包含命令名本身
+type Handler = fn(&[&str]);
+
+/// 一条内置命令
+struct Command {
+    /// 命令名
+    name: &'static str,
+    /// help 中显示的说明
+    help: &'static str,
+    /// 处理函数
+    handler: Handler,
+}
+
+/// 所有内置命令
+static COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        help: "list available commands",
+        handler: cmd_help,
+    },
+    Command {
+        name: "clear",
+        help: "clear the screen",
+        handler: cmd_clear,
+    },
+    Command {
+        name: "echo",
+        help: "print the arguments",
+        handler: cmd_echo,
+    },
+    Command {
+        name: "uptime",
+        help: "show time since boot",
+        handler: cmd_uptime,
+    },
+    Command {
+        name: "colortest",
+        help: "show all 16 colors",
+        handler: cmd_colortest,
+    },
+    Command {
+        name: "serstat",
+        help: "show COM1 line status and error counters",
+        handler: cmd_serstat,
+    },
+    Command {
+        name: "ls",
+        help: "list files in the ramfs",
+        handler: cmd_ls,
+    },
+    Command {
+        name: "cat",
+        help: "print a file: cat <name>",
+        handler: cmd_cat,
+    },
+    Command {
+        name: "write",
+        help: "replace a file's contents: write <name> <text...>",
+        handler: cmd_write,
+    },
+    Command {
+        name: "rm",
+        help: "remove a file: rm <name>",
+        handler: cmd_rm,
+    },
+    Command {
+        name: "peek",
+        help: "hex dump memory to COM1: peek <addr> <len>",
+        handler: cmd_peek,
+    },
+    Command {
+        name: "poke",
+        help: "write one byte: poke <addr> <byte>",
+        handler: cmd_poke,
+    },
+    Command {
+        name: "gdb",
+        help: "stop and wait for a gdb connection on COM1",
+        handler: cmd_gdb,
+    },
+    Command {
+        name: "reboot",
+        help: "restart the machine",
+        handler: cmd_reboot,
+    },
+];
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 运行命令行主循环（不返回）
+///
+/// 调用时必须已开启中断（键盘输入依赖 IRQ1）；
+/// 每执行完一条命令检查一次时钟看门狗，发现命令关中断后没有恢复
+pub fn run() -> ! {
+    loop {
+        timer::watchdog_check();
+        print!("{}", PROMPT);
+        let line = input::read_line();
+        execute(&line);
+    }
+}
+
+/// 解析并执行一行命令
+///
+/// 空行和串口接收中断已经执行过的 `!loglevel` 控制命令直接忽略，未知命令打印错误信息
+pub fn execute(line: &str) {
+    if serial::is_control_command(line) {
+        return;
+    }
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return;
+    };
+    let args: Vec<&str> = words.collect();
+
+    match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => (command.handler)(&args),
+        None => println!("unknown command: {} (type 'help' for a list)", name),
+    }
+}
+
+// =============================================================================
+// 内置命令
+// =============================================================================
+
+/// help：列出所有命令
+fn cmd_help(_args: &[&str]) {
+    for command in COMMANDS {
+        // 说明较长时按单词换行，不在单词中间断开
+        vga::write_string_wrapped(&format!("  {:<8} {}\n", command.name, command.help));
+    }
+}
+
+/// clear：清空屏幕（保留状态栏）
+fn cmd_clear(_args: &[&str]) {
+    vga::clear_content();
+}
+
+/// echo：原样打印参数（以单个空格分隔）
+fn cmd_echo(args: &[&str]) {
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            print!(" ");
+        }
+        print!("{}", arg);
+    }
+    println!();
+}
+
+/// uptime：显示启动以来的时间，以及时钟中断和 RTC 周期中断的次数
+fn cmd_uptime(_args: &[&str]) {
+    let ms = timer::uptime_ms();
+    println!("up {}.{:03} s", ms / 1000, ms % 1000);
+    println!(
+        "timer ticks: {}, rtc ticks: {}",
+        timer::ticks(),
+        cmos::periodic_ticks()
+    );
+}
+
+/// colortest：显示调色板测试图案
+fn cmd_colortest(_args: &[&str]) {
+    vga::color_test();
+}
+
+/// serstat：显示 COM1 的线路状态和错误计数
+fn cmd_serstat(_args: &[&str]) {
+    if !serial::is_initialized() {
+        println!("COM1 not present");
+        return;
+    }
+    println!("line status: {}", serial::line_status());
+    println!(
+        "rx overruns: {}, tx dropped: {}",
+        serial::rx_overruns(),
+        serial::dropped_bytes()
+    );
+}
+
+/// ls：列出 ramfs 中的文件和大小
+fn cmd_ls(_args: &[&str]) {
+    let fs = ramfs::RAMFS.lock();
+    for (name, len) in fs.list() {
+        println!("  {:<16} {:>6} bytes", name, len);
+    }
+}
+
+/// cat：打印文件内容，非 UTF-8 的字节显示为替换字符
+fn cmd_cat(args: &[&str]) {
+    let [name] = args else {
+        println!("usage: cat <name>");
+        return;
+    };
+    match ramfs::RAMFS.lock().read(name) {
+        Ok(contents) => println!("{}", String::from_utf8_lossy(contents)),
+        Err(e) => println!("cat: {}: {}", name, e),
+    }
+}
+
+/// write：用参数（以单个空格连接）替换文件内容，文件不存在时创建
+fn cmd_write(args: &[&str]) {
+    let [name, words @ ..] = args else {
+        println!("usage: write <name> <text...>");
+        return;
+    };
+    ramfs::RAMFS.lock().write(name, words.join(" ").as_bytes());
+}
+
+/// rm：删除文件
+fn cmd_rm(args: &[&str]) {
+    let [name] = args else {
+        println!("usage: rm <name>");
+        return;
+    };
+    if let Err(e) = ramfs::RAMFS.lock().remove(name) {
+        println!("rm: {}: {}", name, e);
+    }
+}
+
+/// 解析数字参数：`0x` 开头按十六进制，否则按十进制
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// peek：把一段内存以十六进制转储的格式输出到串口
+fn cmd_peek(args: &[&str]) {
+    let [addr, len] = args else {
+        println!("usage: peek <addr> <len>");
+        return;
+    };
+    let (Some(addr), Some(len)) = (parse_number(addr), parse_number(len)) else {
+        println!("peek: invalid number");
+        return;
+    };
+    match memory::peek(addr, len as usize) {
+        Ok(()) => println!("{} bytes at {:#x} dumped to COM1", len, addr),
+        Err(e) => println!("peek: {}", e),
+    }
+}
+
+/// poke：向一个地址写入一个字节
+fn cmd_poke(args: &[&str]) {
+    let [addr, value] = args else {
+        println!("usage: poke <addr> <byte>");
+        return;
+    };
+    let Some(addr) = parse_number(addr) else {
+        println!("poke: invalid address");
+        return;
+    };
+    let Some(value) = parse_number(value).and_then(|value| u8::try_from(value).ok()) else {
+        println!("poke: byte must be 0..=255");
+        return;
+    };
+    if let Err(e) = memory::poke(addr, value) {
+        println!("poke: {}", e);
+    }
+}
+
+/// gdb：启用 GDB 调试桩并触发断点，等待宿主机上的 GDB 通过 COM1 连接
+fn cmd_gdb(_args: &[&str]) {
+    println!("waiting for gdb on COM1...");
+    gdbstub::enable();
+    x86_64::instructions::interrupts::int3();
+}
+
+/// reboot：重启计算机
+fn cmd_reboot(_args: &[&str]) {
+    println!("rebooting...");
+    system::reboot();
+}
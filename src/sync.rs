@@ -0,0 +1,134 @@
+//! GwenOS 同步原语模块
+//!
+//! 提供可以和中断处理函数安全共享数据的锁
+
+use crate::cpu;
+use core::ops::{Deref, DerefMut};
+use spin::{Mutex, MutexGuard};
+
+// =============================================================================
+// IrqMutex
+// =============================================================================
+
+/// 加锁期间关闭中断的自旋锁
+///
+/// 普通的 `spin::Mutex` 被主循环持有时，如果中断处理函数也去获取它，
+/// 处理函数会永远自旋（单核上持锁的代码没有机会运行），造成死锁。
+/// `IrqMutex` 在加锁前关闭中断，解锁后再恢复原来的中断状态，
+/// 所以和中断处理函数共享的数据（键盘队列、串口缓冲区等）都应使用它
+///
+/// # 示例
+/// ```ignore
+/// static QUEUE: IrqMutex<Queue> = IrqMutex::new(Queue::new());
+/// QUEUE.lock().push(byte); // 持锁期间不会被中断打断
+/// ```
+pub struct IrqMutex<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> IrqMutex<T> {
+    /// 创建新的锁
+    pub const fn new(value: T) -> Self {
+        IrqMutex {
+            inner: Mutex::new(value),
+        }
+    }
+
+    /// 关闭中断并加锁
+    ///
+    /// 返回的守卫被丢弃时先解锁，再恢复加锁前的中断状态
+    /// （在中断处理函数中加锁时中断本来就是关闭的，解锁后仍保持关闭）
+    pub fn lock(&self) -> IrqMutexGuard<'_, T> {
+        let irq = cpu::disable_guard();
+        IrqMutexGuard {
+            guard: self.inner.lock(),
+            _irq: irq,
+        }
+    }
+
+    /// 尝试关闭中断并加锁，锁已被占用时立即返回 None，中断状态保持不变
+    ///
+    /// 用于不能等待的场合（例如 panic 处理中，持锁的代码可能再也不会运行）
+    pub fn try_lock(&self) -> Option<IrqMutexGuard<'_, T>> {
+        let irq = cpu::disable_guard();
+        let guard = self.inner.try_lock()?;
+        Some(IrqMutexGuard { guard, _irq: irq })
+    }
+}
+
+/// `IrqMutex::lock` 返回的守卫
+///
+/// 字段按声明顺序丢弃：必须先解锁再开中断，否则中断处理函数可能在解锁前拿不到锁
+pub struct IrqMutexGuard<'a, T> {
+    guard: MutexGuard<'a, T>,
+    _irq: cpu::InterruptGuard,
+}
+
+impl<T> Deref for IrqMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for IrqMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn lock_is_exclusive() {
+        let mutex = IrqMutex::new(0u32);
+        {
+            let mut guard = mutex.lock();
+            *guard += 1;
+            assert!(mutex.try_lock().is_none());
+        }
+        let mut guard = mutex.try_lock().expect("lock released on drop");
+        *guard += 1;
+        assert_eq!(*guard, 2);
+    }
+
+    #[test_case]
+    fn lock_disables_interrupts_until_dropped() {
+        let mutex = IrqMutex::new(());
+        assert!(cpu::interrupts_enabled());
+        {
+            let _guard = mutex.lock();
+            assert!(!cpu::interrupts_enabled());
+        }
+        assert!(cpu::interrupts_enabled());
+    }
+
+    #[test_case]
+    fn failed_try_lock_restores_interrupts() {
+        let mutex = IrqMutex::new(());
+        let outer = IrqMutex::new(());
+        let held = mutex.lock();
+        // 持锁期间中断是关闭的，失败的 try_lock 不能把它打开
+        assert!(mutex.try_lock().is_none());
+        assert!(!cpu::interrupts_enabled());
+        drop(held);
+        assert!(cpu::interrupts_enabled());
+
+        // 嵌套加锁：内层解锁后中断仍然关闭
+        {
+            let _outer = outer.lock();
+            {
+                let _inner = mutex.lock();
+            }
+            assert!(!cpu::interrupts_enabled());
+        }
+        assert!(cpu::interrupts_enabled());
+    }
+}
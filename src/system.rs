@@ -0,0 +1,78 @@
+//! GwenOS 系统控制模块
+//!
+//! 提供重启和关机功能
+//!
+//! # 说明
+//! - 重启：通过键盘控制器（8042）拉低 CPU 复位线，失败时退回到三重故障
+//! - 关机：只支持 QEMU/Bochs 模拟器的 ACPI 关机端口；
+//!   真实硬件需要解析 ACPI 表（FADT/DSDT）才能关机，不在本模块范围内
+
+use crate::cpu;
+use crate::port::{inb, outb, outw};
+use x86_64::VirtAddr;
+use x86_64::structures::DescriptorTablePointer;
+
+// =============================================================================
+// 端口与命令定义
+// =============================================================================
+
+/// 8042 键盘控制器的状态/命令端口
+const KBC_STATUS_PORT: u16 = 0x64;
+
+/// 状态寄存器位 1：输入缓冲区满（控制器还没取走上一个命令）
+const KBC_INPUT_FULL: u8 = 0x02;
+
+/// 键盘控制器命令：脉冲拉低 CPU 复位线
+const KBC_CMD_RESET: u8 = 0xFE;
+
+/// QEMU（新版 i440fx/q35）与 Bochs 的 ACPI 关机端口
+const QEMU_ACPI_SHUTDOWN_PORT: u16 = 0x604;
+
+/// 写入关机端口的值（SLP_TYPa | SLP_EN）
+const QEMU_ACPI_SHUTDOWN_VALUE: u16 = 0x2000;
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 重启计算机
+///
+/// 1. 等待键盘控制器空闲，然后发送复位命令 0xFE
+/// 2. 如果机器没有复位，加载一个空的 IDT 并触发中断，
+///    CPU 找不到任何处理函数，最终三重故障导致复位
+#[allow(dead_code)]
+pub fn reboot() -> ! {
+    cpu::disable_interrupts();
+
+    // 等待输入缓冲区清空，再发送复位命令
+    while inb(KBC_STATUS_PORT) & KBC_INPUT_FULL != 0 {
+        cpu::pause();
+    }
+    outb(KBC_STATUS_PORT, KBC_CMD_RESET);
+
+    // 备用方案：三重故障
+    let null_idt = DescriptorTablePointer {
+        limit: 0,
+        base: VirtAddr::new(0),
+    };
+    unsafe {
+        x86_64::instructions::tables::lidt(&null_idt);
+    }
+    x86_64::instructions::interrupts::int3();
+
+    // 不应到达这里
+    cpu::hlt_loop();
+}
+
+/// 关闭计算机（仅限模拟器）
+///
+/// 向 QEMU/Bochs 的 ACPI 关机端口写入关机命令
+/// 在真实硬件上这个端口没有意义，调用后会停在 hlt 循环中
+#[allow(dead_code)]
+pub fn shutdown() -> ! {
+    cpu::disable_interrupts();
+    outw(QEMU_ACPI_SHUTDOWN_PORT, QEMU_ACPI_SHUTDOWN_VALUE);
+
+    // 关机失败（真实硬件）时停机
+    cpu::hlt_loop();
+}
@@ -0,0 +1,84 @@
+//! GwenOS 文本排版模块
+//!
+//! 一行写满后换行、制表位停在哪一列、到达区域底部时是否滚动——这些决定只和宽高有关，
+//! 和显存无关。`vga::Writer`（整屏的滚动区域）和 `vga::Window`（任意矩形）都通过
+//! `TextLayout` 做这些决定；`vga::Writer` 的宽高是泛型参数，
+//! 排版逻辑可以用真正的 Writer 在任意大小（例如 8×4）的数组上测试，不需要 VGA 硬件
+
+/// 制表位宽度（列数）
+pub const TAB_WIDTH: usize = 8;
+
+/// 换行的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineFeed {
+    /// 光标移到这一行
+    Next(usize),
+    /// 光标已经在区域最后一行：区域整体上移一行，光标留在原来的行
+    Scroll,
+}
+
+/// 一块文本区域的排版参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextLayout {
+    /// 一行最多写的列数
+    pub width: usize,
+    /// 滚动区域的起始行（包含）
+    pub top: usize,
+    /// 滚动区域的结束行（不包含）
+    pub bottom: usize,
+}
+
+impl TextLayout {
+    /// 宽 `width` 列、在 `top..bottom` 行内滚动的区域
+    pub const fn new(width: usize, top: usize, bottom: usize) -> Self {
+        TextLayout { width, top, bottom }
+    }
+
+    /// 从第 `col` 列按制表键后光标停在哪一列（不超过行宽）
+    pub const fn tab_stop(&self, col: usize) -> usize {
+        let stop = (col / TAB_WIDTH + 1) * TAB_WIDTH;
+        if stop < self.width { stop } else { self.width }
+    }
+
+    /// 光标在第 `col` 列时这一行是否已经写满（再写一个字符前需要换行）
+    pub const fn is_line_full(&self, col: usize) -> bool {
+        col >= self.width
+    }
+
+    /// 光标在第 `row` 行时换行的结果
+    pub const fn line_feed(&self, row: usize) -> LineFeed {
+        if row + 1 < self.bottom {
+            LineFeed::Next(row + 1)
+        } else {
+            LineFeed::Scroll
+        }
+    }
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn tabs_stop_at_multiples_and_line_end() {
+        let layout = TextLayout::new(12, 0, 4);
+        assert_eq!(layout.tab_stop(0), 8);
+        assert_eq!(layout.tab_stop(8), 12);
+        assert_eq!(TextLayout::new(8, 0, 4).tab_stop(2), 8);
+    }
+
+    #[test_case]
+    fn lines_fill_at_width_and_scroll_at_region_bottom() {
+        // 最后一行是状态栏，不参与滚动
+        let layout = TextLayout::new(8, 0, 3);
+        assert!(!layout.is_line_full(7));
+        assert!(layout.is_line_full(8));
+        assert_eq!(layout.line_feed(0), LineFeed::Next(1));
+        assert_eq!(layout.line_feed(1), LineFeed::Next(2));
+        assert_eq!(layout.line_feed(2), LineFeed::Scroll);
+    }
+}
@@ -0,0 +1,219 @@
+//! GwenOS 定时器模块
+//!
+//! 使用 8253/8254 PIT（可编程间隔定时器）产生周期性时钟中断，
+//! 并维护系统启动以来的 tick 计数
+//!
+//! PIT 有三个通道：
+//! - 通道 0：连接 IRQ0，用作系统时钟
+//! - 通道 1：历史遗留（DRAM 刷新），不使用
+//! - 通道 2：连接 PC 喇叭，用于发声
+
+use crate::port::{inb, outb};
+use crate::{cpu, pic, serial_println};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// =============================================================================
+// PIT 常量定义
+// =============================================================================
+
+/// PIT 的输入时钟频率（Hz）
+const PIT_FREQUENCY: u32 = 1_193_182;
+
+/// 系统时钟频率（每秒 tick 数）
+pub const TICK_HZ: u32 = 100;
+
+/// 每个 tick 对应的毫秒数
+pub const MS_PER_TICK: u64 = 1000 / TICK_HZ as u64;
+
+// PIT 端口地址
+const PIT_CHANNEL0: u16 = 0x40; // 通道 0 数据端口
+const PIT_CHANNEL2: u16 = 0x42; // 通道 2 数据端口
+const PIT_COMMAND: u16 = 0x43; // 模式/命令端口
+
+// PIT 命令字
+const CMD_CHANNEL0_RATE: u8 = 0x36; // 通道 0，先低后高字节，模式 3（方波）
+const CMD_CHANNEL2_SQUARE: u8 = 0xB6; // 通道 2，先低后高字节，模式 3（方波）
+
+/// PC 喇叭控制端口
+/// - 位 0：通道 2 门控（GATE2）
+/// - 位 1：喇叭数据使能
+const SPEAKER_PORT: u16 = 0x61;
+
+/// 定时器使用的 IRQ 线
+pub const TIMER_IRQ: u8 = 0;
+
+// =============================================================================
+// tick 计数
+// =============================================================================
+
+/// 启动以来的时钟中断次数
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// 时钟中断（PIT 或本地 APIC 定时器，同一时间只有一个在运行）调用：tick 计数加一
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 获取启动以来的 tick 数
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// 获取启动以来的毫秒数（精度为一个 tick）
+pub fn uptime_ms() -> u64 {
+    ticks() * MS_PER_TICK
+}
+
+// =============================================================================
+// 看门狗
+// =============================================================================
+
+/// 看门狗窗口（tick 数），0 表示关闭
+static WATCHDOG_WINDOW: AtomicU64 = AtomicU64::new(0);
+
+/// 上一次检查时看到的 tick 数
+static WATCHDOG_LAST_TICK: AtomicU64 = AtomicU64::new(0);
+
+/// tick 数上一次发生变化时的 TSC 值
+static WATCHDOG_LAST_TSC: AtomicU64 = AtomicU64::new(0);
+
+/// 设置看门狗窗口
+///
+/// 之后如果 `watchdog_check` 发现 tick 数在 `ticks` 个 tick 的时间内都没有变化，
+/// 就认为时钟中断停了（通常是关中断后忘了重新打开）。传 0 关闭看门狗
+pub fn set_watchdog(ticks: u64) {
+    WATCHDOG_LAST_TICK.store(self::ticks(), Ordering::Relaxed);
+    WATCHDOG_LAST_TSC.store(cpu::rdtsc(), Ordering::Relaxed);
+    WATCHDOG_WINDOW.store(ticks, Ordering::Relaxed);
+}
+
+/// 检查时钟中断是否还在推进 tick，由主循环定期调用
+///
+/// tick 停止的时间用 TSC 测量，所以需要先调用 `cpu::calibrate_tsc()`，否则不做任何事。
+/// 超过窗口时在串口输出警告（测试中直接 panic），之后每过一个窗口再警告一次
+pub fn watchdog_check() {
+    let window = WATCHDOG_WINDOW.load(Ordering::Relaxed);
+    if window == 0 {
+        return;
+    }
+    let Some(khz) = cpu::tsc_khz() else {
+        return;
+    };
+
+    let now_tick = ticks();
+    let now_tsc = cpu::rdtsc();
+    if WATCHDOG_LAST_TICK.swap(now_tick, Ordering::Relaxed) != now_tick {
+        WATCHDOG_LAST_TSC.store(now_tsc, Ordering::Relaxed);
+        return;
+    }
+
+    let stalled_ms = now_tsc.wrapping_sub(WATCHDOG_LAST_TSC.load(Ordering::Relaxed)) / khz;
+    if stalled_ms < window * MS_PER_TICK {
+        return;
+    }
+    WATCHDOG_LAST_TSC.store(now_tsc, Ordering::Relaxed);
+
+    serial_println!(
+        "[WARN] Timer stalled: no ticks for {} ms (interrupts enabled: {})",
+        stalled_ms,
+        cpu::interrupts_enabled()
+    );
+    #[cfg(test)]
+    panic!("timer stalled");
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// `init` 是否已经调用过
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// 初始化 PIT 通道 0 并打开时钟中断
+///
+/// 需要在 `pic::init()` 之后调用；重复调用只输出警告
+pub fn init() {
+    if INITIALIZED.swap(true, Ordering::SeqCst) {
+        serial_println!("[WARN] timer::init called twice, ignored");
+        return;
+    }
+    let divisor = pit_divisor(TICK_HZ);
+    outb(PIT_COMMAND, CMD_CHANNEL0_RATE);
+    outb(PIT_CHANNEL0, (divisor & 0xFF) as u8);
+    outb(PIT_CHANNEL0, (divisor >> 8) as u8);
+
+    pic::unmask(TIMER_IRQ);
+}
+
+/// PIT 是否已经开始产生时钟中断
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
+
+/// 等待 tick 计数前进 `n`
+///
+/// 用 `hlt` 等待时钟中断推进 tick，等待期间 CPU 处于停机状态，不会空转。
+/// 调用时必须已开启中断，否则 tick 永远不会增加：这种情况下在串口报警
+/// （测试中直接 panic）并立即返回，而不是永远卡住
+pub fn busy_sleep_ticks(n: u64) {
+    if !cpu::interrupts_enabled() {
+        serial_println!(
+            "[WARN] busy_sleep_ticks({}) called with interrupts disabled",
+            n
+        );
+        #[cfg(test)]
+        panic!("busy_sleep_ticks with interrupts disabled");
+        #[cfg(not(test))]
+        return;
+    }
+
+    let start = ticks();
+    while ticks().wrapping_sub(start) < n {
+        cpu::hlt();
+    }
+}
+
+/// 睡眠指定毫秒数
+///
+/// 精度为一个 tick（10ms），规则同 `busy_sleep_ticks`
+pub fn sleep_ms(ms: u64) {
+    busy_sleep_ticks(ms.div_ceil(MS_PER_TICK));
+}
+
+/// 让 PC 喇叭以指定频率发声一段时间
+///
+/// 通过 PIT 通道 2 产生方波，并打开端口 0x61 的喇叭门控位
+/// 结束后恢复端口 0x61 的原始值，不影响其中与喇叭无关的位
+///
+/// # 参数
+/// - `frequency_hz`: 声音频率（Hz），为 0 时不发声
+/// - `duration_ms`: 持续时间（毫秒）
+#[allow(dead_code)]
+pub fn beep(frequency_hz: u32, duration_ms: u32) {
+    if frequency_hz == 0 {
+        return;
+    }
+
+    // 1. 设置通道 2 的频率
+    let divisor = pit_divisor(frequency_hz);
+    outb(PIT_COMMAND, CMD_CHANNEL2_SQUARE);
+    outb(PIT_CHANNEL2, (divisor & 0xFF) as u8);
+    outb(PIT_CHANNEL2, (divisor >> 8) as u8);
+
+    // 2. 打开喇叭（位 0 和位 1）
+    let original = inb(SPEAKER_PORT);
+    outb(SPEAKER_PORT, original | 0x03);
+
+    // 3. 等待指定时长
+    sleep_ms(duration_ms as u64);
+
+    // 4. 恢复原始值，关闭喇叭
+    outb(SPEAKER_PORT, original);
+}
+
+/// 计算 PIT 分频值
+///
+/// 分频值是 16 位的，0 表示 65536，这里限制在 1..=0xFFFF 内
+fn pit_divisor(frequency_hz: u32) -> u16 {
+    (PIT_FREQUENCY / frequency_hz).clamp(1, 0xFFFF) as u16
+}
@@ -3,7 +3,11 @@
 //! 提供 VGA 文本模式的安全输出功能
 //! 使用 volatile 确保写入不被编译器优化掉
 
+use crate::port::{inb, outb};
+use crate::text_layout::{LineFeed, TextLayout};
+use crate::{format, timer};
 use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use volatile::Volatile;
@@ -16,12 +20,26 @@ use volatile::Volatile;
 /// 标准的 VGA 文本模式缓冲区起始地址
 const VGA_BUFFER_ADDR: usize = 0xb8000;
 
+/// 显存映射的对齐要求：显存总是按整页映射，新地址必须页对齐
+const VGA_BUFFER_ALIGN: usize = 4096;
+
+/// 探测显存时使用的单元相对 0xb8000 的偏移：32 KiB 文本显存的最后一个字，
+/// 80×50 模式也用不到这里，探测不会在屏幕上留下痕迹
+const VGA_PROBE_OFFSET: usize = 0x7FFE;
+
+/// 探测时依次写入并读回的两个值（每一位都翻转一次）
+const VGA_PROBE_PATTERNS: [u16; 2] = [0xA55A, 0x5AA5];
+
 /// VGA 文本模式的屏幕宽度（字符数）
 pub const VGA_WIDTH: usize = 80;
 
-/// VGA 文本模式的屏幕高度（行数）
+/// 默认 80×25 文本模式的屏幕高度（行数）
+/// 当前实际行数由 Writer 在运行时维护，见 [`height`]
 pub const VGA_HEIGHT: usize = 25;
 
+/// 80×50 文本模式的屏幕高度（行数），也是缓冲区能容纳的最大行数
+pub const MAX_HEIGHT: usize = 50;
+
 // =============================================================================
 // VGA 颜色定义
 // =============================================================================
@@ -50,11 +68,262 @@ pub enum Color {
     White = 15,
 }
 
+impl Color {
+    /// 按颜色编号排列的全部 16 种颜色
+    const ALL: [Color; 16] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Cyan,
+        Color::Red,
+        Color::Magenta,
+        Color::Brown,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightBlue,
+        Color::LightGreen,
+        Color::LightCyan,
+        Color::LightRed,
+        Color::Pink,
+        Color::Yellow,
+        Color::White,
+    ];
+
+    /// 按 ANSI 颜色序号排列的颜色：前 8 个对应 30-37，后 8 个（高亮）对应 90-97
+    ///
+    /// ANSI 的"黄色"（33）在 VGA 上是棕色，高亮黄色（93）才是 Yellow
+    const ANSI: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Brown,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::LightGray,
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::Yellow,
+        Color::LightBlue,
+        Color::Pink,
+        Color::LightCyan,
+        Color::White,
+    ];
+
+    /// VGA 默认调色板的 RGB 值，按颜色编号排列
+    const PALETTE: [[u8; 3]; 16] = [
+        [0x00, 0x00, 0x00],
+        [0x00, 0x00, 0xAA],
+        [0x00, 0xAA, 0x00],
+        [0x00, 0xAA, 0xAA],
+        [0xAA, 0x00, 0x00],
+        [0xAA, 0x00, 0xAA],
+        [0xAA, 0x55, 0x00],
+        [0xAA, 0xAA, 0xAA],
+        [0x55, 0x55, 0x55],
+        [0x55, 0x55, 0xFF],
+        [0x55, 0xFF, 0x55],
+        [0x55, 0xFF, 0xFF],
+        [0xFF, 0x55, 0x55],
+        [0xFF, 0x55, 0xFF],
+        [0xFF, 0xFF, 0x55],
+        [0xFF, 0xFF, 0xFF],
+    ];
+
+    /// 各颜色的亮度（0~255），按颜色编号排列，编译时由 `PALETTE` 算出
+    const LUMINANCE: [u8; 16] = Color::luminance_table();
+
+    /// 按 0.299R + 0.587G + 0.114B（四舍五入）计算调色板中每种颜色的亮度
+    const fn luminance_table() -> [u8; 16] {
+        let mut table = [0u8; 16];
+        let mut i = 0;
+        while i < table.len() {
+            let [r, g, b] = Color::PALETTE[i];
+            let weighted = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+            table[i] = ((weighted + 500) / 1000) as u8;
+            i += 1;
+        }
+        table
+    }
+
+    /// 亮度不低于这个值的背景用黑字，否则用白字
+    const LIGHT_THRESHOLD: u8 = 128;
+
+    /// 按颜色编号（0~15）排列的全部 16 种颜色
+    pub const fn all() -> [Color; 16] {
+        Color::ALL
+    }
+
+    /// 由低 4 位得到颜色（高 4 位被忽略）
+    const fn from_low_nibble(value: u8) -> Color {
+        Color::ALL[(value & 0x0F) as usize]
+    }
+
+    /// 由 ANSI SGR 前景色代码得到颜色：30-37 普通色，90-97 高亮色
+    ///
+    /// 例如 31 → Red，91 → LightRed；其他代码返回 None
+    pub const fn from_ansi_fg(code: u8) -> Option<Color> {
+        match code {
+            30..=37 => Some(Color::ANSI[(code - 30) as usize]),
+            90..=97 => Some(Color::ANSI[(code - 90 + 8) as usize]),
+            _ => None,
+        }
+    }
+
+    /// 由 ANSI SGR 背景色代码得到颜色：40-47 普通色，100-107 高亮色
+    pub const fn from_ansi_bg(code: u8) -> Option<Color> {
+        match code {
+            40..=47 | 100..=107 => Color::from_ansi_fg(code - 10),
+            _ => None,
+        }
+    }
+
+    /// 在背景色 `bg` 上清晰可读的前景色：亮背景用黑色，暗背景用白色
+    ///
+    /// 例如黄色背景返回 Black，蓝色背景返回 White
+    pub const fn readable_on(bg: Color) -> Color {
+        if Color::LUMINANCE[bg as usize] >= Color::LIGHT_THRESHOLD {
+            Color::Black
+        } else {
+            Color::White
+        }
+    }
+
+    /// 对应的 ANSI SGR 前景色代码（30-37 或 90-97）
+    pub const fn to_ansi_fg(self) -> u8 {
+        let mut index = 0;
+        while Color::ANSI[index] as u8 != self as u8 {
+            index += 1;
+        }
+        if index < 8 {
+            30 + index as u8
+        } else {
+            90 + (index - 8) as u8
+        }
+    }
+}
+
+/// 从颜色编号（0~15）转换，超出范围时返回原值作为错误
+impl TryFrom<u8> for Color {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        if value < 16 {
+            Ok(Color::from_low_nibble(value))
+        } else {
+            Err(value)
+        }
+    }
+}
+
 /// 颜色代码，包含前景色和背景色
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct ColorCode(u8);
 
+#[allow(dead_code)]
+impl ColorCode {
+    // 常用的颜色组合，省得记属性字节的位布局
+    pub const WHITE_ON_BLACK: ColorCode = ColorCode::new(Color::White, Color::Black);
+    pub const LIGHT_GRAY_ON_BLACK: ColorCode = ColorCode::new(Color::LightGray, Color::Black);
+    pub const RED_ON_BLACK: ColorCode = ColorCode::new(Color::Red, Color::Black);
+    pub const LIGHT_RED_ON_BLACK: ColorCode = ColorCode::new(Color::LightRed, Color::Black);
+    pub const GREEN_ON_BLACK: ColorCode = ColorCode::new(Color::Green, Color::Black);
+    pub const LIGHT_GREEN_ON_BLACK: ColorCode = ColorCode::new(Color::LightGreen, Color::Black);
+    pub const YELLOW_ON_BLACK: ColorCode = ColorCode::new(Color::Yellow, Color::Black);
+    pub const LIGHT_CYAN_ON_BLACK: ColorCode = ColorCode::new(Color::LightCyan, Color::Black);
+    pub const WHITE_ON_RED: ColorCode = ColorCode::new(Color::White, Color::Red);
+    pub const WHITE_ON_BLUE: ColorCode = ColorCode::new(Color::White, Color::Blue);
+    pub const BLACK_ON_LIGHT_GRAY: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+}
+
+/// 内核默认的配色（白字黑底，即 `Theme::DEFAULT.normal`）
+///
+/// 全局 Writer 以这个颜色启动，ANSI 的 SGR 0 也重置到它；
+/// 运行时可以用 `set_theme` 换一整套主题，或用 `set_default_color` 只换这一种颜色
+pub const fn default_color() -> ColorCode {
+    Theme::DEFAULT.normal
+}
+
+// =============================================================================
+// 主题
+// =============================================================================
+
+/// 一套配色：按用途给颜色命名，可以在编译期定义为 `const`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// 普通输出（Writer 的默认颜色）
+    pub normal: ColorCode,
+    /// 错误信息
+    pub error: ColorCode,
+    /// 警告信息
+    pub warn: ColorCode,
+    /// 命令行提示符
+    pub prompt: ColorCode,
+}
+
+#[allow(dead_code)]
+impl Theme {
+    /// 内核默认主题：黑底白字，错误亮红、警告黄、提示符亮绿
+    pub const DEFAULT: Theme = Theme::new(
+        ColorCode::WHITE_ON_BLACK,
+        ColorCode::LIGHT_RED_ON_BLACK,
+        ColorCode::YELLOW_ON_BLACK,
+        ColorCode::LIGHT_GREEN_ON_BLACK,
+    );
+
+    /// 仿 Solarized 的主题：16 色里最接近的蓝底浅灰字
+    pub const SOLARIZED_ISH: Theme = Theme::on_background(
+        Color::Blue,
+        Color::LightGray,
+        Color::LightRed,
+        Color::Yellow,
+        Color::LightCyan,
+    );
+
+    /// 由四种颜色代码组成的主题
+    pub const fn new(
+        normal: ColorCode,
+        error: ColorCode,
+        warn: ColorCode,
+        prompt: ColorCode,
+    ) -> Theme {
+        Theme {
+            normal,
+            error,
+            warn,
+            prompt,
+        }
+    }
+
+    /// 所有用途共用背景色 `background`，只有前景色不同的主题
+    pub const fn on_background(
+        background: Color,
+        normal: Color,
+        error: Color,
+        warn: Color,
+        prompt: Color,
+    ) -> Theme {
+        Theme::new(
+            ColorCode::new(normal, background),
+            ColorCode::new(error, background),
+            ColorCode::new(warn, background),
+            ColorCode::new(prompt, background),
+        )
+    }
+
+    /// 把所有用途的背景色换成 `background`，前景色不变
+    pub const fn with_background(self, background: Color) -> Theme {
+        Theme::new(
+            self.normal.with_background(background),
+            self.error.with_background(background),
+            self.warn.with_background(background),
+            self.prompt.with_background(background),
+        )
+    }
+}
+
 impl ColorCode {
     /// 创建新的颜色代码
     ///
@@ -64,6 +333,147 @@ impl ColorCode {
     pub const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    /// 以 `bg` 为背景、自动选择可读前景色（黑或白）的颜色代码
+    pub const fn contrasting(bg: Color) -> ColorCode {
+        ColorCode::new(Color::readable_on(bg), bg)
+    }
+
+    /// 由原始属性字节创建颜色代码（高 4 位背景，低 4 位前景）
+    pub const fn from_u8(byte: u8) -> ColorCode {
+        ColorCode(byte)
+    }
+
+    /// 原始属性字节
+    pub const fn as_u8(self) -> u8 {
+        self.0
+    }
+
+    /// 前景色（低 4 位）
+    #[allow(dead_code)]
+    pub const fn foreground(self) -> Color {
+        Color::from_low_nibble(self.0)
+    }
+
+    /// 背景色（高 4 位；启用闪烁时最高位表示闪烁，这里仍按颜色解释）
+    #[allow(dead_code)]
+    pub const fn background(self) -> Color {
+        Color::from_low_nibble(self.0 >> 4)
+    }
+
+    /// 替换前景色，保留背景色（包括闪烁位）
+    pub const fn with_foreground(self, foreground: Color) -> ColorCode {
+        ColorCode((self.0 & 0xF0) | foreground as u8)
+    }
+
+    /// 替换背景色，保留前景色
+    pub const fn with_background(self, background: Color) -> ColorCode {
+        ColorCode((self.0 & 0x0F) | (background as u8) << 4)
+    }
+
+    /// 交换前景色和背景色（反色显示）
+    pub const fn inverted(self) -> ColorCode {
+        ColorCode(self.0.rotate_left(4))
+    }
+
+    /// 设置或清除前景色的高亮位（位 3），如 Red → LightRed
+    pub const fn with_bright(self, on: bool) -> ColorCode {
+        if on {
+            ColorCode(self.0 | FOREGROUND_BRIGHT)
+        } else {
+            ColorCode(self.0 & !FOREGROUND_BRIGHT)
+        }
+    }
+
+    /// 设置或清除属性字节的最高位（位 7），即字符闪烁
+    ///
+    /// 这一位和亮背景色共用：硬件处于闪烁模式时它让字符闪烁、背景只有 8 种颜色；
+    /// 调用过 [`disable_blink`] 之后（`vga::init()` 默认会调用）它表示背景色的高亮位，
+    /// 这时 `with_blink(true)` 不会让字符闪烁，只会把背景变亮（如 Blue → LightBlue）
+    #[allow(dead_code)]
+    pub const fn with_blink(self, on: bool) -> ColorCode {
+        if on {
+            ColorCode(self.0 | ATTR_BLINK)
+        } else {
+            ColorCode(self.0 & !ATTR_BLINK)
+        }
+    }
+}
+
+/// 属性字节中前景色的高亮位
+const FOREGROUND_BRIGHT: u8 = 0x08;
+
+/// 属性字节的最高位：闪烁模式下表示闪烁，否则表示背景色的高亮位
+const ATTR_BLINK: u8 = 0x80;
+
+// =============================================================================
+// 启动步骤标记
+// =============================================================================
+
+/// 启动步骤进行中的标记，`Writer::boot_step` 显示在行首
+const BOOT_STEP_PENDING: &str = "[  ..  ]";
+
+/// 启动步骤成功的标记
+///
+/// 三个标记都是 8 列宽：`finish_boot_step` 原地改写 `[  ..  ]`，
+/// 较短的 `[ OK ]`/`[FAIL]` 会在右边留下旧标记的 ` ]`，所以两边用空格补齐
+const BOOT_STEP_OK: &str = "[  OK  ]";
+
+/// 启动步骤失败的标记（8 列宽，原因见 `BOOT_STEP_OK`）
+const BOOT_STEP_FAIL: &str = "[ FAIL ]";
+
+// =============================================================================
+// CP437 字符映射
+// =============================================================================
+
+/// 截断模式下表示"这一行还有内容被丢弃"的字形（CP437 的 »）
+const TRUNCATION_MARKER: u8 = 0xAF;
+
+/// 不可显示字符的替代字形（CP437 的 ■）
+const REPLACEMENT_GLYPH: u8 = 0xfe;
+
+/// CP437 代码页 0x80~0xFF 对应的 Unicode 字符
+/// 下标 i 对应字形代码 0x80 + i
+#[rustfmt::skip]
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', // 0x80
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', // 0x90
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', // 0xA0
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', // 0xB0
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', // 0xC0
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', // 0xD0
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', // 0xE0
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}', // 0xF0
+];
+
+// 双线框字形（CP437）
+const BOX_TOP_LEFT: u8 = 0xC9; // ╔
+const BOX_TOP_RIGHT: u8 = 0xBB; // ╗
+const BOX_BOTTOM_LEFT: u8 = 0xC8; // ╚
+const BOX_BOTTOM_RIGHT: u8 = 0xBC; // ╝
+const BOX_HORIZONTAL: u8 = 0xCD; // ═
+const BOX_VERTICAL: u8 = 0xBA; // ║
+
+/// 将 Unicode 字符转换为 CP437 字形代码
+///
+/// ASCII 可打印字符原样返回；0x80~0xFF 区间按 CP437 表查找，
+/// 例如 `'─'` → 0xC4，`'┌'` → 0xDA
+///
+/// # 返回
+/// 找不到对应字形时返回 `None`
+pub fn char_to_cp437(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        _ => CP437_HIGH
+            .iter()
+            .position(|&glyph| glyph == c)
+            .map(|index| 0x80 + index as u8),
+    }
+}
+
+/// 将字符转换为可直接写入显存的字形代码，无法显示的字符用 ■ 表示
+fn glyph_for(c: char) -> u8 {
+    char_to_cp437(c).unwrap_or(REPLACEMENT_GLYPH)
 }
 
 // =============================================================================
@@ -74,225 +484,2661 @@ impl ColorCode {
 /// 包含 ASCII 字符和颜色属性
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
-struct ScreenChar {
+pub struct ScreenChar {
     /// ASCII 字符码
-    ascii_character: u8,
+    pub ascii_character: u8,
     /// 颜色属性（前景色 + 背景色）
-    color_code: ColorCode,
+    pub color_code: ColorCode,
 }
 
-/// VGA 文本缓冲区
-/// 使用 Volatile 包装确保写入不被优化
-#[repr(transparent)]
-struct Buffer {
-    /// 字符数组：25行 × 80列
-    chars: [[Volatile<ScreenChar>; VGA_WIDTH]; VGA_HEIGHT],
+/// 单元格的类型化表示：字形加前景色、背景色，不需要了解属性字节的位布局
+///
+/// 和 `ScreenChar` 可以无损互相转换（背景色的高亮位按颜色 8~15 解释）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    /// CP437 字形代码
+    pub glyph: u8,
+    /// 前景色
+    pub fg: Color,
+    /// 背景色
+    pub bg: Color,
 }
 
-// =============================================================================
-// Writer 结构体
-// =============================================================================
+impl From<Cell> for ScreenChar {
+    fn from(cell: Cell) -> ScreenChar {
+        ScreenChar {
+            ascii_character: cell.glyph,
+            color_code: ColorCode::new(cell.fg, cell.bg),
+        }
+    }
+}
 
-/// VGA 文本写入器
-/// 管理当前光标位置和颜色
-pub struct Writer {
-    /// 当前列位置
-    column_position: usize,
-    /// 当前行位置
-    row_position: usize,
-    /// 当前使用的颜色代码
-    color_code: ColorCode,
-    /// VGA 缓冲区的可变引用
-    buffer: &'static mut Buffer,
+impl From<ScreenChar> for Cell {
+    fn from(ch: ScreenChar) -> Cell {
+        Cell {
+            glyph: ch.ascii_character,
+            fg: ch.color_code.foreground(),
+            bg: ch.color_code.background(),
+        }
+    }
 }
 
-impl Writer {
-    /// 写入单个字节
-    ///
-    /// # 参数
-    /// - `byte`: 要写入的字节（ASCII 字符）
-    pub fn write_byte(&mut self, byte: u8) {
-        match byte {
-            // 换行符：移动到下一行
-            b'\n' => self.new_line(),
-            // 可打印 ASCII 字符
-            byte => {
-                // 如果当前行已满，换行
-                if self.column_position >= VGA_WIDTH {
-                    self.new_line();
-                }
+/// 一行写满后继续写入时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// 自动换到下一行继续写（默认）
+    Wrap,
+    /// 丢弃超出的部分，并在最后一列显示 `»` 标记，直到下一个换行符
+    Truncate,
+    /// 一行最多写 `LOGICAL_WIDTH` 列，超出屏幕的部分保存在宽行缓冲区中，
+    /// 用 `scroll_horizontal` 查看；写满逻辑行后换行
+    Extend,
+}
 
-                let row = self.row_position;
-                let col = self.column_position;
+/// 双缓冲模式下什么时候自动把后台缓冲区显示到屏幕
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// 只在调用 `present()` 时显示（默认）
+    Manual,
+    /// 每写入一个 `\n` 显示一次：一行之内不闪烁，每行写完立即可见
+    OnNewline,
+    /// 每次写入（`write_string`、`print!` 的每一段等）结束时都显示
+    OnEveryWrite,
+}
 
-                // 使用 volatile 写入确保不被优化
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_character: byte,
-                    color_code: self.color_code,
-                });
+/// 一屏内容的快照
+///
+/// 按 `MAX_HEIGHT` 行分配，80×50 模式下也能放下整屏；`height` 记录拍快照时屏幕的行数，
+/// 只有前 `height` 行有效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    /// 按行优先顺序排列的字符单元
+    pub cells: [ScreenChar; VGA_WIDTH * MAX_HEIGHT],
+    /// 有效的行数（25 或 50）
+    pub height: usize,
+}
 
-                self.column_position += 1;
-            }
-        }
+impl Snapshot {
+    /// 有效的各行
+    pub fn rows(&self) -> &[[ScreenChar; VGA_WIDTH]] {
+        let rows = self.cells.as_chunks::<VGA_WIDTH>().0;
+        &rows[..self.height.min(MAX_HEIGHT)]
     }
+}
 
-    /// 写入字符串
+/// 空白的快照（全部为 0、80×25），用于初始化快照缓冲区
+pub const EMPTY_SNAPSHOT: Snapshot = Snapshot {
+    cells: [EMPTY_CELL; VGA_WIDTH * MAX_HEIGHT],
+    height: VGA_HEIGHT,
+};
+
+/// VGA 文本缓冲区
+/// 使用 Volatile 包装确保写入不被优化
+///
+/// 通常就是 0xb8000 处的显存（`W` × `H` 取默认的 80 列 × 50 行）；
+/// 测试时可以用 `Buffer::from_cells` 让 Writer 写任意大小的普通数组
+#[repr(transparent)]
+pub struct Buffer<const W: usize = VGA_WIDTH, const H: usize = MAX_HEIGHT> {
+    /// 字符数组：H 行 × W 列（显存最多 50 行，80×25 模式只使用前 25 行）
+    chars: [[Volatile<ScreenChar>; W]; H],
+}
+
+/// 和 `Buffer` 布局相同的普通数组，供测试代替显存
+#[cfg(test)]
+pub type Cells<const W: usize = VGA_WIDTH, const H: usize = MAX_HEIGHT> = [[ScreenChar; W]; H];
+
+impl<const W: usize, const H: usize> Buffer<W, H> {
+    /// 把普通数组当作显存使用，让 Writer 的逻辑不依赖 0xb8000
     ///
-    /// # 参数
-    /// - `s`: 要写入的字符串
-    pub fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // 可打印 ASCII 字符或换行符
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // 不可打印字符用 ■ 表示
-                _ => self.write_byte(0xfe),
-            }
-        }
+    /// `Buffer` 和 `Volatile` 都是 `repr(transparent)`，和 `Cells` 的布局完全相同
+    #[cfg(test)]
+    pub fn from_cells(cells: &'static mut Cells<W, H>) -> &'static mut Buffer<W, H> {
+        unsafe { &mut *(cells as *mut Cells<W, H> as *mut Buffer<W, H>) }
     }
+}
 
-    /// 在指定位置写入字符串
+impl Buffer {
+    /// 把 `addr` 处的内存当作显存
     ///
-    /// # 参数
-    /// - `s`: 要写入的字符串
-    /// - `row`: 行号（0-24）
-    /// - `col`: 列号（0-79）
-    /// - `color`: 颜色代码
-    pub fn write_string_at(&mut self, s: &str, row: usize, col: usize, color: ColorCode) {
-        // 边界检查：确保不超出屏幕范围
-        if row >= VGA_HEIGHT {
-            return;
+    /// # Safety
+    /// `addr` 必须指向已映射、至少 `MAX_HEIGHT` 行大小的显存，并且没有被其他 Writer 使用
+    unsafe fn at_addr(addr: usize) -> Result<&'static mut Buffer, BufferAddrError> {
+        if addr == 0 {
+            return Err(BufferAddrError::Null);
+        }
+        if !addr.is_multiple_of(VGA_BUFFER_ALIGN) {
+            return Err(BufferAddrError::Misaligned);
         }
+        Ok(unsafe { &mut *(addr as *mut Buffer) })
+    }
+}
 
-        let mut current_col = col;
+// =============================================================================
+// 回滚缓冲区
+// =============================================================================
 
-        for byte in s.bytes() {
-            // 边界检查：确保不超出当前行
-            if current_col >= VGA_WIDTH {
-                break;
-            }
+/// 回滚缓冲区最多保存的历史行数
+pub const SCROLLBACK_LINES: usize = 1000;
 
-            let char_to_write = match byte {
-                0x20..=0x7e => byte,
-                _ => 0xfe, // 不可打印字符用 ■ 表示
-            };
+/// 一行屏幕内容
+type Line<const W: usize = VGA_WIDTH> = [ScreenChar; W];
 
-            // 使用 volatile 写入
-            self.buffer.chars[row][current_col].write(ScreenChar {
-                ascii_character: char_to_write,
-                color_code: color,
-            });
+/// 静态初始化用的空单元格（全零，让回滚缓冲区放在 .bss 中，不占内核镜像）
+const EMPTY_CELL: ScreenChar = ScreenChar {
+    ascii_character: 0,
+    color_code: ColorCode(0),
+};
 
-            current_col += 1;
-        }
-    }
+/// 回滚缓冲区
+///
+/// 保存滚出屏幕顶部的历史行（环形缓冲区，写满后覆盖最旧的一行），
+/// 以及回滚期间的实时画面：回滚时显存里显示的是历史内容，
+/// 新输出先写到 `live` 中，回到底部时再整体复制回显存
+struct Scrollback<const W: usize, const H: usize> {
+    /// 历史行
+    lines: [Line<W>; SCROLLBACK_LINES],
+    /// 下一行写入的位置
+    head: usize,
+    /// 已保存的行数
+    len: usize,
+    /// 回滚期间的实时画面（按屏幕行号索引）
+    live: [Line<W>; H],
+}
 
-    /// 换行处理
-    fn new_line(&mut self) {
-        // 如果不是最后一行，直接下移
-        if self.row_position < VGA_HEIGHT - 1 {
-            self.row_position += 1;
-        } else {
-            // 最后一行，滚动屏幕
-            self.scroll();
+impl<const W: usize, const H: usize> Scrollback<W, H> {
+    const fn new() -> Self {
+        Scrollback {
+            lines: [[EMPTY_CELL; W]; SCROLLBACK_LINES],
+            head: 0,
+            len: 0,
+            live: [[EMPTY_CELL; W]; H],
         }
-        self.column_position = 0;
     }
 
-    /// 屏幕滚动
-    /// 将所有行上移一行，最后一行清空
-    fn scroll(&mut self) {
-        // 将每一行的内容复制到上一行
-        for row in 1..VGA_HEIGHT {
-            for col in 0..VGA_WIDTH {
-                let character = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(character);
-            }
-        }
-        // 清空最后一行
-        self.clear_row(VGA_HEIGHT - 1);
+    /// 追加一行历史，写满时覆盖最旧的一行
+    fn push(&mut self, line: Line<W>) {
+        self.lines[self.head] = line;
+        self.head = (self.head + 1) % SCROLLBACK_LINES;
+        self.len = (self.len + 1).min(SCROLLBACK_LINES);
     }
 
-    /// 清空指定行
-    ///
-    /// # 参数
-    /// - `row`: 要清空的行号
-    fn clear_row(&mut self, row: usize) {
-        let blank = ScreenChar {
-            ascii_character: b' ',
-            color_code: self.color_code,
-        };
-        for col in 0..VGA_WIDTH {
-            self.buffer.chars[row][col].write(blank);
-        }
+    /// 获取第 `index` 行历史（0 为最旧的一行）
+    fn line(&self, index: usize) -> &Line<W> {
+        &self.lines[(self.head + SCROLLBACK_LINES - self.len + index) % SCROLLBACK_LINES]
     }
+}
 
-    /// 清空整个屏幕
-    pub fn clear_screen(&mut self) {
-        for row in 0..VGA_HEIGHT {
-            self.clear_row(row);
-        }
-        self.column_position = 0;
-        self.row_position = 0;
-    }
+// =============================================================================
+// 宽行与水平滚动
+// =============================================================================
 
-    /// 设置当前颜色
-    #[allow(dead_code)]
-    pub fn set_color(&mut self, color: ColorCode) {
-        self.color_code = color;
-    }
+/// 逻辑行的最大宽度（列），`WrapMode::Extend` 下一行写满这么多列才换行
+pub const LOGICAL_WIDTH: usize = 200;
+
+/// 一行中超出屏幕宽度的部分
+///
+/// 数组长度不能由泛型参数 `W` 计算，所以按 `LOGICAL_WIDTH` 分配；
+/// 宽度为 `W` 的屏幕只用前 `WideLines::<W, H>::OVERFLOW_WIDTH` 个
+type Overflow = [ScreenChar; LOGICAL_WIDTH];
+
+/// 宽行缓冲区
+///
+/// 逻辑行的前 80 列就是屏幕上的内容，超出的部分按屏幕行号保存在 `overflow` 中；
+/// 水平滚动期间显存里显示的是窗口内容，原来的前 80 列保存在 `window` 中，
+/// 回到最左边时再复制回显存。滚出屏幕顶部的行只有前 80 列进入回滚缓冲区
+struct WideLines<const W: usize, const H: usize> {
+    /// 每一行超出屏幕宽度的部分
+    overflow: [Overflow; H],
+    /// 水平滚动期间保存的前 80 列（按屏幕行号索引）
+    window: [Line<W>; H],
 }
 
-/// 实现 fmt::Write trait，支持格式化输出
-impl fmt::Write for Writer {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.write_string(s);
-        Ok(())
+impl<const W: usize, const H: usize> WideLines<W, H> {
+    /// 一行最多能超出屏幕宽度的列数（80 列的屏幕上是逻辑列 80 ~ 199）
+    const OVERFLOW_WIDTH: usize = LOGICAL_WIDTH.saturating_sub(W);
+
+    const fn new() -> Self {
+        WideLines {
+            overflow: [[EMPTY_CELL; LOGICAL_WIDTH]; H],
+            window: [[EMPTY_CELL; W]; H],
+        }
+    }
+
+    /// 第 `row` 行第 `col` 个逻辑列的内容（需要在保存过 `window` 之后调用）
+    fn cell(&self, row: usize, col: usize) -> ScreenChar {
+        if col < W {
+            self.window[row][col]
+        } else {
+            self.overflow[row][col - W]
+        }
     }
 }
 
 // =============================================================================
-// 全局 Writer 实例
+// 双缓冲与软件光标
 // =============================================================================
 
-lazy_static! {
-    /// 全局 VGA Writer 实例
-    /// 使用 Mutex 保护，确保线程安全
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        row_position: 0,
-        color_code: ColorCode::new(Color::White, Color::Black),
-        buffer: unsafe { &mut *(VGA_BUFFER_ADDR as *mut Buffer) },
-    });
+/// Writer 的大块存储：回滚缓冲区（约 160 KiB）、宽行缓冲区和双缓冲的后台缓冲区
+///
+/// 太大，不能放在 Writer 里（创建时会占用栈），也放不进堆；
+/// 通常放在 `static` 中，每个 Writer 独占一份
+pub struct WriterStorage<const W: usize = VGA_WIDTH, const H: usize = MAX_HEIGHT> {
+    /// 回滚缓冲区
+    scrollback: Scrollback<W, H>,
+    /// 双缓冲的后台缓冲区：启用双缓冲时所有输出先写到这里，`present()` 时一次性复制到显存
+    back_buffer: [Line<W>; H],
+    /// 宽行缓冲区
+    wide: WideLines<W, H>,
 }
 
-// =============================================================================
-// 公共函数接口
-// =============================================================================
+impl<const W: usize, const H: usize> WriterStorage<W, H> {
+    /// 空的存储（全零，放在 .bss 中，不占内核镜像）
+    pub const fn new() -> Self {
+        WriterStorage {
+            scrollback: Scrollback::new(),
+            back_buffer: [[EMPTY_CELL; W]; H],
+            wide: WideLines::new(),
+        }
+    }
+}
 
-/// 清空屏幕
-pub fn clear_screen() {
-    WRITER.lock().clear_screen();
+impl<const W: usize, const H: usize> Default for WriterStorage<W, H> {
+    fn default() -> Self {
+        WriterStorage::new()
+    }
 }
 
-/// 在指定位置写入字符串
+/// 全局 WRITER 的存储空间
 ///
-/// # 参数
+/// 只在创建 WRITER 时取一次可变引用，之后只通过 WRITER 访问
+static mut WRITER_STORAGE: WriterStorage = WriterStorage::new();
+
+/// 软件光标闪烁的半周期（毫秒）
+const CURSOR_BLINK_MS: u64 = 500;
+
+/// 软件光标当前是否处于"亮"的半周期，由时钟中断翻转
+static CURSOR_BLINK_ON: AtomicBool = AtomicBool::new(true);
+
+/// `init` 是否已经调用过
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// 启动时探测到的显存是否可用，`init` 之前默认可用
+static VGA_PRESENT: AtomicBool = AtomicBool::new(true);
+
+/// 是否启用了双缓冲（Writer::double_buffered 的副本）
+///
+/// 时钟中断只在它为 true 时才去访问 WRITER，避免在 WRITER 初始化之前触碰它
+static DOUBLE_BUFFER_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// =============================================================================
+// Writer 结构体
+// =============================================================================
+
+/// VGA 文本写入器
+/// 管理当前光标位置和颜色
+///
+/// `W` × `H` 是缓冲区的列数和最大行数，默认就是 VGA 显存的 80 × 50；
+/// 换行、滚动、回滚等逻辑只依赖这两个参数，测试中可以在 8 × 4 的数组上运行
+pub struct Writer<const W: usize = VGA_WIDTH, const H: usize = MAX_HEIGHT> {
+    /// 当前列位置
+    column_position: usize,
+    /// 当前行位置
+    row_position: usize,
+    /// 当前文本模式的行数（25 或 50）
+    height: usize,
+    /// 当前使用的颜色代码（set_color 设置的原始颜色）
+    color_code: ColorCode,
+    /// `write_styled` 正在写入的片段颜色，只用于字形，不影响清屏和滚动出来的空行
+    run_color: Option<ColorCode>,
+    /// 默认颜色：ANSI 重置时恢复到这个颜色
+    default_color: ColorCode,
+    /// 当前主题（`normal` 和 `default_color` 在 `set_theme` 时一致）
+    theme: Theme,
+    /// 反色显示：写入时交换前景色和背景色
+    inverse: bool,
+    /// 高亮显示：写入时置位前景色的高亮位
+    bright: bool,
+    /// 滚动区域的起始行（包含）
+    scroll_top: usize,
+    /// 滚动区域的结束行（不包含）
+    scroll_bottom: usize,
+    /// CP437 直通模式：为 true 时 write_byte 把 0x80~0xFF 原样写入显存
+    cp437_passthrough: bool,
+    /// save_cursor 保存的光标位置（行，列）
+    saved_cursor: Option<(usize, usize)>,
+    /// 最近一个还没有结束的启动步骤所在的行（滚动时跟着上移）
+    boot_step_row: Option<usize>,
+    /// 行满后的处理方式
+    wrap_mode: WrapMode,
+    /// 硬件光标是否启用（启用时光标移动会同步到 CRTC）
+    hw_cursor_enabled: bool,
+    /// 向上回滚的行数，0 表示显示实时画面
+    view_offset: usize,
+    /// 回滚缓冲区
+    scrollback: &'static mut Scrollback<W, H>,
+    /// 水平滚动的列数，0 表示显示每行的前 80 列
+    h_offset: usize,
+    /// 宽行缓冲区
+    wide: &'static mut WideLines<W, H>,
+    /// 是否启用双缓冲（输出先写后台缓冲区，`present()` 时才显示）
+    double_buffered: bool,
+    /// 双缓冲模式下自动显示的时机
+    flush_policy: FlushPolicy,
+    /// 双缓冲模式下是否绘制闪烁的软件光标
+    soft_cursor_visible: bool,
+    /// 双缓冲的后台缓冲区
+    back_buffer: &'static mut [Line<W>; H],
+    /// 已经写到屏幕上的字形总数（回绕计数），用于 `write_fmt_counted`
+    glyphs_written: usize,
+    /// VGA 缓冲区的可变引用
+    buffer: &'static mut Buffer<W, H>,
+}
+
+impl<const W: usize, const H: usize> Writer<W, H> {
+    /// 创建写入 `buffer` 的 Writer，光标在左上角，最后一行保留给状态栏
+    ///
+    /// 全局 `WRITER` 之外需要单独的 Writer 时使用（例如测试中写普通数组）
+    ///
+    /// # 参数
+    /// - `buffer`: 显存（或 `Buffer::from_cells` 包装的数组）
+    /// - `storage`: 回滚缓冲区和双缓冲的存储空间，不能和其他 Writer 共用
+    /// - `color`: 默认颜色
+    pub fn new(
+        buffer: &'static mut Buffer<W, H>,
+        storage: &'static mut WriterStorage<W, H>,
+        color: ColorCode,
+    ) -> Self {
+        let height = VGA_HEIGHT.min(H);
+        Writer {
+            column_position: 0,
+            row_position: 0,
+            height,
+            color_code: color,
+            run_color: None,
+            default_color: color,
+            theme: Theme::DEFAULT,
+            inverse: false,
+            bright: false,
+            scroll_top: 0,
+            scroll_bottom: height - 1,
+            cp437_passthrough: false,
+            saved_cursor: None,
+            boot_step_row: None,
+            wrap_mode: WrapMode::Wrap,
+            hw_cursor_enabled: false,
+            view_offset: 0,
+            scrollback: &mut storage.scrollback,
+            h_offset: 0,
+            wide: &mut storage.wide,
+            double_buffered: false,
+            flush_policy: FlushPolicy::Manual,
+            soft_cursor_visible: true,
+            back_buffer: &mut storage.back_buffer,
+            glyphs_written: 0,
+            buffer,
+        }
+    }
+
+    /// 写入单个字节
+    ///
+    /// 支持的控制字符：`\n` 换行、`\r` 回到行首、`\t` 跳到下一个制表位、`\b` 退格
+    /// 其余 0x20 以下的控制字符显示为 ■；0x80~0xFF 只有在 CP437 直通模式下才原样显示
+    ///
+    /// # 参数
+    /// - `byte`: 要写入的字节（ASCII 字符或 CP437 字形代码）
+    #[allow(dead_code)]
+    pub fn write_byte(&mut self, byte: u8) {
+        self.process_byte(byte);
+        self.finish_write();
+    }
+
+    /// 原样写入单个字形代码，不做任何过滤或转换
+    ///
+    /// 和 `write_byte` 不同，控制字符（包括 `\n`）和 0x80~0xFF 都直接作为 CP437 字形显示，
+    /// 写入后光标右移一格，到行尾时自动换行
+    #[allow(dead_code)]
+    pub fn write_raw_byte(&mut self, byte: u8) {
+        self.put_glyph(byte);
+        self.finish_write();
+    }
+
+    /// 原样写入一串字形代码，规则同 `write_raw_byte`
+    #[allow(dead_code)]
+    pub fn write_raw(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.put_glyph(byte);
+        }
+        self.finish_write();
+    }
+
+    /// 以小写十六进制写入数字（不带 `0x` 前缀），不足 `width` 位时补 0
+    ///
+    /// 手工转换数字，不经过 `core::fmt`，适合早期启动和异常处理
+    #[allow(dead_code)]
+    pub fn write_hex(&mut self, value: u64, width: usize) {
+        let mut buf = [0u8; format::HEX_DIGITS_MAX];
+        for &digit in format::hex_digits(value, width, &mut buf) {
+            self.process_byte(digit);
+        }
+        self.finish_write();
+    }
+
+    /// 以十进制写入数字，同样不经过 `core::fmt`
+    #[allow(dead_code)]
+    pub fn write_dec(&mut self, value: u64) {
+        let mut buf = [0u8; format::DEC_DIGITS_MAX];
+        for &digit in format::dec_digits(value, &mut buf) {
+            self.process_byte(digit);
+        }
+        self.finish_write();
+    }
+
+    /// 写入格式化文本，返回实际写到屏幕上的字符数
+    ///
+    /// 只统计让光标前进的字形：制表符按展开的空格数计算，换行、回车、退格不计入，
+    /// 截断模式下被丢弃的字符也不计入。用于对齐列或绘制进度条，不需要预先测量字符串
+    pub fn write_fmt_counted(&mut self, args: fmt::Arguments) -> usize {
+        let before = self.glyphs_written;
+        let _ = fmt::Write::write_fmt(self, args);
+        self.glyphs_written.wrapping_sub(before)
+    }
+
+    /// 一次写入结束：同步硬件光标，`FlushPolicy::OnEveryWrite` 时显示后台缓冲区
+    fn finish_write(&mut self) {
+        self.sync_hw_cursor();
+        if self.flush_policy == FlushPolicy::OnEveryWrite {
+            self.present();
+        }
+    }
+
+    /// 写入的换行符：换行，`FlushPolicy::OnNewline` 时显示后台缓冲区
+    ///
+    /// 一行写满后的自动换行不算，不会触发显示
+    fn line_break(&mut self) {
+        self.new_line();
+        if self.flush_policy == FlushPolicy::OnNewline {
+            self.present();
+        }
+    }
+
+    /// 处理单个字节（不更新硬件光标）
+    fn process_byte(&mut self, byte: u8) {
+        match byte {
+            // 换行符：移动到下一行
+            b'\n' => self.line_break(),
+            // 回车符：回到行首
+            b'\r' => self.column_position = 0,
+            // 制表符：用空格填充到下一个制表位
+            b'\t' => {
+                let next_stop = self.layout().tab_stop(self.column_position);
+                while self.column_position < next_stop {
+                    self.put_glyph(b' ');
+                }
+            }
+            // 退格符：光标左移一格（不擦除字符）
+            0x08 => self.column_position = self.column_position.saturating_sub(1),
+            // 可打印 ASCII 字符
+            0x20..=0x7e => self.put_glyph(byte),
+            // CP437 扩展字形（直通模式）
+            0x80..=0xff if self.cp437_passthrough => self.put_glyph(byte),
+            // 其他控制字符和未启用直通时的扩展字节
+            _ => self.put_glyph(REPLACEMENT_GLYPH),
+        }
+    }
+
+    /// 在当前光标处写入一个字形，不做任何转换
+    fn put_glyph(&mut self, glyph: u8) {
+        // 如果当前行已满：换行，或者（截断模式）丢弃并在行尾标记
+        if self.layout().is_line_full(self.column_position) {
+            match self.wrap_mode {
+                WrapMode::Wrap | WrapMode::Extend => self.new_line(),
+                WrapMode::Truncate => {
+                    self.mark_truncated();
+                    return;
+                }
+            }
+        }
+
+        let row = self.row_position;
+        let col = self.column_position;
+        let ch = ScreenChar {
+            ascii_character: glyph,
+            color_code: self.effective_color(),
+        };
+
+        if col < W {
+            self.put_char(row, col, ch);
+        } else {
+            // 超出屏幕宽度的逻辑列写入宽行缓冲区
+            self.reset_horizontal();
+            self.wide.overflow[row][col - W] = ch;
+        }
+
+        self.column_position += 1;
+        self.glyphs_written = self.glyphs_written.wrapping_add(1);
+    }
+
+    /// 一行最多能写的列数（逻辑行宽度）
+    fn line_width(&self) -> usize {
+        match self.wrap_mode {
+            WrapMode::Extend => W + WideLines::<W, H>::OVERFLOW_WIDTH,
+            WrapMode::Wrap | WrapMode::Truncate => W,
+        }
+    }
+
+    /// 当前的排版参数：逻辑行宽度和滚动区域
+    fn layout(&self) -> TextLayout {
+        TextLayout::new(self.line_width(), self.scroll_top, self.scroll_bottom)
+    }
+
+    /// 截断模式下把当前行最后一列改成 `»`，表示有内容被丢弃
+    fn mark_truncated(&mut self) {
+        let row = self.row_position;
+        let last = W - 1;
+        if self.char_at(row, last).ascii_character != TRUNCATION_MARKER {
+            self.put_char(
+                row,
+                last,
+                ScreenChar {
+                    ascii_character: TRUNCATION_MARKER,
+                    color_code: self.effective_color(),
+                },
+            );
+        }
+    }
+
+    /// 设置行满后的处理方式（换行、截断或保存为宽行）
+    pub fn set_wrap_mode(&mut self, mode: WrapMode) {
+        self.wrap_mode = mode;
+    }
+
+    /// 写入字符串
+    ///
+    /// ASCII 字符按 `write_byte` 处理，其他 Unicode 字符通过 CP437 表转换为字形
+    /// （如制表符 `─│┌┐└┘`），无法转换的字符显示为 ■
+    ///
+    /// # 参数
+    /// - `s`: 要写入的字符串
+    pub fn write_string(&mut self, s: &str) {
+        for c in s.chars() {
+            self.process_char(c);
+        }
+        self.finish_write();
+    }
+
+    /// 用指定颜色写入一段文字，写完后恢复原来的当前颜色
+    ///
+    /// 换行、制表位、行满换行和滚动的规则和 `write_string` 完全相同，
+    /// 一段文字在中途换行或滚动时后半段仍然使用 `color`，
+    /// 滚动出来的空行仍然使用当前颜色；
+    /// 用于一行中不同片段颜色不同的场合（例如语法高亮）
+    ///
+    /// # 参数
+    /// - `s`: 要写入的字符串
+    /// - `color`: 这段文字的颜色（反色/高亮仍然叠加在它上面）
+    pub fn write_styled(&mut self, s: &str, color: ColorCode) {
+        let saved = self.run_color.replace(color);
+        for c in s.chars() {
+            self.process_char(c);
+        }
+        self.run_color = saved;
+        self.finish_write();
+    }
+
+    /// 写入字符串，按单词换行
+    ///
+    /// 下一个单词（以空格分隔）在当前行放不下时先换行，不把单词拆成两半；
+    /// 比一整行还长的单词仍然在行尾硬换行。行满后紧跟的空格被丢弃，新行不会以空格开头
+    ///
+    /// # 参数
+    /// - `s`: 要写入的字符串，可以包含 `\n`
+    pub fn write_string_wrapped(&mut self, s: &str) {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.line_break();
+            }
+            for (j, word) in line.split(' ').enumerate() {
+                if j > 0 && self.column_position < W {
+                    self.put_glyph(b' ');
+                }
+                let len = word.chars().count();
+                if self.column_position > 0 && len <= W && self.column_position + len > W {
+                    self.new_line();
+                }
+                for c in word.chars() {
+                    self.process_char(c);
+                }
+            }
+        }
+        self.finish_write();
+    }
+
+    /// 写入单个字符（不更新硬件光标）
+    ///
+    /// ASCII 字符按 `write_byte` 的规则处理，其他字符通过 CP437 表转换为字形
+    fn process_char(&mut self, c: char) {
+        if c.is_ascii() {
+            self.process_byte(c as u8);
+        } else {
+            self.put_glyph(glyph_for(c));
+        }
+    }
+
+    /// 获取当前光标位置（行，列）
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (self.row_position, self.column_position)
+    }
+
+    /// 移动光标到指定位置
+    ///
+    /// 行号被限制在滚动区域内（状态栏所在的行不会被选中），列号超出时限制到最后一列
+    ///
+    /// # 参数
+    /// - `row`: 行号
+    /// - `col`: 列号
+    pub fn set_cursor(&mut self, row: usize, col: usize) {
+        self.row_position = row.clamp(self.scroll_top, self.scroll_bottom - 1);
+        self.column_position = col.min(W - 1);
+        self.sync_hw_cursor();
+    }
+
+    /// 保存当前光标位置（只保存一个，后保存的覆盖先保存的）
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.cursor_position());
+    }
+
+    /// 恢复 save_cursor 保存的光标位置，没有保存过时不做任何事
+    pub fn restore_cursor(&mut self) {
+        if let Some((row, col)) = self.saved_cursor {
+            self.set_cursor(row, col);
+        }
+    }
+
+    /// 设置是否让硬件光标跟随写入位置
+    pub fn set_hw_cursor_enabled(&mut self, enabled: bool) {
+        self.hw_cursor_enabled = enabled;
+        self.apply_hw_cursor();
+    }
+
+    /// 硬件光标当前是否应该显示
+    ///
+    /// 回滚期间光标所在的实时画面不可见；双缓冲时显存只在 present 时更新，
+    /// 硬件光标会和画面不同步，改用软件光标
+    fn hw_cursor_visible(&self) -> bool {
+        self.hw_cursor_enabled && self.view_offset == 0 && !self.double_buffered
+    }
+
+    /// 按当前状态打开或关闭硬件光标
+    fn apply_hw_cursor(&self) {
+        if self.hw_cursor_visible() {
+            enable_cursor();
+            self.sync_hw_cursor();
+        } else {
+            disable_cursor();
+        }
+    }
+
+    /// 把软件光标位置同步到硬件光标（硬件光标不可见时不做任何事）
+    fn sync_hw_cursor(&self) {
+        if self.hw_cursor_visible() {
+            update_cursor(self.row_position, self.cursor_window_column());
+        }
+    }
+
+    /// 光标在可见窗口中的列
+    ///
+    /// 行满待换行时显示在最后一列；逻辑列在窗口之外时贴在窗口左右边缘
+    fn cursor_window_column(&self) -> usize {
+        self.column_position
+            .min(self.line_width() - 1)
+            .saturating_sub(self.h_offset)
+            .min(W - 1)
+    }
+
+    /// 设置 CP437 直通模式
+    ///
+    /// 开启后 `write_byte` 会把 0x80~0xFF 原样写入显存（显示为 CP437 字形），
+    /// 关闭时这些字节显示为 ■
+    #[allow(dead_code)]
+    pub fn set_cp437_passthrough(&mut self, on: bool) {
+        self.cp437_passthrough = on;
+    }
+
+    /// 在指定位置写入字符串
+    ///
+    /// # 参数
+    /// - `s`: 要写入的字符串
+    /// - `row`: 行号（0-24）
+    /// - `col`: 列号（0-79）
+    /// - `color`: 颜色代码
+    ///
+    /// # 返回
+    /// 实际写入的字符数；小于字符串的字符数时说明在行尾被截断
+    pub fn write_string_at(&mut self, s: &str, row: usize, col: usize, color: ColorCode) -> usize {
+        // 边界检查：确保不超出屏幕范围
+        if row >= self.height {
+            return 0;
+        }
+
+        // 边界检查：确保不超出当前行（zip 会在行尾自动停止）
+        let mut written = 0;
+        for (current_col, c) in (col..W).zip(s.chars()) {
+            // 不可打印字符用 ■ 表示
+            self.put_char(
+                row,
+                current_col,
+                ScreenChar {
+                    ascii_character: glyph_for(c),
+                    color_code: color,
+                },
+            );
+            written += 1;
+        }
+        written
+    }
+
+    /// 用空格清除一行中的一段区域（超出屏幕的部分被裁剪）
+    ///
+    /// 用于擦除长度未知的旧文本，不影响光标位置
+    ///
+    /// # 参数
+    /// - `row`/`col`: 起始行号和列号
+    /// - `width`: 清除的列数
+    /// - `color`: 清除后的颜色（背景色决定这段区域的底色）
+    pub fn clear_region(&mut self, row: usize, col: usize, width: usize, color: ColorCode) {
+        self.fill_rect(row, col, 1, width, b' ', color);
+    }
+
+    /// 在指定单元格写入字形，超出屏幕范围时忽略
+    fn write_cell(&mut self, row: usize, col: usize, glyph: u8, color: ColorCode) {
+        if row < self.height && col < W {
+            self.put_char(
+                row,
+                col,
+                ScreenChar {
+                    ascii_character: glyph,
+                    color_code: color,
+                },
+            );
+        }
+    }
+
+    /// 写入一个单元格
+    ///
+    /// 回滚期间滚动区域内的写入进入实时画面副本，其余情况直接写显存；
+    /// 水平滚动期间写滚动区域时先回到最左边。
+    /// 超出屏幕范围时忽略（所有写入都经过这里，保证不会越界访问显存）
+    fn put_char(&mut self, row: usize, col: usize, ch: ScreenChar) {
+        if row >= self.height || col >= W {
+            return;
+        }
+        if self.h_offset > 0 && (self.scroll_top..self.scroll_bottom).contains(&row) {
+            self.reset_horizontal();
+        }
+        if self.is_scrolled_back_row(row) {
+            self.scrollback.live[row][col] = ch;
+        } else {
+            self.screen_write(row, col, ch);
+        }
+    }
+
+    /// 读取一个单元格（回滚或水平滚动期间从保存的副本读取），超出屏幕范围时返回空白
+    fn char_at(&self, row: usize, col: usize) -> ScreenChar {
+        if row >= self.height || col >= W {
+            return ScreenChar {
+                ascii_character: b' ',
+                color_code: self.color_code,
+            };
+        }
+        if self.h_offset > 0 && (self.scroll_top..self.scroll_bottom).contains(&row) {
+            self.wide.window[row][col]
+        } else if self.is_scrolled_back_row(row) {
+            self.scrollback.live[row][col]
+        } else {
+            self.screen_read(row, col)
+        }
+    }
+
+    /// 写入一个单元格，不移动光标；超出屏幕范围时忽略
+    pub fn put_cell(&mut self, row: usize, col: usize, cell: Cell) {
+        self.put_char(row, col, cell.into());
+    }
+
+    /// 读取一个单元格，超出屏幕范围时返回 None
+    pub fn get_cell(&self, row: usize, col: usize) -> Option<Cell> {
+        (row < self.height && col < W).then(|| self.char_at(row, col).into())
+    }
+
+    /// 写入屏幕上的一个单元格：双缓冲时写后台缓冲区，否则直接写显存
+    fn screen_write(&mut self, row: usize, col: usize, ch: ScreenChar) {
+        if self.double_buffered {
+            self.back_buffer[row][col] = ch;
+        } else {
+            // 使用 volatile 写入确保不被优化
+            self.buffer.chars[row][col].write(ch);
+        }
+    }
+
+    /// 读取屏幕上的一个单元格：双缓冲时读后台缓冲区，否则直接读显存
+    fn screen_read(&self, row: usize, col: usize) -> ScreenChar {
+        if self.double_buffered {
+            self.back_buffer[row][col]
+        } else {
+            self.buffer.chars[row][col].read()
+        }
+    }
+
+    /// 该行当前是否被历史内容遮住（回滚中且位于滚动区域内）
+    fn is_scrolled_back_row(&self, row: usize) -> bool {
+        self.view_offset > 0 && (self.scroll_top..self.scroll_bottom).contains(&row)
+    }
+
+    /// 用指定字形填充矩形区域
+    ///
+    /// 超出屏幕的部分会被裁剪，不影响光标位置
+    ///
+    /// # 参数
+    /// - `top`/`left`: 左上角的行号和列号
+    /// - `height`/`width`: 矩形的高度和宽度
+    /// - `ch`: 填充用的字形代码（如 `b' '` 用于清除区域）
+    /// - `color`: 颜色代码
+    pub fn fill_rect(
+        &mut self,
+        top: usize,
+        left: usize,
+        height: usize,
+        width: usize,
+        ch: u8,
+        color: ColorCode,
+    ) {
+        let bottom = top.saturating_add(height).min(self.height);
+        let right = left.saturating_add(width).min(W);
+        for row in top..bottom {
+            for col in left..right {
+                self.write_cell(row, col, ch, color);
+            }
+        }
+    }
+
+    /// 用双线字形绘制矩形边框
+    ///
+    /// 矩形会先被裁剪到屏幕范围内，再绘制裁剪后的边框；
+    /// 裁剪后高或宽小于 2 时无法构成边框，直接返回
+    ///
+    /// # 参数
+    /// - `top`/`left`: 左上角的行号和列号
+    /// - `height`/`width`: 边框的外部高度和宽度（包含边框本身）
+    /// - `color`: 颜色代码
+    pub fn draw_box(
+        &mut self,
+        top: usize,
+        left: usize,
+        height: usize,
+        width: usize,
+        color: ColorCode,
+    ) {
+        let bottom = top.saturating_add(height).min(self.height);
+        let right = left.saturating_add(width).min(W);
+        if bottom.saturating_sub(top) < 2 || right.saturating_sub(left) < 2 {
+            return;
+        }
+        let (last_row, last_col) = (bottom - 1, right - 1);
+
+        // 上下两条横线
+        for col in left + 1..last_col {
+            self.write_cell(top, col, BOX_HORIZONTAL, color);
+            self.write_cell(last_row, col, BOX_HORIZONTAL, color);
+        }
+        // 左右两条竖线
+        for row in top + 1..last_row {
+            self.write_cell(row, left, BOX_VERTICAL, color);
+            self.write_cell(row, last_col, BOX_VERTICAL, color);
+        }
+        // 四个角
+        self.write_cell(top, left, BOX_TOP_LEFT, color);
+        self.write_cell(top, last_col, BOX_TOP_RIGHT, color);
+        self.write_cell(last_row, left, BOX_BOTTOM_LEFT, color);
+        self.write_cell(last_row, last_col, BOX_BOTTOM_RIGHT, color);
+    }
+
+    /// 在屏幕中央绘制带双线边框的消息框，每行文字在框内水平居中
+    ///
+    /// 框的宽度由最长的一行决定（左右各留一格空白），高度由行数决定；
+    /// 内容超出屏幕时，多出的行被丢弃，过长的行被截断
+    ///
+    /// # 参数
+    /// - `lines`: 各行文字
+    /// - `color`: 边框和文字的颜色
+    pub fn print_box_centered(&mut self, lines: &[&str], color: ColorCode) {
+        // 边框和两侧的空白各占 2 列，上下边框占 2 行
+        let inner_width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(W.saturating_sub(4));
+        let inner_height = lines.len().min(self.height - 2);
+        let (width, height) = (inner_width + 4, inner_height + 2);
+        let top = (self.height - height) / 2;
+        let left = W.saturating_sub(width) / 2;
+
+        self.fill_rect(top, left, height, width, b' ', color);
+        self.draw_box(top, left, height, width, color);
+        for (i, line) in lines.iter().take(inner_height).enumerate() {
+            // 按字符截断，不能切在 UTF-8 字符中间
+            let end = line
+                .char_indices()
+                .nth(inner_width)
+                .map_or(line.len(), |(index, _)| index);
+            let line = &line[..end];
+            let col = left + 2 + (inner_width - line.chars().count()) / 2;
+            self.write_string_at(line, top + 1 + i, col, color);
+        }
+    }
+
+    /// 换行处理
+    fn new_line(&mut self) {
+        match self.layout().line_feed(self.row_position) {
+            // 不是滚动区域的最后一行，直接下移
+            LineFeed::Next(row) => self.row_position = row,
+            // 滚动区域的最后一行，滚动屏幕
+            LineFeed::Scroll => self.scroll(),
+        }
+        self.column_position = 0;
+    }
+
+    /// 屏幕滚动
+    /// 将滚动区域内的所有行上移一行，区域最后一行清空
+    /// 区域之外的行（如状态栏）保持不变
+    fn scroll(&mut self) {
+        // 滚出区域顶部的行保存到回滚缓冲区
+        let top = self.scroll_top;
+        let line: Line<W> = core::array::from_fn(|col| self.char_at(top, col));
+        self.scrollback.push(line);
+
+        // 将区域内每一行的内容整行复制到上一行
+        self.reset_horizontal();
+        for row in self.scroll_top + 1..self.scroll_bottom {
+            self.copy_row(row, row - 1);
+        }
+        // 宽行超出屏幕的部分跟着上移
+        self.wide
+            .overflow
+            .copy_within(self.scroll_top + 1..self.scroll_bottom, self.scroll_top);
+        // 清空区域最后一行
+        self.clear_row(self.scroll_bottom - 1);
+
+        // 未结束的启动步骤跟着内容上移，滚出区域后就无法再更新
+        self.boot_step_row = match self.boot_step_row {
+            Some(row) if row > self.scroll_top => Some(row - 1),
+            _ => None,
+        };
+
+        // 回滚期间保持视图停在原来的内容上，不跳回底部；
+        // 已经停在最旧的一行时，最旧的历史被覆盖，需要重绘
+        if self.view_offset > 0 {
+            if self.view_offset < self.scrollback.len {
+                self.view_offset += 1;
+            } else {
+                self.repaint_view();
+            }
+        }
+    }
+
+    /// 把滚动区域内的第 `src` 行整行复制到第 `dst` 行
+    ///
+    /// 和 `put_char` 一样按回滚、双缓冲的状态选择写到哪里，但一次复制 80 个单元格：
+    /// 显存按普通内存块复制（160 字节），编译器可以用更宽的指令，比逐个单元格 volatile 读写快得多。
+    /// 调用前必须已经回到水平滚动的最左边
+    fn copy_row(&mut self, src: usize, dst: usize) {
+        if self.is_scrolled_back_row(dst) {
+            self.scrollback.live[dst] = self.scrollback.live[src];
+        } else if self.double_buffered {
+            self.back_buffer[dst] = self.back_buffer[src];
+        } else {
+            let chars = &mut self.buffer.chars;
+            // SAFETY: 两行都在显存范围内；VGA 显存可以用任意宽度访问，
+            // `Volatile` 是 `repr(transparent)`，按字节复制和逐个单元格写入的结果相同
+            unsafe {
+                core::ptr::copy(chars[src].as_ptr(), chars[dst].as_mut_ptr(), W);
+            }
+        }
+    }
+
+    /// 向上回滚若干行，显示更早的历史输出
+    ///
+    /// 回滚期间的新输出照常追加到历史中，但视图不会跳回底部，
+    /// 直到调用 `scroll_forward` 回到实时画面
+    ///
+    /// # 参数
+    /// - `lines`: 回滚的行数（超出历史长度时停在最旧的一行）
+    pub fn scroll_back(&mut self, lines: usize) {
+        let target = self
+            .view_offset
+            .saturating_add(lines)
+            .min(self.scrollback.len);
+        if target == self.view_offset {
+            return;
+        }
+
+        // 第一次离开底部：先回到最左边并保存实时画面，之后的输出都写到副本中
+        if self.view_offset == 0 {
+            self.reset_horizontal();
+            for row in self.scroll_top..self.scroll_bottom {
+                for col in 0..W {
+                    self.scrollback.live[row][col] = self.screen_read(row, col);
+                }
+            }
+        }
+
+        self.view_offset = target;
+        self.apply_hw_cursor();
+        self.repaint_view();
+    }
+
+    /// 向下滚动若干行，回到底部时恢复实时画面
+    ///
+    /// # 参数
+    /// - `lines`: 滚动的行数（超出时停在底部）
+    pub fn scroll_forward(&mut self, lines: usize) {
+        if self.view_offset == 0 {
+            return;
+        }
+
+        self.view_offset = self.view_offset.saturating_sub(lines);
+        if self.view_offset > 0 {
+            self.repaint_view();
+            return;
+        }
+
+        // 回到底部：把实时画面复制回屏幕，恢复光标
+        for row in self.scroll_top..self.scroll_bottom {
+            for col in 0..W {
+                self.screen_write(row, col, self.scrollback.live[row][col]);
+            }
+        }
+        self.apply_hw_cursor();
+        self.present();
+    }
+
+    /// 水平滚动若干列，查看 `WrapMode::Extend` 写入的超出屏幕宽度的内容
+    ///
+    /// 滚动区域内每一行显示从逻辑列 `h_offset` 开始的 80 列；区域之外的行（如状态栏）不动。
+    /// 新的输出会让视图先回到最左边；回滚期间不能水平滚动
+    ///
+    /// # 参数
+    /// - `cols`: 滚动的列数，正数向右、负数向左（停在 0 和 `LOGICAL_WIDTH - 80` 之间）
+    pub fn scroll_horizontal(&mut self, cols: isize) {
+        if self.view_offset > 0 {
+            return;
+        }
+        let target = self
+            .h_offset
+            .saturating_add_signed(cols)
+            .min(WideLines::<W, H>::OVERFLOW_WIDTH);
+        if target == self.h_offset {
+            return;
+        }
+
+        // 第一次离开最左边：先保存屏幕上的前 80 列
+        if self.h_offset == 0 {
+            for row in self.scroll_top..self.scroll_bottom {
+                for col in 0..W {
+                    self.wide.window[row][col] = self.screen_read(row, col);
+                }
+            }
+        }
+
+        // 按新的偏移重绘窗口；回到 0 时正好把保存的前 80 列复制回去
+        self.h_offset = target;
+        for row in self.scroll_top..self.scroll_bottom {
+            for col in 0..W {
+                let ch = self.wide.cell(row, target + col);
+                self.screen_write(row, col, ch);
+            }
+        }
+        self.sync_hw_cursor();
+        self.present();
+    }
+
+    /// 水平滚动期间回到最左边，没有水平滚动时不做任何事
+    fn reset_horizontal(&mut self) {
+        if self.h_offset > 0 {
+            self.scroll_horizontal(-(self.h_offset as isize));
+        }
+    }
+
+    /// 是否正在回滚（显示的是历史内容而不是实时画面）
+    #[allow(dead_code)]
+    pub fn is_scrolled_back(&self) -> bool {
+        self.view_offset > 0
+    }
+
+    /// 一页的行数（滚动区域的行数），用于 PageUp/PageDown
+    pub fn page_lines(&self) -> usize {
+        self.scroll_bottom - self.scroll_top
+    }
+
+    /// 按当前回滚位置重绘滚动区域
+    ///
+    /// 把"历史行 + 实时画面"看成一个连续的序列，
+    /// 显示从末尾往上数 `view_offset` 行处结束的那一页
+    fn repaint_view(&mut self) {
+        let history_len = self.scrollback.len;
+        for i in 0..self.page_lines() {
+            let row = self.scroll_top + i;
+            let index = history_len - self.view_offset + i;
+            let line = if index < history_len {
+                *self.scrollback.line(index)
+            } else {
+                self.scrollback.live[self.scroll_top + index - history_len]
+            };
+            for (col, &ch) in line.iter().enumerate() {
+                self.screen_write(row, col, ch);
+            }
+        }
+        self.present();
+    }
+
+    /// 启用或关闭双缓冲
+    ///
+    /// 启用后输出只写到后台缓冲区，调用 `present()` 才会显示，
+    /// 硬件光标被隐藏，改为在 present 时绘制闪烁的软件光标；
+    /// 关闭时先显示后台缓冲区的内容，再恢复直接写显存和硬件光标
+    pub fn set_double_buffered(&mut self, on: bool) {
+        if on == self.double_buffered {
+            return;
+        }
+
+        if on {
+            // 从当前画面开始，避免第一次 present 把屏幕清空
+            for row in 0..self.height {
+                for col in 0..W {
+                    self.back_buffer[row][col] = self.buffer.chars[row][col].read();
+                }
+            }
+            self.double_buffered = true;
+        } else {
+            // 不带软件光标地显示最终画面
+            self.double_buffered = false;
+            for row in 0..self.height {
+                for col in 0..W {
+                    self.buffer.chars[row][col].write(self.back_buffer[row][col]);
+                }
+            }
+        }
+        DOUBLE_BUFFER_ACTIVE.store(on, Ordering::Relaxed);
+        self.apply_hw_cursor();
+        self.present();
+    }
+
+    /// 设置双缓冲模式下自动显示的时机（默认 `FlushPolicy::Manual`）
+    ///
+    /// 只影响双缓冲模式，直接写显存时输出总是立即可见
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
+    }
+
+    /// 把后台缓冲区复制到显存（没有启用双缓冲时不做任何事）
+    ///
+    /// 软件光标可见且处于"亮"的半周期时，光标所在单元格交换前景色和背景色
+    pub fn present(&mut self) {
+        if !self.double_buffered {
+            return;
+        }
+
+        let cursor = self.soft_cursor_cell();
+        for row in 0..self.height {
+            for col in 0..W {
+                let mut ch = self.back_buffer[row][col];
+                if cursor == Some((row, col)) {
+                    ch.color_code = ch.color_code.inverted();
+                }
+                self.buffer.chars[row][col].write(ch);
+            }
+        }
+    }
+
+    /// 设置双缓冲模式下是否显示软件光标
+    pub fn show_cursor(&mut self, visible: bool) {
+        self.soft_cursor_visible = visible;
+        self.present();
+    }
+
+    /// 当前应该绘制软件光标的单元格（行，列），不需要绘制时返回 None
+    fn soft_cursor_cell(&self) -> Option<(usize, usize)> {
+        let visible = self.soft_cursor_visible
+            && self.view_offset == 0
+            && CURSOR_BLINK_ON.load(Ordering::Relaxed);
+        visible.then(|| (self.row_position, self.cursor_window_column()))
+    }
+
+    /// 设置滚动区域
+    ///
+    /// 普通输出（print!/println!）只会在 `top..bottom` 行内换行和滚动
+    ///
+    /// # 参数
+    /// - `top`: 起始行（包含）
+    /// - `bottom`: 结束行（不包含），超出屏幕时截断到当前行数
+    #[allow(dead_code)]
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.height);
+        // 区域至少要有一行，否则忽略
+        if top >= bottom {
+            return;
+        }
+        // 回滚和水平滚动的副本按旧区域保存，先回到实时画面
+        self.scroll_forward(self.view_offset);
+        self.reset_horizontal();
+        self.scroll_top = top;
+        self.scroll_bottom = bottom;
+
+        // 光标落在新区域之外时，移到区域内
+        if self.row_position < top || self.row_position >= bottom {
+            self.row_position = top;
+            self.column_position = 0;
+        }
+    }
+
+    /// 状态栏所在的行（屏幕最底部一行）
+    /// 普通输出的默认滚动区域为 0..status_row()，不会覆盖状态栏
+    pub fn status_row(&self) -> usize {
+        self.height - 1
+    }
+
+    /// 当前文本模式的行数
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// 切换行数（文本模式改变后调用）
+    ///
+    /// 滚动区域的底部跟随新的状态栏位置，并把光标重新限制在区域内；
+    /// 行数变多时，新露出的行（包括原来的状态栏行）会被清空
+    fn set_height(&mut self, height: usize) {
+        let height = height.clamp(2, H);
+        self.scroll_forward(self.view_offset);
+        self.reset_horizontal();
+        let old_status_row = self.status_row();
+        self.height = height;
+
+        for row in old_status_row..height {
+            self.clear_row(row);
+        }
+
+        self.scroll_bottom = self.status_row();
+        self.scroll_top = self.scroll_top.min(self.scroll_bottom - 1);
+        if self.row_position >= self.scroll_bottom {
+            self.row_position = self.scroll_bottom - 1;
+        }
+        self.row_position = self.row_position.max(self.scroll_top);
+    }
+
+    /// 写入状态栏
+    ///
+    /// 状态栏独占屏幕最底部一行，不受光标和滚动影响
+    /// 文本会用空格补齐到整行宽度，避免残留上一条较长消息的字符
+    ///
+    /// # 参数
+    /// - `s`: 状态文本（超出屏幕宽度的部分被截断）
+    /// - `color`: 状态栏颜色
+    pub fn set_status(&mut self, s: &str, color: ColorCode) {
+        let mut chars = s.chars();
+        let status_row = self.status_row();
+        for col in 0..W {
+            // 不可打印字符用 ■ 表示，文本结束后用空格补齐
+            let char_to_write = chars.next().map_or(b' ', glyph_for);
+            self.put_char(
+                status_row,
+                col,
+                ScreenChar {
+                    ascii_character: char_to_write,
+                    color_code: color,
+                },
+            );
+        }
+    }
+
+    /// 清空指定行
+    ///
+    /// # 参数
+    /// - `row`: 要清空的行号（超出屏幕时不做任何事）
+    fn clear_row(&mut self, row: usize) {
+        if row >= self.height {
+            return;
+        }
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+        for col in 0..W {
+            self.put_char(row, col, blank);
+        }
+        self.wide.overflow[row] = [blank; LOGICAL_WIDTH];
+    }
+
+    /// 清空整个屏幕（包括状态栏）
+    pub fn clear_screen(&mut self) {
+        for row in 0..self.height {
+            self.clear_row(row);
+        }
+        self.column_position = 0;
+        self.row_position = self.scroll_top;
+        self.boot_step_row = None;
+        self.sync_hw_cursor();
+    }
+
+    /// 只清空滚动区域内的行，区域之外的行（如状态栏）保持不变
+    ///
+    /// 光标回到区域第一行的行首
+    pub fn clear_content(&mut self) {
+        for row in self.scroll_top..self.scroll_bottom {
+            self.clear_row(row);
+        }
+        self.column_position = 0;
+        self.row_position = self.scroll_top;
+        self.boot_step_row = None;
+        self.sync_hw_cursor();
+    }
+
+    /// 从光标所在列清除到行尾（ANSI 的 EL），使用当前颜色，光标不动
+    ///
+    /// `WrapMode::Extend` 下超出屏幕宽度的部分也一起清除
+    pub fn clear_to_eol(&mut self) {
+        let (row, col) = (self.row_position, self.column_position);
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: self.color_code,
+        };
+        for c in col..W {
+            self.put_char(row, c, blank);
+        }
+        self.wide.overflow[row][col.saturating_sub(W)..].fill(blank);
+    }
+
+    /// 从光标处清除到屏幕右下角（ANSI 的 ED），使用当前颜色，光标不动
+    ///
+    /// 和 ED 一样清到整个屏幕的底部，状态栏也会被清除
+    pub fn clear_to_eos(&mut self) {
+        self.clear_to_eol();
+        for row in self.row_position + 1..self.height {
+            self.clear_row(row);
+        }
+    }
+
+    /// 开始一个启动步骤：在新的一行显示 `[  ..  ] 消息`，并记住这一行
+    ///
+    /// 之后调用 `finish_boot_step` 把方括号改写成结果
+    pub fn boot_step(&mut self, args: fmt::Arguments) {
+        if self.column_position != 0 {
+            self.new_line();
+        }
+        let row = self.row_position;
+        self.write_string_at(BOOT_STEP_PENDING, row, 0, ColorCode::LIGHT_GRAY_ON_BLACK);
+        self.boot_step_row = Some(row);
+
+        self.column_position = BOOT_STEP_PENDING.len() + 1;
+        let _ = fmt::Write::write_fmt(self, format_args!("{}\n", args));
+    }
+
+    /// 结束最近的启动步骤，把它的方括号改写成 `[  OK  ]`（绿色）或 `[ FAIL ]`（红色）
+    ///
+    /// 没有未结束的步骤（或者那一行已经滚出屏幕）时不做任何事
+    pub fn finish_boot_step(&mut self, ok: bool) {
+        let Some(row) = self.boot_step_row.take() else {
+            return;
+        };
+        let (status, color) = if ok {
+            (BOOT_STEP_OK, ColorCode::LIGHT_GREEN_ON_BLACK)
+        } else {
+            (BOOT_STEP_FAIL, ColorCode::LIGHT_RED_ON_BLACK)
+        };
+        self.write_string_at(status, row, 0, color);
+    }
+
+    /// 设置当前颜色
+    pub fn set_color(&mut self, color: ColorCode) {
+        self.color_code = color;
+    }
+
+    /// 获取当前颜色（set_color 设置的原始颜色，不含反色/高亮）
+    pub fn color(&self) -> ColorCode {
+        self.color_code
+    }
+
+    /// 设置默认颜色，同时把它作为当前颜色
+    ///
+    /// 之后的输出、换行滚动出来的空行和 `clear_screen` 都使用这个颜色
+    pub fn set_default_color(&mut self, color: ColorCode) {
+        self.default_color = color;
+        self.color_code = color;
+    }
+
+    /// 获取默认颜色
+    pub fn default_color(&self) -> ColorCode {
+        self.default_color
+    }
+
+    /// 切换主题：`theme.normal` 成为默认颜色和当前颜色，其余颜色通过 `theme()` 查询
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.theme = *theme;
+        self.set_default_color(theme.normal);
+    }
+
+    /// 当前主题
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// 设置反色显示
+    ///
+    /// 开启后写入的字符交换前景色和背景色，关闭后恢复 set_color 设置的颜色
+    pub fn set_inverse(&mut self, on: bool) {
+        self.inverse = on;
+    }
+
+    /// 设置高亮显示
+    ///
+    /// 开启后写入的字符使用高亮前景色，关闭后恢复 set_color 设置的颜色
+    pub fn set_bright(&mut self, on: bool) {
+        self.bright = on;
+    }
+
+    /// 写入字符时实际使用的颜色
+    ///
+    /// 先在原始颜色上应用高亮（作用于前景色），再应用反色
+    fn effective_color(&self) -> ColorCode {
+        let mut color = self.run_color.unwrap_or(self.color_code);
+        if self.bright {
+            color = color.with_bright(true);
+        }
+        if self.inverse {
+            color = color.inverted();
+        }
+        color
+    }
+}
+
+/// 只适用于 VGA 显存尺寸（80 × 50）的操作
+impl Writer {
+    /// 创建写入 `addr` 处显存的 Writer（例如重新映射到别处的 VGA 显存）
+    ///
+    /// # Safety
+    /// `addr` 必须指向已映射的 VGA 显存，并且没有被其他 Writer 使用
+    ///
+    /// # Errors
+    /// 地址为 0 或没有按页对齐
+    #[allow(dead_code)]
+    pub unsafe fn with_buffer_addr(
+        addr: usize,
+        storage: &'static mut WriterStorage,
+        color: ColorCode,
+    ) -> Result<Writer, BufferAddrError> {
+        let buffer = unsafe { Buffer::at_addr(addr)? };
+        Ok(Writer::new(buffer, storage, color))
+    }
+
+    /// 把屏幕当前所有行（25 或 50 行）的内容保存到调用者提供的缓冲区
+    ///
+    /// 直接写显存时逐个单元格 volatile 读取显存；双缓冲时读取后台缓冲区
+    pub fn snapshot(&self, out: &mut Snapshot) {
+        out.height = self.height;
+        for (i, cell) in out.cells[..self.height * VGA_WIDTH].iter_mut().enumerate() {
+            *cell = self.screen_read(i / VGA_WIDTH, i % VGA_WIDTH);
+        }
+    }
+
+    /// 把 `snapshot` 保存的内容写回屏幕，不改变光标位置
+    ///
+    /// 快照的行数比当前屏幕多时（拍快照之后切回了 80×25），多出的行被丢弃
+    pub fn restore(&mut self, snap: &Snapshot) {
+        for (row, cells) in snap.rows().iter().enumerate().take(self.height) {
+            for (col, &cell) in cells.iter().enumerate() {
+                self.screen_write(row, col, cell);
+            }
+        }
+        self.present();
+    }
+
+    /// 让 Writer 改用新的显存映射
+    ///
+    /// 新旧地址映射的是同一块物理显存，屏幕内容不需要复制
+    fn set_buffer(&mut self, buffer: &'static mut Buffer) {
+        self.buffer = buffer;
+        self.present();
+    }
+}
+
+/// 实现 fmt::Write trait，支持格式化输出
+///
+/// 写显存不会失败：无法显示的字符用 ■ 代替，超出屏幕时滚动，
+/// 因此 `write_str` 总是返回 `Ok(())`
+impl<const W: usize, const H: usize> fmt::Write for Writer<W, H> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
+    }
+}
+
+// =============================================================================
+// 子窗口
+// =============================================================================
+
+/// 屏幕上的一个矩形子窗口，有自己的光标，输出只在窗口范围内换行和滚动
+///
+/// 例如左边一个日志窗口、右边一个状态窗口，互不覆盖；
+/// 窗口不拥有显存，每次输出时通过全局 `WRITER` 写入（也可以直接调用 `Writer::write_window`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    /// 窗口第一行在屏幕上的行号
+    pub top: usize,
+    /// 窗口第一列在屏幕上的列号
+    pub left: usize,
+    /// 行数
+    pub height: usize,
+    /// 列数
+    pub width: usize,
+    /// 输出颜色
+    pub color: ColorCode,
+    /// 窗口内的光标行（相对窗口）
+    row: usize,
+    /// 窗口内的光标列（相对窗口），等于 `width` 时表示行满待换行
+    column: usize,
+}
+
+impl Window {
+    /// 在全局 `WRITER` 的屏幕上创建窗口，光标在窗口左上角（裁剪规则见 `Writer::window`）
+    ///
+    /// 会短暂获取 `WRITER` 的锁读取当前行数，已经持有锁时请直接调用 `Writer::window`
+    pub fn new(top: usize, left: usize, height: usize, width: usize, color: ColorCode) -> Window {
+        WRITER.lock().window(top, left, height, width, color)
+    }
+
+    /// 窗口的排版参数（行列号相对窗口）
+    fn layout(&self) -> TextLayout {
+        TextLayout::new(self.width, 0, self.height)
+    }
+
+    /// 窗口内的光标位置（行，列），相对窗口左上角
+    pub fn cursor_position(&self) -> (usize, usize) {
+        (self.row, self.column)
+    }
+
+    /// 在窗口中输出格式化文本
+    pub fn print(&mut self, args: fmt::Arguments) {
+        let _ = fmt::Write::write_fmt(self, args);
+    }
+
+    /// 在窗口中输出格式化文本并换行
+    pub fn println(&mut self, args: fmt::Arguments) {
+        self.print(args);
+        self.print(format_args!("\n"));
+    }
+
+    /// 用窗口颜色清空窗口，光标回到左上角
+    pub fn clear(&mut self) {
+        WRITER.lock().clear_window(self);
+    }
+}
+
+/// 写入窗口不会失败，和 `Writer` 一样总是返回 `Ok(())`
+impl fmt::Write for Window {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        WRITER.lock().write_window(self, s);
+        Ok(())
+    }
+}
+
+impl Writer {
+    /// 在这个 Writer 的屏幕上创建窗口，光标在窗口左上角
+    ///
+    /// 超出当前屏幕（80 列 × 当前行数，80×50 模式下为 50 行）的部分被裁掉，宽高至少为 1
+    pub fn window(
+        &self,
+        top: usize,
+        left: usize,
+        height: usize,
+        width: usize,
+        color: ColorCode,
+    ) -> Window {
+        let top = top.min(self.height - 1);
+        let left = left.min(VGA_WIDTH - 1);
+        Window {
+            top,
+            left,
+            height: height.clamp(1, self.height - top),
+            width: width.clamp(1, VGA_WIDTH - left),
+            color,
+            row: 0,
+            column: 0,
+        }
+    }
+
+    /// 在子窗口中写入字符串，只在窗口范围内换行和滚动
+    ///
+    /// 控制字符的处理和 `write_string` 相同；不移动 Writer 自己的光标
+    pub fn write_window(&mut self, window: &mut Window, s: &str) {
+        for c in s.chars() {
+            match c {
+                '\n' => self.window_new_line(window),
+                '\r' => window.column = 0,
+                '\t' => {
+                    let next_stop = window.layout().tab_stop(window.column);
+                    while window.column < next_stop {
+                        self.window_put(window, b' ');
+                    }
+                }
+                '\x08' => window.column = window.column.saturating_sub(1),
+                c if c.is_ascii_control() => self.window_put(window, REPLACEMENT_GLYPH),
+                c => self.window_put(window, glyph_for(c)),
+            }
+        }
+    }
+
+    /// 用窗口颜色清空子窗口，窗口光标回到左上角
+    pub fn clear_window(&mut self, window: &mut Window) {
+        for row in 0..window.height {
+            self.clear_window_row(window, row);
+        }
+        window.row = 0;
+        window.column = 0;
+    }
+
+    /// 在窗口光标处写入一个字形，行满时先在窗口内换行
+    fn window_put(&mut self, window: &mut Window, glyph: u8) {
+        if window.layout().is_line_full(window.column) {
+            self.window_new_line(window);
+        }
+        self.put_char(
+            window.top + window.row,
+            window.left + window.column,
+            ScreenChar {
+                ascii_character: glyph,
+                color_code: window.color,
+            },
+        );
+        window.column += 1;
+    }
+
+    /// 窗口内换行：已经在最后一行时只滚动窗口覆盖的矩形
+    fn window_new_line(&mut self, window: &mut Window) {
+        match window.layout().line_feed(window.row) {
+            LineFeed::Next(row) => window.row = row,
+            LineFeed::Scroll => self.scroll_window(window),
+        }
+        window.column = 0;
+    }
+
+    /// 把窗口内的每一行上移一行，最后一行用窗口颜色清空
+    ///
+    /// 只复制窗口的列范围；窗口占满整行时和滚动区域一样整行复制
+    fn scroll_window(&mut self, window: &Window) {
+        let full_width = window.left == 0 && window.width == VGA_WIDTH;
+        for row in window.top + 1..window.top + window.height {
+            if full_width {
+                self.reset_horizontal();
+                self.copy_row(row, row - 1);
+            } else {
+                for col in window.left..window.left + window.width {
+                    let ch = self.char_at(row, col);
+                    self.put_char(row - 1, col, ch);
+                }
+            }
+        }
+        self.clear_window_row(window, window.height - 1);
+    }
+
+    /// 用窗口颜色清空窗口的第 `row` 行（相对窗口）
+    fn clear_window_row(&mut self, window: &Window, row: usize) {
+        let blank = ScreenChar {
+            ascii_character: b' ',
+            color_code: window.color,
+        };
+        for col in window.left..window.left + window.width {
+            self.put_char(window.top + row, col, blank);
+        }
+    }
+}
+
+// =============================================================================
+// 进度条
+// =============================================================================
+
+// 进度条的字形（CP437）：每格分 4 级，依次为 ░ ▒ ▓ █
+const PROGRESS_GLYPHS: [u8; 5] = [b' ', 0xB0, 0xB1, 0xB2, 0xDB];
+
+/// 每一格能表示的进度级数
+const PROGRESS_STEPS: usize = PROGRESS_GLYPHS.len() - 1;
+
+/// 进度条右侧百分比标签占的列数（`" 100%"`）
+const PROGRESS_LABEL_WIDTH: usize = 5;
+
+/// 显示在屏幕某一行上的进度条：左边是方块组成的条，右边是百分比
+///
+/// 直接写入单元格，不移动光标；只重画和上一次不同的单元格，频繁更新也不会闪烁。
+/// 位置和宽度只能通过 `new`/`move_to` 设置，保证整条进度条都在屏幕宽度之内
+pub struct ProgressBar {
+    /// 所在的行
+    row: usize,
+    /// 第一列
+    left: usize,
+    /// 总列数（包括百分比标签），不超过 `VGA_WIDTH - left`
+    width: usize,
+    /// 颜色
+    color: ColorCode,
+    /// 当前进度（0~100）
+    percent: u8,
+    /// 上一次画在每一列上的字形，0 表示还没有画过
+    drawn: [u8; VGA_WIDTH],
+}
+
+impl ProgressBar {
+    /// 创建进度条（进度为 0，还没有画到屏幕上）
+    ///
+    /// 超出屏幕右边的部分被裁掉；宽度至少要放得下百分比标签和一格进度条
+    pub fn new(row: usize, left: usize, width: usize, color: ColorCode) -> ProgressBar {
+        let (left, width) = Self::clamp_span(left, width);
+        ProgressBar {
+            row,
+            left,
+            width,
+            color,
+            percent: 0,
+            drawn: [0; VGA_WIDTH],
+        }
+    }
+
+    /// 把第一列和宽度限制在屏幕之内（规则见 `new`）
+    const fn clamp_span(left: usize, width: usize) -> (usize, usize) {
+        let max_left = VGA_WIDTH - PROGRESS_LABEL_WIDTH - 1;
+        let left = if left < max_left { left } else { max_left };
+        let max_width = VGA_WIDTH - left;
+        let width = if width < PROGRESS_LABEL_WIDTH + 1 {
+            PROGRESS_LABEL_WIDTH + 1
+        } else if width > max_width {
+            max_width
+        } else {
+            width
+        };
+        (left, width)
+    }
+
+    /// 所在的行
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// 第一列
+    pub fn left(&self) -> usize {
+        self.left
+    }
+
+    /// 总列数（包括百分比标签）
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// 移动到新的位置并整条重画（和 `new` 一样裁剪到屏幕之内），旧位置上的内容不会被擦除
+    #[allow(dead_code)]
+    pub fn move_to(&mut self, row: usize, left: usize, width: usize) {
+        let (left, width) = Self::clamp_span(left, width);
+        self.row = row;
+        self.left = left;
+        self.width = width;
+        self.redraw();
+    }
+
+    /// 当前进度（0~100）
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    /// 设置进度（超过 100 按 100 处理）并更新屏幕
+    pub fn set_percent(&mut self, percent: u8) {
+        self.percent = percent.min(100);
+        WRITER.lock().draw_progress_bar(self);
+    }
+
+    /// 按完成数量设置进度：`done / total`，`total` 为 0 时视为已完成
+    pub fn set_progress(&mut self, done: usize, total: usize) {
+        let percent = (done.min(total) * 100).checked_div(total).unwrap_or(100);
+        self.set_percent(percent as u8);
+    }
+
+    /// 忘掉已经画过的内容，整条重画（进度条被其他输出覆盖之后使用）
+    pub fn redraw(&mut self) {
+        self.drawn = [0; VGA_WIDTH];
+        WRITER.lock().draw_progress_bar(self);
+    }
+
+    /// 进度条部分的格数
+    fn bar_cells(&self) -> usize {
+        self.width - PROGRESS_LABEL_WIDTH
+    }
+
+    /// 第 `col` 列（相对进度条左端）应该显示的字形
+    fn glyph_at(&self, col: usize) -> u8 {
+        let bar_cells = self.bar_cells();
+        if col < bar_cells {
+            let filled = usize::from(self.percent) * bar_cells * PROGRESS_STEPS / 100;
+            let level = filled
+                .saturating_sub(col * PROGRESS_STEPS)
+                .min(PROGRESS_STEPS);
+            return PROGRESS_GLYPHS[level];
+        }
+        // 百分比标签：一个空格加右对齐的 "NNN%"
+        let mut buf = [0u8; 20];
+        let digits = format::dec_digits(u64::from(self.percent), &mut buf);
+        let label_col = col - bar_cells;
+        let first_digit = PROGRESS_LABEL_WIDTH - 1 - digits.len();
+        match label_col {
+            c if c == PROGRESS_LABEL_WIDTH - 1 => b'%',
+            c if c >= first_digit => digits[c - first_digit],
+            _ => b' ',
+        }
+    }
+}
+
+impl Writer {
+    /// 画出进度条中和上一次不同的单元格，不移动光标
+    pub fn draw_progress_bar(&mut self, bar: &mut ProgressBar) {
+        for col in 0..bar.width.min(bar.drawn.len()) {
+            let glyph = bar.glyph_at(col);
+            if bar.drawn[col] == glyph {
+                continue;
+            }
+            bar.drawn[col] = glyph;
+            self.put_char(
+                bar.row,
+                bar.left + col,
+                ScreenChar {
+                    ascii_character: glyph,
+                    color_code: bar.color,
+                },
+            );
+        }
+    }
+}
+
+// =============================================================================
+// 全局 Writer 实例
+// =============================================================================
+
+lazy_static! {
+    /// 全局 VGA Writer 实例
+    /// 使用 Mutex 保护，确保线程安全
+    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer::new(
+        unsafe { &mut *(VGA_BUFFER_ADDR as *mut Buffer) },
+        unsafe { &mut *core::ptr::addr_of_mut!(WRITER_STORAGE) },
+        default_color(),
+    ));
+}
+
+/// `set_buffer_addr` 拒绝新地址的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferAddrError {
+    /// 地址为 0
+    Null,
+    /// 地址没有按页对齐
+    Misaligned,
+}
+
+// =============================================================================
+// VGA 硬件寄存器
+// =============================================================================
+
+// 属性控制器（Attribute Controller）端口
+const ATTR_ADDRESS_DATA: u16 = 0x3C0; // 索引/数据写端口（由内部触发器切换）
+const ATTR_DATA_READ: u16 = 0x3C1; // 数据读端口
+const INPUT_STATUS_1: u16 = 0x3DA; // 读取它会把触发器复位到"索引"状态
+
+/// 属性模式控制寄存器索引（0x10），并置位 0x20（PAS）保持屏幕显示
+const ATTR_MODE_CONTROL: u8 = 0x10 | 0x20;
+
+/// 属性模式控制寄存器位 3：属性字节最高位表示闪烁（而不是亮背景）
+const ATTR_BLINK_ENABLE: u8 = 0x08;
+
+// 序列器（Sequencer）端口与寄存器
+const SEQ_INDEX: u16 = 0x3C4;
+const SEQ_DATA: u16 = 0x3C5;
+const SEQ_MAP_MASK: u8 = 0x02; // 写入哪些位平面
+const SEQ_MEMORY_MODE: u8 = 0x04; // 内存模式（奇偶寻址等）
+
+// 图形控制器（Graphics Controller）端口与寄存器
+const GC_INDEX: u16 = 0x3CE;
+const GC_DATA: u16 = 0x3CF;
+const GC_READ_MAP: u8 = 0x04; // 读取哪个位平面
+const GC_MODE: u8 = 0x05; // 读写模式
+const GC_MISC: u8 = 0x06; // 显存映射区域
+
+// CRT 控制器（CRTC）端口与寄存器
+const CRTC_INDEX: u16 = 0x3D4;
+const CRTC_DATA: u16 = 0x3D5;
+const CRTC_MAX_SCANLINE: u8 = 0x09; // 位 0-4：字符高度 - 1
+const CRTC_CURSOR_START: u8 = 0x0A; // 位 0-4：光标起始扫描线
+const CRTC_CURSOR_END: u8 = 0x0B; // 位 0-4：光标结束扫描线
+const CRTC_CURSOR_HIGH: u8 = 0x0E; // 光标位置（线性偏移）高字节
+const CRTC_CURSOR_LOW: u8 = 0x0F; // 光标位置（线性偏移）低字节
+
+/// 光标起始寄存器位 5：隐藏光标
+const CURSOR_DISABLE: u8 = 0x20;
+
+/// 访问位平面 2 时字体数据的映射地址
+const FONT_PLANE_ADDR: usize = 0xA0000;
+
+/// 字体在位平面 2 中每个字符占用的字节数（无论实际高度）
+const FONT_CHAR_STRIDE: usize = 32;
+
+/// 80×25 模式的 8×16 字体，切换到 80×50 时保存，切回时恢复
+static SAVED_FONT_8X16: Mutex<Option<[u8; 256 * 16]>> = Mutex::new(None);
+
+/// 读写 VGA 的索引/数据寄存器对
+fn read_indexed(index_port: u16, data_port: u16, index: u8) -> u8 {
+    outb(index_port, index);
+    inb(data_port)
+}
+
+fn write_indexed(index_port: u16, data_port: u16, index: u8, value: u8) {
+    outb(index_port, index);
+    outb(data_port, value);
+}
+
+/// 把位平面 2（字体平面）映射到 0xA0000，并在闭包返回后恢复文本模式映射
+fn with_font_plane<R>(f: impl FnOnce(*mut u8) -> R) -> R {
+    // 只写平面 2，顺序寻址，读平面 2，映射到 A0000
+    write_indexed(SEQ_INDEX, SEQ_DATA, SEQ_MAP_MASK, 0x04);
+    write_indexed(SEQ_INDEX, SEQ_DATA, SEQ_MEMORY_MODE, 0x07);
+    write_indexed(GC_INDEX, GC_DATA, GC_READ_MAP, 0x02);
+    write_indexed(GC_INDEX, GC_DATA, GC_MODE, 0x00);
+    write_indexed(GC_INDEX, GC_DATA, GC_MISC, 0x04);
+
+    let result = f(FONT_PLANE_ADDR as *mut u8);
+
+    // 恢复文本模式：写平面 0/1，奇偶寻址，映射到 B8000
+    write_indexed(SEQ_INDEX, SEQ_DATA, SEQ_MAP_MASK, 0x03);
+    write_indexed(SEQ_INDEX, SEQ_DATA, SEQ_MEMORY_MODE, 0x03);
+    write_indexed(GC_INDEX, GC_DATA, GC_READ_MAP, 0x00);
+    write_indexed(GC_INDEX, GC_DATA, GC_MODE, 0x10);
+    write_indexed(GC_INDEX, GC_DATA, GC_MISC, 0x0E);
+
+    result
+}
+
+/// 设置字符高度（扫描线数），同时调整光标形状到字符底部两行
+fn set_char_height(lines: u8) {
+    let max_scanline = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_MAX_SCANLINE);
+    write_indexed(
+        CRTC_INDEX,
+        CRTC_DATA,
+        CRTC_MAX_SCANLINE,
+        (max_scanline & 0xE0) | (lines - 1),
+    );
+
+    let cursor_start = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_START);
+    write_indexed(
+        CRTC_INDEX,
+        CRTC_DATA,
+        CRTC_CURSOR_START,
+        (cursor_start & 0xE0) | (lines - 2),
+    );
+    let cursor_end = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_END);
+    write_indexed(
+        CRTC_INDEX,
+        CRTC_DATA,
+        CRTC_CURSOR_END,
+        (cursor_end & 0xE0) | (lines - 1),
+    );
+}
+
+/// 显示硬件光标（保留当前光标形状）
+fn enable_cursor() {
+    let start = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_START);
+    write_indexed(
+        CRTC_INDEX,
+        CRTC_DATA,
+        CRTC_CURSOR_START,
+        start & !CURSOR_DISABLE,
+    );
+}
+
+/// 隐藏硬件光标
+fn disable_cursor() {
+    let start = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_START);
+    write_indexed(
+        CRTC_INDEX,
+        CRTC_DATA,
+        CRTC_CURSOR_START,
+        start | CURSOR_DISABLE,
+    );
+}
+
+/// 把硬件光标移动到指定位置
+///
+/// CRTC 使用线性偏移（row × 80 + col）表示光标位置
+fn update_cursor(row: usize, col: usize) {
+    // 超出范围时限制在屏幕最后一格，避免乘法溢出或光标跑到显存之外
+    let offset = (row.min(MAX_HEIGHT - 1) * VGA_WIDTH + col.min(VGA_WIDTH - 1)) as u16;
+    write_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_HIGH, (offset >> 8) as u8);
+    write_indexed(
+        CRTC_INDEX,
+        CRTC_DATA,
+        CRTC_CURSOR_LOW,
+        (offset & 0xFF) as u8,
+    );
+}
+
+/// 从 CRTC 读回硬件光标的线性偏移
+fn read_cursor_offset() -> u16 {
+    let high = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_HIGH);
+    let low = read_indexed(CRTC_INDEX, CRTC_DATA, CRTC_CURSOR_LOW);
+    u16::from(high) << 8 | u16::from(low)
+}
+
+/// 关闭字符闪烁，让属性字节的最高位表示亮背景色
+///
+/// 默认情况下属性字节最高位表示"闪烁"，背景色只有 8 种，
+/// 关闭后背景色也能使用全部 16 种颜色（如浅灰背景不再闪烁）
+pub fn disable_blink() {
+    // 1. 读 0x3DA 把触发器复位到索引状态
+    inb(INPUT_STATUS_1);
+    // 2. 选择模式控制寄存器，读出当前值
+    outb(ATTR_ADDRESS_DATA, ATTR_MODE_CONTROL);
+    let mode = inb(ATTR_DATA_READ);
+    // 3. 触发器此时处于数据状态，写回清除闪烁位后的值
+    outb(ATTR_ADDRESS_DATA, mode & !ATTR_BLINK_ENABLE);
+}
+
+// =============================================================================
+// 公共函数接口
+// =============================================================================
+
+/// 初始化 VGA 文本模式
+///
+/// 先探测显存是否存在，不存在（没有 VGA 显卡，例如 QEMU 的 `-vga none`）时 `println!` 改为输出到串口；
+/// 存在时关闭闪烁属性，使 16 种背景色都可用，并让硬件光标跟随输出位置。
+/// 重复调用只输出警告（再次探测会短暂改写显存）
+pub fn init() {
+    if INITIALIZED.swap(true, Ordering::SeqCst) {
+        crate::serial_println!("[WARN] vga::init called twice, ignored");
+        return;
+    }
+    let present = probe();
+    VGA_PRESENT.store(present, Ordering::Relaxed);
+    if !present {
+        return;
+    }
+    disable_blink();
+    WRITER.lock().set_hw_cursor_enabled(true);
+}
+
+/// `init` 是否已经调用过
+#[allow(dead_code)]
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::SeqCst)
+}
+
+/// 探测 0xb8000 处是否真的是显存：写入测试值再用 volatile 读回，结束后恢复原值
+///
+/// 没有显卡时这段地址读回的是总线上的固定值（通常全为 1），和写入的不一致
+///
+/// 只能发现显卡不存在，不能发现"显卡存在但画面没人看"：QEMU 的 `-nographic`
+/// 仍然模拟一块完整的 VGA 显卡（显存和 CRTC 寄存器都正常读写），探测结果为存在。
+/// 这种情况下想让 `println!` 走串口，需要同时加上 `-vga none`
+fn probe() -> bool {
+    let cell = (VGA_BUFFER_ADDR + VGA_PROBE_OFFSET) as *mut u16;
+    // SAFETY: 0xb8000 起的 32 KiB 在启动时已恒等映射，探测的单元不在屏幕可见范围内
+    unsafe {
+        let original = cell.read_volatile();
+        let present = VGA_PROBE_PATTERNS.iter().all(|&pattern| {
+            cell.write_volatile(pattern);
+            cell.read_volatile() == pattern
+        });
+        cell.write_volatile(original);
+        present
+    }
+}
+
+/// 启动时是否探测到了 VGA 显存
+///
+/// 不存在时 `print!`/`println!` 自动改为输出到串口；
+/// 只反映显卡是否存在，QEMU `-nographic` 下仍然为 true（见 `probe`）
+pub fn is_present() -> bool {
+    VGA_PRESENT.load(Ordering::Relaxed)
+}
+
+/// 切换到 80×50 文本模式（8×8 字体）
+///
+/// 8×8 字体由当前 8×16 字体隔行取样得到（每两条扫描线合并为一条），
+/// 原字体会被保存，以便 [`set_mode_80x25`] 恢复
+/// 垂直显示的总扫描线数仍为 400，400 / 8 = 50 行
+///
+/// # 已知限制
+/// 内核没有自带 8×8 字体，按位或合并扫描线只是近似：笔画之间只隔一条扫描线的字形
+/// （如 `e`、`s`、`8`、`B`）会糊成一团，框线字形也会变粗。
+/// 80×50 模式适合临时查看更多输出，长时间阅读请使用 80×25
+#[allow(dead_code)]
+pub fn set_mode_80x50() {
+    let mut writer = WRITER.lock();
+    if writer.height == MAX_HEIGHT {
+        return;
+    }
+
+    let mut saved = SAVED_FONT_8X16.lock();
+    with_font_plane(|plane| {
+        let mut font = [0u8; 256 * 16];
+        for (ch, glyph) in font.chunks_mut(16).enumerate() {
+            let base = ch * FONT_CHAR_STRIDE;
+            // 读出原 8×16 字形
+            for (line, byte) in glyph.iter_mut().enumerate() {
+                *byte = unsafe { core::ptr::read_volatile(plane.add(base + line)) };
+            }
+            // 合并相邻两条扫描线，写入 8×8 字形
+            for line in 0..8 {
+                let merged = glyph[line * 2] | glyph[line * 2 + 1];
+                unsafe { core::ptr::write_volatile(plane.add(base + line), merged) };
+            }
+        }
+        *saved = Some(font);
+    });
+    set_char_height(8);
+
+    writer.set_height(MAX_HEIGHT);
+}
+
+/// 切换回默认的 80×25 文本模式（8×16 字体）
+#[allow(dead_code)]
+pub fn set_mode_80x25() {
+    let mut writer = WRITER.lock();
+    if writer.height == VGA_HEIGHT {
+        return;
+    }
+
+    // 恢复之前保存的 8×16 字体
+    if let Some(font) = SAVED_FONT_8X16.lock().as_ref() {
+        with_font_plane(|plane| {
+            for (ch, glyph) in font.chunks(16).enumerate() {
+                let base = ch * FONT_CHAR_STRIDE;
+                for (line, &byte) in glyph.iter().enumerate() {
+                    unsafe { core::ptr::write_volatile(plane.add(base + line), byte) };
+                }
+            }
+        });
+    }
+    set_char_height(16);
+
+    writer.set_height(VGA_HEIGHT);
+}
+
+/// 让一段文字在屏幕上水平居中的起始列
+///
+/// 文字比屏幕宽时返回 0（从行首开始，超出部分被截断）
+///
+/// # 参数
+/// - `text_width`: 文字占用的列数
+pub fn centered_column(text_width: usize) -> usize {
+    VGA_WIDTH.saturating_sub(text_width) / 2
+}
+
+/// 获取当前光标位置（行，列）
+#[allow(dead_code)]
+pub fn cursor_position() -> (usize, usize) {
+    WRITER.lock().cursor_position()
+}
+
+/// 移动光标到指定位置（超出滚动区域时限制在边界内）
+///
+/// # 参数
+/// - `row`: 行号
+/// - `col`: 列号
+#[allow(dead_code)]
+pub fn set_cursor(row: usize, col: usize) {
+    WRITER.lock().set_cursor(row, col);
+}
+
+/// 从 CRTC 寄存器读回硬件光标的实际位置（行，列）
+///
+/// 和 `cursor_position()` 比较可以发现软件光标和硬件光标不同步的问题；
+/// 硬件光标没有启用时读到的是最后一次同步的位置
+#[allow(dead_code)]
+pub fn hardware_cursor_position() -> (usize, usize) {
+    let offset = usize::from(read_cursor_offset());
+    (offset / VGA_WIDTH, offset % VGA_WIDTH)
+}
+
+/// 保存当前光标位置
+#[allow(dead_code)]
+pub fn save_cursor() {
+    WRITER.lock().save_cursor();
+}
+
+/// 恢复之前保存的光标位置
+#[allow(dead_code)]
+pub fn restore_cursor() {
+    WRITER.lock().restore_cursor();
+}
+
+/// 设置是否显示硬件光标并让它跟随输出位置
+#[allow(dead_code)]
+pub fn set_hw_cursor_enabled(enabled: bool) {
+    WRITER.lock().set_hw_cursor_enabled(enabled);
+}
+
+/// 当前文本模式的行数（25 或 50）
+#[allow(dead_code)]
+pub fn height() -> usize {
+    WRITER.lock().height()
+}
+
+/// 向上回滚若干行（显示更早的输出）
+#[allow(dead_code)]
+pub fn scroll_back(lines: usize) {
+    WRITER.lock().scroll_back(lines);
+}
+
+/// 向下滚动若干行，回到底部时恢复实时画面
+#[allow(dead_code)]
+pub fn scroll_forward(lines: usize) {
+    WRITER.lock().scroll_forward(lines);
+}
+
+/// 水平滚动若干列（正数向右），查看超出屏幕宽度的宽行内容
+pub fn scroll_horizontal(cols: isize) {
+    WRITER.lock().scroll_horizontal(cols);
+}
+
+/// 启用或关闭双缓冲（启用后需要调用 `present()` 才会显示输出）
+#[allow(dead_code)]
+pub fn set_double_buffered(on: bool) {
+    WRITER.lock().set_double_buffered(on);
+}
+
+/// 设置双缓冲模式下自动显示的时机，例如 `FlushPolicy::OnNewline` 让 `println!` 每行立即可见
+#[allow(dead_code)]
+pub fn set_flush_policy(policy: FlushPolicy) {
+    WRITER.lock().set_flush_policy(policy);
+}
+
+/// 把双缓冲的后台缓冲区显示到屏幕
+#[allow(dead_code)]
+pub fn present() {
+    WRITER.lock().present();
+}
+
+/// 设置双缓冲模式下是否显示闪烁的软件光标
+#[allow(dead_code)]
+pub fn show_cursor(visible: bool) {
+    WRITER.lock().show_cursor(visible);
+}
+
+/// 时钟中断调用：推进软件光标的闪烁
+///
+/// 每 `CURSOR_BLINK_MS` 毫秒翻转一次闪烁相位，并在双缓冲模式下重新 present，
+/// 这样即使没有新的输出，光标也会闪烁。被打断的代码正持有 WRITER 锁时跳过这一次
+pub fn on_timer_tick() {
+    let blink_ticks = CURSOR_BLINK_MS / timer::MS_PER_TICK;
+    if !timer::ticks().is_multiple_of(blink_ticks) {
+        return;
+    }
+    CURSOR_BLINK_ON.fetch_xor(true, Ordering::Relaxed);
+
+    if DOUBLE_BUFFER_ACTIVE.load(Ordering::Relaxed)
+        && let Some(mut writer) = WRITER.try_lock()
+    {
+        writer.present();
+    }
+}
+
+/// 把 VGA 输出重新指向显存的新虚拟地址
+///
+/// 默认通过恒等映射的 0xb8000 访问显存；分页把显存映射到别处（如高半区）之后，
+/// 需要调用这个函数，VGA 控制台才能继续工作
+///
+/// # 错误
+/// 地址为 0 或者没有按页对齐时返回错误，Writer 保持原来的地址
+///
+/// # Safety
+/// `addr` 开始的整块文本显存（MAX_HEIGHT × VGA_WIDTH 个字符单元）必须已经映射到物理地址 0xb8000 且可写，
+/// 并且之后一直保持映射
+#[allow(dead_code)]
+pub unsafe fn set_buffer_addr(addr: usize) -> Result<(), BufferAddrError> {
+    let buffer = unsafe { Buffer::at_addr(addr)? };
+    WRITER.lock().set_buffer(buffer);
+    Ok(())
+}
+
+/// 设置行满后的处理方式：`WrapMode::Wrap` 换行（默认），`WrapMode::Truncate` 截断，
+/// `WrapMode::Extend` 保存为宽行
+#[allow(dead_code)]
+pub fn set_wrap_mode(mode: WrapMode) {
+    WRITER.lock().set_wrap_mode(mode);
+}
+
+/// 在当前位置输出调色板测试图案
+///
+/// 第一行用 16 种前景色（黑底）显示颜色编号，第二行用 16 种背景色填充色块。
+/// 关闭闪烁（`disable_blink`）后，后 8 种背景色应该是稳定的亮色而不是闪烁
+pub fn color_test() {
+    let mut writer = WRITER.lock();
+    let original = writer.color();
+    let cell_width = VGA_WIDTH / 16;
+
+    for background in [false, true] {
+        if writer.column_position != 0 {
+            writer.new_line();
+        }
+        for (i, color) in Color::all().into_iter().enumerate() {
+            let code = if background {
+                ColorCode::new(Color::Black, color)
+            } else {
+                ColorCode::new(color, Color::Black)
+            };
+            writer.set_color(code);
+            let _ = fmt::Write::write_fmt(&mut *writer, format_args!("{:^1$}", i, cell_width));
+        }
+    }
+
+    writer.set_color(original);
+    writer.write_string("\n");
+}
+
+/// 把屏幕当前所有行的内容保存到 `out`
+pub fn snapshot(out: &mut Snapshot) {
+    WRITER.lock().snapshot(out);
+}
+
+/// 把 `snapshot` 保存的内容写回屏幕
+#[allow(dead_code)]
+pub fn restore(snap: &Snapshot) {
+    WRITER.lock().restore(snap);
+}
+
+/// 设置默认颜色（也立即成为当前颜色），之后清屏和滚动出来的空行也使用它
+#[allow(dead_code)]
+pub fn set_default_color(color: ColorCode) {
+    WRITER.lock().set_default_color(color);
+}
+
+/// 切换主题：`theme.normal` 成为默认颜色，错误、警告和提示符的颜色通过 `theme()` 查询
+#[allow(dead_code)]
+pub fn set_theme(theme: &Theme) {
+    WRITER.lock().set_theme(theme);
+}
+
+/// 当前主题
+#[allow(dead_code)]
+pub fn theme() -> Theme {
+    WRITER.lock().theme()
+}
+
+/// 在指定位置写入一个单元格，不移动光标
+#[allow(dead_code)]
+pub fn put_cell(row: usize, col: usize, cell: Cell) {
+    WRITER.lock().put_cell(row, col, cell);
+}
+
+/// 读取指定位置的单元格，超出屏幕范围时返回 None
+#[allow(dead_code)]
+pub fn get_cell(row: usize, col: usize) -> Option<Cell> {
+    WRITER.lock().get_cell(row, col)
+}
+
+/// 清空屏幕
+pub fn clear_screen() {
+    WRITER.lock().clear_screen();
+}
+
+/// 清空滚动区域内的内容，保留状态栏
+pub fn clear_content() {
+    WRITER.lock().clear_content();
+}
+
+/// 从光标处清除到行尾，光标不动
+#[allow(dead_code)]
+pub fn clear_to_eol() {
+    WRITER.lock().clear_to_eol();
+}
+
+/// 从光标处清除到屏幕右下角，光标不动
+#[allow(dead_code)]
+pub fn clear_to_eos() {
+    WRITER.lock().clear_to_eos();
+}
+
+/// 不加锁，直接用 volatile 写入 0xb8000 处的显存，在指定位置显示字符串
+///
+/// **只用于 panic 和早期启动**：`WRITER` 还没有初始化，或者可能正被被打断的代码持有时使用。
+/// 不移动光标、不滚动，超出行尾的部分被截断；无法显示的字符用 ■ 表示
+///
+/// # 参数
+/// - `s`: 要显示的字符串
+/// - `row`/`col`: 起始行号和列号（超出 80×25 时不显示）
+/// - `color`: 颜色
+pub fn force_print_raw(s: &str, row: usize, col: usize, color: ColorCode) {
+    if row >= VGA_HEIGHT {
+        return;
+    }
+    let buffer = VGA_BUFFER_ADDR as *mut u16;
+    for (current_col, c) in (col..VGA_WIDTH).zip(s.chars()) {
+        let cell = u16::from(color.as_u8()) << 8 | u16::from(glyph_for(c));
+        // SAFETY: 0xb8000 处的文本显存在启动时已恒等映射，下标不超出 80×25
+        unsafe {
+            buffer
+                .add(row * VGA_WIDTH + current_col)
+                .write_volatile(cell)
+        };
+    }
+}
+
+/// 不等待 `WRITER` 的锁，在指定位置显示字符串，用于 panic
+///
+/// `WRITER` 空闲时和 `write_string_at` 相同（双缓冲和回滚保持一致）；
+/// 被占用时（例如在持有 WRITER 锁的代码中 panic，持有者再也不会释放）改用 `force_print_raw`
+pub fn force_write_string_at(s: &str, row: usize, col: usize, color: ColorCode) {
+    match WRITER.try_lock() {
+        Some(mut writer) => {
+            writer.write_string_at(s, row, col, color);
+        }
+        None => force_print_raw(s, row, col, color),
+    }
+}
+
+/// 不等待 `WRITER` 的锁保存屏幕内容，用于 panic
+///
+/// `WRITER` 空闲时和 `snapshot` 相同；被占用时直接 volatile 读取 0xb8000 处的显存。
+/// 这时无法得知当前行数，只读取前 25 行；双缓冲时读到的是已经显示的画面
+pub fn force_snapshot_raw(out: &mut Snapshot) {
+    if let Some(writer) = WRITER.try_lock() {
+        writer.snapshot(out);
+        return;
+    }
+    let buffer = VGA_BUFFER_ADDR as *const ScreenChar;
+    out.height = VGA_HEIGHT;
+    for (i, cell) in out.cells[..VGA_WIDTH * VGA_HEIGHT].iter_mut().enumerate() {
+        // SAFETY: 0xb8000 处的文本显存在启动时已恒等映射，下标不超出 80×25；
+        // ScreenChar 是 repr(C) 的两个字节，和显存单元的布局相同
+        *cell = unsafe { buffer.add(i).read_volatile() };
+    }
+}
+
+/// 把一整屏内容（`frame.height` 行）一次复制到 0xb8000 处的显存
+///
+/// 用一次 `copy_nonoverlapping` 复制整帧（80×25 时 4000 字节），不逐格检查下标、不加锁，
+/// 用于自己在内存中拼好整帧画面的全屏重绘；需要加锁的安全版本是 `restore`。
+/// 不经过 Writer：回滚、双缓冲的后台缓冲区和光标都不会更新，
+/// 双缓冲时下一次 `present` 会覆盖这里写入的内容
+///
+/// # Safety
+/// - 0xb8000 处的文本显存必须仍然恒等映射且可写（没有用 `set_buffer_addr` 改到别处后取消映射）
+/// - 复制期间不能有其他代码通过 `WRITER` 写屏幕，否则两边的内容会混在一起
+#[allow(dead_code)]
+pub unsafe fn blit(frame: &Snapshot) {
+    let rows = frame.rows();
+    // SAFETY: ScreenChar 是 repr(C) 的两个字节，快照的各行和显存前 `rows.len()` 行的布局相同，
+    // 最多 MAX_HEIGHT 行，不超出显存；调用者保证显存已映射且没有并发写入
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            rows.as_ptr().cast::<ScreenChar>(),
+            VGA_BUFFER_ADDR as *mut ScreenChar,
+            rows.len() * VGA_WIDTH,
+        );
+    }
+}
+
+/// 写入字符串，按单词换行（见 `Writer::write_string_wrapped`）
+///
+/// 启动时没有探测到显存时原样输出到串口
+pub fn write_string_wrapped(s: &str) {
+    if !is_present() {
+        crate::serial::write_string(s);
+        return;
+    }
+    WRITER.lock().write_string_wrapped(s);
+}
+
+/// 用指定颜色写入一段文字，不改变当前颜色（见 `Writer::write_styled`）
+#[allow(dead_code)]
+pub fn write_styled(s: &str, color: ColorCode) {
+    WRITER.lock().write_styled(s, color);
+}
+
+/// 在当前光标处原样写入一串 CP437 字形代码（不过滤控制字符和扩展字节）
+#[allow(dead_code)]
+pub fn write_raw(bytes: &[u8]) {
+    WRITER.lock().write_raw(bytes);
+}
+
+/// 在指定位置写入字符串
+///
+/// # 参数
 /// - `s`: 要写入的字符串
 /// - `row`: 行号
 /// - `col`: 列号
 /// - `color_byte`: 颜色字节（高4位背景，低4位前景）
-pub fn write_string_at(s: &str, row: usize, col: usize, color_byte: u8) {
+///
+/// # 返回
+/// 实际写入的字符数（在行尾被截断时小于字符串的字符数）
+pub fn write_string_at(s: &str, row: usize, col: usize, color_byte: u8) -> usize {
     WRITER
         .lock()
-        .write_string_at(s, row, col, ColorCode(color_byte));
+        .write_string_at(s, row, col, ColorCode::from_u8(color_byte))
+}
+
+/// 用空格清除一行中的一段区域
+///
+/// # 参数
+/// - `row`/`col`: 起始行号和列号
+/// - `width`: 清除的列数（超出屏幕的部分被裁剪）
+/// - `color`: 清除后的颜色
+#[allow(dead_code)]
+pub fn clear_region(row: usize, col: usize, width: usize, color: ColorCode) {
+    WRITER.lock().clear_region(row, col, width, color);
+}
+
+/// 绘制矩形边框（双线 CP437 字形）
+///
+/// # 参数
+/// - `top`/`left`: 左上角的行号和列号
+/// - `height`/`width`: 边框的外部高度和宽度
+/// - `color`: 颜色代码
+#[allow(dead_code)]
+pub fn draw_box(top: usize, left: usize, height: usize, width: usize, color: ColorCode) {
+    WRITER.lock().draw_box(top, left, height, width, color);
+}
+
+/// 以 `bg` 为背景绘制矩形边框，边框颜色自动选黑或白，保证可读
+#[allow(dead_code)]
+pub fn draw_box_on(top: usize, left: usize, height: usize, width: usize, bg: Color) {
+    draw_box(top, left, height, width, ColorCode::contrasting(bg));
+}
+
+/// 在屏幕中央绘制带边框的消息框（如启动画面），每行文字居中显示
+///
+/// # 参数
+/// - `lines`: 各行文字（超出屏幕的行被丢弃，过长的行被截断）
+/// - `color`: 边框和文字的颜色
+pub fn print_box_centered(lines: &[&str], color: ColorCode) {
+    WRITER.lock().print_box_centered(lines, color);
+}
+
+/// 用指定字形填充矩形区域
+///
+/// # 参数
+/// - `top`/`left`: 左上角的行号和列号
+/// - `height`/`width`: 矩形的高度和宽度
+/// - `ch`: 填充用的字形代码
+/// - `color`: 颜色代码
+#[allow(dead_code)]
+pub fn fill_rect(top: usize, left: usize, height: usize, width: usize, ch: u8, color: ColorCode) {
+    WRITER.lock().fill_rect(top, left, height, width, ch, color);
+}
+
+/// 写入状态栏（屏幕最底部一行）
+///
+/// # 参数
+/// - `s`: 状态文本
+/// - `color`: 状态栏颜色
+pub fn set_status(s: &str, color: ColorCode) {
+    WRITER.lock().set_status(s, color);
+}
+
+/// 以 `bg` 为背景写入状态栏，文字颜色自动选黑或白，保证可读
+pub fn set_status_on(s: &str, bg: Color) {
+    set_status(s, ColorCode::contrasting(bg));
 }
 
 /// 用于 print! 宏的内部打印函数
+///
+/// 忽略 `write_fmt` 的返回值：`Writer::write_str` 不会失败，
+/// 错误只可能来自参数的 `Display` 实现。这里不能 unwrap，
+/// 否则打印路径中的 panic 会进入同样要打印的 panic 处理函数
+///
+/// 启动时没有探测到显存时改为输出到串口
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
-    WRITER.lock().write_fmt(args).unwrap();
+    if !is_present() {
+        crate::serial::_print(args);
+        return;
+    }
+    let _ = WRITER.lock().write_fmt(args);
+}
+
+/// 用于 print_counted! 宏的内部函数
+///
+/// 没有 VGA 时输出到串口，返回 0（屏幕上没有写入任何字符）
+#[doc(hidden)]
+pub fn _print_counted(args: fmt::Arguments) -> usize {
+    if !is_present() {
+        crate::serial::_print(args);
+        return 0;
+    }
+    WRITER.lock().write_fmt_counted(args)
+}
+
+/// 用于 println_at! 宏的内部函数
+///
+/// 先在栈上格式化（不需要堆），再整段写到指定位置；超出缓冲区或行尾的部分被截断
+#[doc(hidden)]
+pub fn _print_at(row: usize, col: usize, color: ColorCode, args: fmt::Arguments) -> usize {
+    use core::fmt::Write;
+
+    // 一行最多 80 个字符，每个字符最多 4 字节 UTF-8
+    let mut buf = [0u8; VGA_WIDTH * 4];
+    let mut w = format::ByteMutWriter::new(&mut buf);
+    let _ = w.write_fmt(args);
+    WRITER.lock().write_string_at(w.as_str(), row, col, color)
+}
+
+/// 用于 boot_step! 宏的内部函数
+#[doc(hidden)]
+pub fn _boot_step(args: fmt::Arguments) {
+    WRITER.lock().boot_step(args);
+}
+
+/// 用于 boot_ok!/boot_fail! 宏的内部函数
+#[doc(hidden)]
+pub fn _finish_boot_step(ok: bool) {
+    WRITER.lock().finish_boot_step(ok);
 }
 
 // =============================================================================
@@ -311,3 +3157,761 @@ macro_rules! println {
     () => ($crate::print!("\n"));
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
+
+/// 和 `print!` 相同，但返回实际写到屏幕上的字符数（制表符按展开后计算，控制字符不计入）
+///
+/// 例如 `let n = print_counted!("{}", name); print!("{:1$}", "", 20 - n);` 对齐下一列
+#[macro_export]
+macro_rules! print_counted {
+    ($($arg:tt)*) => ($crate::vga::_print_counted(format_args!($($arg)*)));
+}
+
+/// 在屏幕指定位置输出格式化文本（不移动光标，不换行），返回实际写入的字符数
+///
+/// 例如 `println_at!(24, 0, ColorCode::BLACK_ON_LIGHT_GRAY, "ticks: {}", ticks)`；
+/// 超出行尾的部分被截断
+#[macro_export]
+macro_rules! println_at {
+    ($row:expr, $col:expr, $color:expr, $($arg:tt)*) => {
+        $crate::vga::_print_at($row, $col, $color, format_args!($($arg)*))
+    };
+}
+
+/// 在 VGA 屏幕上开始一个启动步骤，显示 `[  ..  ] 消息`
+#[macro_export]
+macro_rules! boot_step {
+    ($($arg:tt)*) => ($crate::vga::_boot_step(format_args!($($arg)*)));
+}
+
+/// 把最近的启动步骤标记为成功：`[  OK  ]`（绿色）
+#[macro_export]
+macro_rules! boot_ok {
+    () => {
+        $crate::vga::_finish_boot_step(true)
+    };
+}
+
+/// 把最近的启动步骤标记为失败：`[ FAIL ]`（红色）
+#[macro_export]
+macro_rules! boot_fail {
+    () => {
+        $crate::vga::_finish_boot_step(false)
+    };
+}
+
+// =============================================================================
+// 测试
+// =============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 在普通数组上创建一个全新的 Writer，上一次调用留下的内容会被清空
+    ///
+    /// 测试逐个执行，同一时间只有一个测试持有返回的 Writer
+    fn test_writer() -> Writer {
+        static mut CELLS: Cells = [[EMPTY_CELL; VGA_WIDTH]; MAX_HEIGHT];
+        static mut STORAGE: WriterStorage = WriterStorage::new();
+        // 空单元格和空存储都是全零：直接清零，不在栈上构造 160 KiB 的临时值
+        let (cells, storage) = unsafe {
+            let cells = core::ptr::addr_of_mut!(CELLS);
+            let storage = core::ptr::addr_of_mut!(STORAGE);
+            cells.write_bytes(0, 1);
+            storage.write_bytes(0, 1);
+            (&mut *cells, &mut *storage)
+        };
+        Writer::new(Buffer::from_cells(cells), storage, COLOR)
+    }
+
+    /// 8 列 × 4 行的 Writer（最后一行是状态栏），用来检查换行、制表位和滚动的边界
+    fn small_writer() -> Writer<8, 4> {
+        static mut CELLS: Cells<8, 4> = [[EMPTY_CELL; 8]; 4];
+        static mut STORAGE: WriterStorage<8, 4> = WriterStorage::new();
+        let (cells, storage) = unsafe {
+            let cells = core::ptr::addr_of_mut!(CELLS);
+            let storage = core::ptr::addr_of_mut!(STORAGE);
+            cells.write_bytes(0, 1);
+            storage.write_bytes(0, 1);
+            (&mut *cells, &mut *storage)
+        };
+        Writer::new(Buffer::from_cells(cells), storage, COLOR)
+    }
+
+    /// 小 Writer 第 `row` 行的字形，没有写过的单元格当作空格
+    fn small_line(writer: &Writer<8, 4>, row: usize) -> [u8; 8] {
+        core::array::from_fn(|col| match writer.char_at(row, col).ascii_character {
+            0 => b' ',
+            glyph => glyph,
+        })
+    }
+
+    const COLOR: ColorCode = ColorCode::WHITE_ON_BLACK;
+
+    #[test_case]
+    fn write_raw_keeps_control_and_extended_bytes() {
+        let mut writer = test_writer();
+        writer.set_cursor(0, VGA_WIDTH - 2);
+        writer.write_raw(&[b'\n', 0xDB, 0x01]);
+        assert_eq!(writer.char_at(0, VGA_WIDTH - 2).ascii_character, b'\n');
+        assert_eq!(writer.char_at(0, VGA_WIDTH - 1).ascii_character, 0xDB);
+        // 行满后自动换行，写到下一行行首
+        assert_eq!(writer.cursor_position().1, 1);
+        let row = writer.cursor_position().0;
+        assert_eq!(writer.char_at(row, 0).ascii_character, 0x01);
+    }
+
+    #[test_case]
+    fn set_buffer_addr_rejects_null_and_misaligned() {
+        assert_eq!(unsafe { set_buffer_addr(0) }, Err(BufferAddrError::Null));
+        assert_eq!(
+            unsafe { set_buffer_addr(VGA_BUFFER_ADDR + 2) },
+            Err(BufferAddrError::Misaligned)
+        );
+        // 重新指向原来的恒等映射地址总是有效的
+        assert_eq!(unsafe { set_buffer_addr(VGA_BUFFER_ADDR) }, Ok(()));
+    }
+
+    #[test_case]
+    fn boot_step_status_is_rewritten_in_place() {
+        let mut writer = test_writer();
+        writer.boot_step(format_args!("Testing boot steps"));
+        let row = writer.boot_step_row.expect("boot step row recorded");
+        assert_eq!(writer.char_at(row, 3).ascii_character, b'.');
+        assert_eq!(writer.char_at(row, 9).ascii_character, b'T');
+
+        writer.finish_boot_step(false);
+        assert_eq!(writer.char_at(row, 2).ascii_character, b'F');
+        assert_eq!(
+            writer.char_at(row, 2).color_code,
+            ColorCode::LIGHT_RED_ON_BLACK
+        );
+        assert_eq!(writer.boot_step_row, None);
+    }
+
+    #[test_case]
+    fn truncate_mode_drops_overflow_and_marks_last_column() {
+        let mut writer = test_writer();
+        writer.set_wrap_mode(WrapMode::Truncate);
+        writer.set_cursor(4, 0);
+        for _ in 0..VGA_WIDTH + 5 {
+            writer.write_string("x");
+        }
+        let (row, col) = writer.cursor_position();
+        let last = writer.char_at(4, VGA_WIDTH - 1).ascii_character;
+
+        assert_eq!((row, col), (4, VGA_WIDTH));
+        assert_eq!(last, TRUNCATION_MARKER);
+    }
+
+    #[test_case]
+    fn extend_mode_scrolls_wide_lines_into_view() {
+        let mut writer = test_writer();
+        writer.set_wrap_mode(WrapMode::Extend);
+        writer.set_cursor(4, 0);
+        for i in 0..VGA_WIDTH + 20 {
+            writer.write_byte(b'a' + (i % 26) as u8);
+        }
+        let position = writer.cursor_position();
+
+        writer.scroll_horizontal(10);
+        let scrolled = writer.screen_read(4, VGA_WIDTH - 1).ascii_character;
+        let saved = writer.char_at(4, 0).ascii_character;
+        writer.scroll_horizontal(-10);
+        let restored = writer.screen_read(4, 0).ascii_character;
+
+        assert_eq!(position, (4, VGA_WIDTH + 20));
+        assert_eq!(scrolled, b'a' + ((VGA_WIDTH + 9) % 26) as u8);
+        assert_eq!(saved, b'a');
+        assert_eq!(restored, b'a');
+    }
+
+    #[test_case]
+    fn counted_write_skips_control_characters() {
+        let mut writer = test_writer();
+        writer.set_cursor(5, 0);
+        let count = writer.write_fmt_counted(format_args!("a\tb{}\r\n", 42));
+
+        // a + 7 个展开的空格 + b + 42
+        assert_eq!(count, 1 + 7 + 1 + 2);
+    }
+
+    #[test_case]
+    fn snapshot_restore_round_trip() {
+        let mut writer = test_writer();
+        writer.write_string_at("snapshot", 3, 10, COLOR);
+        let mut snap = EMPTY_SNAPSHOT;
+        writer.snapshot(&mut snap);
+        assert_eq!(snap.height, writer.height);
+        assert_eq!(snap.rows()[3][10].ascii_character, b's');
+
+        writer.clear_region(3, 10, 8, COLOR);
+        assert_eq!(writer.char_at(3, 10).ascii_character, b' ');
+        writer.restore(&snap);
+        assert_eq!(writer.char_at(3, 10).ascii_character, b's');
+        assert_eq!(writer.char_at(3, 17).ascii_character, b't');
+    }
+
+    #[test_case]
+    fn snapshot_keeps_rows_below_25_in_80x50_mode() {
+        let mut writer = test_writer();
+        writer.set_height(MAX_HEIGHT);
+        writer.write_string_at("top", 0, 0, COLOR);
+        writer.write_string_at("bottom", 40, 0, COLOR);
+        let mut snap = EMPTY_SNAPSHOT;
+        writer.snapshot(&mut snap);
+        assert_eq!(snap.height, MAX_HEIGHT);
+        assert_eq!(snap.rows().len(), MAX_HEIGHT);
+        assert_eq!(snap.rows()[40][0].ascii_character, b'b');
+
+        // 切回 80×25 后恢复：只写回当前存在的行，不越界
+        writer.set_height(VGA_HEIGHT);
+        writer.clear_row(0);
+        writer.restore(&snap);
+        assert_eq!(writer.char_at(0, 0).ascii_character, b't');
+    }
+
+    #[test_case]
+    fn write_string_wrapped_moves_words_to_next_line() {
+        let mut writer = test_writer();
+        writer.clear_content();
+        let (start, _) = writer.cursor_position();
+        let long = [b'x'; VGA_WIDTH + 5];
+        let long = core::str::from_utf8(&long).unwrap();
+
+        writer.write_string("a".repeat(VGA_WIDTH - 3).as_str());
+        writer.write_string_wrapped(" word ");
+        writer.write_string_wrapped(long);
+        let (row, col) = writer.cursor_position();
+
+        assert_eq!(writer.char_at(start, VGA_WIDTH - 3).ascii_character, b' ');
+        assert_eq!(writer.char_at(start + 1, 0).ascii_character, b'w');
+        assert_eq!(writer.char_at(start + 1, 5).ascii_character, b'x');
+        assert_eq!((row, col), (start + 2, 10));
+    }
+
+    #[test_case]
+    fn probe_detects_vga_memory() {
+        let cell = (VGA_BUFFER_ADDR + VGA_PROBE_OFFSET) as *const u16;
+        let before = unsafe { cell.read_volatile() };
+        assert!(probe());
+        assert_eq!(unsafe { cell.read_volatile() }, before);
+    }
+
+    #[test_case]
+    fn clear_content_keeps_status_bar() {
+        let mut writer = test_writer();
+        writer.set_status("status", COLOR);
+        let status_row = writer.status_row();
+        writer.write_string("content");
+        writer.clear_content();
+
+        let top = writer.scroll_top;
+        assert_eq!(writer.char_at(status_row, 0).ascii_character, b's');
+        assert_eq!(writer.char_at(top, 0).ascii_character, b' ');
+        assert_eq!(writer.cursor_position(), (top, 0));
+    }
+
+    #[test_case]
+    fn default_color_paints_cleared_rows() {
+        let theme = ColorCode::LIGHT_GREEN_ON_BLACK;
+        let mut writer = test_writer();
+        writer.set_default_color(theme);
+        writer.clear_row(3);
+        let cell = writer.char_at(3, 0);
+        writer.set_default_color(default_color());
+
+        assert_eq!(cell.color_code, theme);
+        assert_eq!(writer.color(), default_color());
+    }
+
+    #[test_case]
+    fn const_theme_sets_default_color() {
+        const DIMMED: Theme = Theme::SOLARIZED_ISH.with_background(Color::Black);
+        assert_eq!(DIMMED.error, ColorCode::LIGHT_RED_ON_BLACK);
+
+        let mut writer = test_writer();
+        writer.set_theme(&Theme::SOLARIZED_ISH);
+        let (color, theme) = (writer.default_color(), writer.theme());
+        writer.set_theme(&Theme::DEFAULT);
+
+        assert_eq!(color, ColorCode::new(Color::LightGray, Color::Blue));
+        assert_eq!(theme.prompt.background(), Color::Blue);
+        assert_eq!(writer.default_color(), default_color());
+    }
+
+    #[test_case]
+    fn write_hex_and_dec_format_without_fmt() {
+        let mut writer = test_writer();
+        writer.write_byte(b'\n');
+        let (row, _) = writer.cursor_position();
+        writer.write_hex(0xbeef, 8);
+        writer.write_byte(b' ');
+        writer.write_hex(u64::MAX, 4);
+        writer.write_byte(b' ');
+        writer.write_dec(0);
+        writer.write_byte(b' ');
+        writer.write_dec(18_446_744_073_709_551_615);
+
+        let expected = b"0000beef ffffffffffffffff 0 18446744073709551615";
+        for (col, &byte) in expected.iter().enumerate() {
+            assert_eq!(writer.char_at(row, col).ascii_character, byte);
+        }
+    }
+
+    #[test_case]
+    fn println_at_formats_and_truncates() {
+        assert_eq!(crate::println_at!(5, 0, COLOR, "ticks: {}", 42), 9);
+        assert_eq!(WRITER.lock().char_at(5, 7).ascii_character, b'4');
+        assert_eq!(
+            crate::println_at!(5, VGA_WIDTH - 3, COLOR, "{:>10}", 12345),
+            3
+        );
+    }
+
+    #[test_case]
+    fn ansi_codes_round_trip() {
+        assert_eq!(Color::from_ansi_fg(31), Some(Color::Red));
+        assert_eq!(Color::from_ansi_fg(91), Some(Color::LightRed));
+        assert_eq!(Color::from_ansi_bg(44), Some(Color::Blue));
+        assert_eq!(Color::from_ansi_bg(107), Some(Color::White));
+        assert_eq!(Color::from_ansi_fg(38), None);
+        assert_eq!(Color::from_ansi_bg(37), None);
+        for color in Color::ALL {
+            assert_eq!(Color::from_ansi_fg(color.to_ansi_fg()), Some(color));
+        }
+    }
+
+    #[test_case]
+    fn hardware_cursor_follows_writes() {
+        let mut writer = WRITER.lock();
+        writer.set_hw_cursor_enabled(true);
+        writer.set_cursor(3, 0);
+        writer.write_string("12345");
+        let software = writer.cursor_position();
+        writer.set_hw_cursor_enabled(false);
+        drop(writer);
+
+        assert_eq!(software, (3, 5));
+        assert_eq!(hardware_cursor_position(), software);
+    }
+
+    #[test_case]
+    fn luminance_is_computed_from_palette() {
+        assert_eq!(
+            Color::LUMINANCE,
+            [
+                0, 19, 100, 119, 51, 70, 101, 170, 85, 104, 185, 204, 136, 155, 236, 255
+            ]
+        );
+        assert_eq!(Color::LUMINANCE[Color::LightCyan as usize], 204);
+        assert_eq!(Color::LUMINANCE[Color::LightBlue as usize], 104);
+    }
+
+    #[test_case]
+    fn readable_on_picks_black_for_light_backgrounds() {
+        assert_eq!(Color::readable_on(Color::Yellow), Color::Black);
+        assert_eq!(Color::readable_on(Color::LightGray), Color::Black);
+        assert_eq!(Color::readable_on(Color::Blue), Color::White);
+        assert_eq!(Color::readable_on(Color::Black), Color::White);
+        for bg in Color::ALL {
+            assert_ne!(Color::readable_on(bg), bg);
+        }
+        assert_eq!(
+            ColorCode::contrasting(Color::LightGray),
+            ColorCode::BLACK_ON_LIGHT_GRAY
+        );
+    }
+
+    #[test_case]
+    fn color_code_consts_match_attribute_bytes() {
+        assert_eq!(ColorCode::WHITE_ON_BLACK.as_u8(), 0x0f);
+        assert_eq!(ColorCode::WHITE_ON_RED.as_u8(), 0x4f);
+        assert_eq!(ColorCode::LIGHT_RED_ON_BLACK.as_u8(), 0x0c);
+        assert_eq!(ColorCode::BLACK_ON_LIGHT_GRAY.as_u8(), 0x70);
+    }
+
+    #[test_case]
+    fn with_blink_toggles_only_high_bit() {
+        let blinking = ColorCode::WHITE_ON_BLUE.with_blink(true);
+        assert_eq!(blinking.as_u8(), 0x9f);
+        assert_eq!(blinking.with_blink(false), ColorCode::WHITE_ON_BLUE);
+    }
+
+    #[test_case]
+    fn write_string_at_out_of_range_writes_nothing() {
+        let mut writer = test_writer();
+        assert_eq!(writer.write_string_at("x", MAX_HEIGHT, 0, COLOR), 0);
+        assert_eq!(writer.write_string_at("x", usize::MAX, 0, COLOR), 0);
+        assert_eq!(writer.write_string_at("x", 0, VGA_WIDTH, COLOR), 0);
+        assert_eq!(writer.write_string_at("x", 0, usize::MAX, COLOR), 0);
+    }
+
+    #[test_case]
+    fn write_string_at_truncates_at_row_end() {
+        let mut writer = test_writer();
+        assert_eq!(writer.write_string_at("abcdef", 0, VGA_WIDTH - 2, COLOR), 2);
+    }
+
+    #[test_case]
+    fn rectangles_out_of_range_do_not_panic() {
+        let mut writer = test_writer();
+        writer.fill_rect(usize::MAX, usize::MAX, usize::MAX, usize::MAX, b'#', COLOR);
+        writer.fill_rect(MAX_HEIGHT, 0, 5, 5, b'#', COLOR);
+        writer.draw_box(usize::MAX, 0, usize::MAX, usize::MAX, COLOR);
+        writer.draw_box(0, VGA_WIDTH - 1, 10, 10, COLOR);
+        writer.clear_region(usize::MAX, usize::MAX, usize::MAX, COLOR);
+        writer.clear_row(MAX_HEIGHT);
+        writer.clear_row(usize::MAX);
+    }
+
+    #[test_case]
+    fn cursor_is_clamped_to_scroll_region() {
+        let mut writer = test_writer();
+        writer.set_cursor(usize::MAX, usize::MAX);
+        assert_eq!(
+            writer.cursor_position(),
+            (writer.scroll_bottom - 1, VGA_WIDTH - 1)
+        );
+        // 状态栏所在的最后一行不在滚动区域内
+        assert_ne!(writer.cursor_position().0, writer.status_row());
+    }
+
+    #[test_case]
+    fn cursor_above_scroll_region_moves_to_its_top() {
+        let mut writer = test_writer();
+        writer.set_scroll_region(2, 5);
+        writer.set_cursor(0, 0);
+        assert_eq!(writer.cursor_position(), (2, 0));
+        writer.set_cursor(7, 0);
+        assert_eq!(writer.cursor_position(), (4, 0));
+    }
+
+    #[test_case]
+    fn print_box_centered_sizes_box_to_content() {
+        let mut writer = test_writer();
+        writer.print_box_centered(&["Hello", "abc"], COLOR);
+        // 内宽 5，框宽 9，框高 4：居中后左上角在 (height - 4) / 2 行、(80 - 9) / 2 列
+        let (top, left) = ((writer.height() - 4) / 2, (VGA_WIDTH - 9) / 2);
+        assert_eq!(writer.char_at(top, left).ascii_character, BOX_TOP_LEFT);
+        assert_eq!(
+            writer.char_at(top + 3, left + 8).ascii_character,
+            BOX_BOTTOM_RIGHT
+        );
+        assert_eq!(writer.char_at(top + 1, left + 2).ascii_character, b'H');
+        assert_eq!(writer.char_at(top + 2, left + 3).ascii_character, b'a');
+
+        // 过宽的内容被截断到屏幕宽度，边框仍然完整
+        let long = [b'x'; VGA_WIDTH + 10];
+        let long = core::str::from_utf8(&long).unwrap();
+        writer.print_box_centered(&[long], COLOR);
+        let top = (writer.height() - 3) / 2;
+        assert_eq!(writer.char_at(top, 0).ascii_character, BOX_TOP_LEFT);
+        assert_eq!(
+            writer.char_at(top + 1, VGA_WIDTH - 1).ascii_character,
+            BOX_VERTICAL
+        );
+        assert_eq!(writer.char_at(top + 1, VGA_WIDTH - 3).ascii_character, b'x');
+    }
+
+    #[test_case]
+    fn writer_runs_on_plain_array() {
+        let mut writer = test_writer();
+        writer.write_string("hi\nthere");
+
+        assert_eq!(writer.cursor_position(), (1, 5));
+        assert_eq!(writer.char_at(0, 1).ascii_character, b'i');
+        assert_eq!(writer.char_at(1, 4).ascii_character, b'e');
+        assert_eq!(writer.char_at(1, 4).color_code, COLOR);
+    }
+
+    #[test_case]
+    fn small_writer_wraps_long_lines_at_its_width() {
+        let mut writer = small_writer();
+        writer.set_scroll_region(0, 4);
+        writer.write_string("abcdefghij");
+
+        assert_eq!(&small_line(&writer, 0), b"abcdefgh");
+        assert_eq!(&small_line(&writer, 1), b"ij      ");
+        assert_eq!(writer.cursor_position(), (1, 2));
+    }
+
+    #[test_case]
+    fn small_writer_tab_stops_at_line_end() {
+        let mut writer = small_writer();
+        writer.write_string("ab\tc");
+        // 制表位落在行尾：光标停在行满状态，下一个字符换到下一行
+        assert_eq!(&small_line(&writer, 0), b"ab      ");
+        assert_eq!(&small_line(&writer, 1), b"c       ");
+    }
+
+    #[test_case]
+    fn small_writer_extends_lines_to_logical_width() {
+        let mut writer = small_writer();
+        writer.set_wrap_mode(WrapMode::Extend);
+        for i in 0..LOGICAL_WIDTH - 1 {
+            writer.write_byte(b'a' + (i % 26) as u8);
+        }
+        // 8 列的屏幕上逻辑行同样是 LOGICAL_WIDTH 列，不会提前换行
+        assert_eq!(writer.cursor_position(), (0, LOGICAL_WIDTH - 1));
+
+        writer.scroll_horizontal(isize::MAX);
+        let line = small_line(&writer, 0);
+        let expected: [u8; 7] =
+            core::array::from_fn(|i| b'a' + ((LOGICAL_WIDTH - 8 + i) % 26) as u8);
+        assert_eq!(&line[..7], &expected);
+    }
+
+    #[test_case]
+    fn small_writer_scrolling_keeps_status_row() {
+        let mut writer = small_writer();
+        writer.set_status("status", COLOR);
+        writer.write_string("one\ntwo\nthree\nfour");
+
+        assert_eq!(&small_line(&writer, 0), b"two     ");
+        assert_eq!(&small_line(&writer, 1), b"three   ");
+        assert_eq!(&small_line(&writer, 2), b"four    ");
+        assert_eq!(&small_line(&writer, 3), b"status  ");
+        // 滚出顶部的行进入回滚缓冲区
+        writer.scroll_back(1);
+        assert_eq!(&small_line(&writer, 0), b"one     ");
+        writer.scroll_forward(1);
+        assert_eq!(&small_line(&writer, 0), b"two     ");
+    }
+
+    #[test_case]
+    fn block_scroll_matches_cell_by_cell_copy() {
+        let mut writer = test_writer();
+        for row in 0..VGA_HEIGHT {
+            for col in 0..VGA_WIDTH {
+                let ch = ScreenChar {
+                    ascii_character: b'!' + ((row * 7 + col) % 90) as u8,
+                    color_code: ColorCode((row + col) as u8),
+                };
+                writer.put_char(row, col, ch);
+            }
+        }
+
+        // 旧的做法：逐个单元格把每一行复制到上一行
+        let mut expected = [[EMPTY_CELL; VGA_WIDTH]; VGA_HEIGHT];
+        for (row, line) in expected.iter_mut().enumerate() {
+            for (col, cell) in line.iter_mut().enumerate() {
+                *cell = writer.char_at(row, col);
+            }
+        }
+        for row in writer.scroll_top + 1..writer.scroll_bottom {
+            expected[row - 1] = expected[row];
+        }
+
+        writer.scroll();
+        for (row, line) in expected.iter().enumerate().take(writer.scroll_bottom - 1) {
+            for (col, &cell) in line.iter().enumerate() {
+                assert_eq!(writer.char_at(row, col), cell);
+            }
+        }
+        // 状态栏不动，区域最后一行被清空
+        let status = writer.status_row();
+        assert_eq!(writer.char_at(status, 3), expected[status][3]);
+        assert_eq!(writer.char_at(status - 1, 3).ascii_character, b' ');
+    }
+
+    #[test_case]
+    fn window_wraps_and_scrolls_inside_its_bounds() {
+        let mut writer = test_writer();
+        writer.write_string_at("outside", 3, 10, COLOR);
+
+        // 2 行 × 4 列的窗口：第三行输出时窗口滚动一次
+        let mut window = writer.window(2, 10, 2, 4, ColorCode::LIGHT_GREEN_ON_BLACK);
+        writer.write_window(&mut window, "abcdefgh\nxy");
+
+        assert_eq!(window.cursor_position(), (1, 2));
+        assert_eq!(writer.char_at(2, 10).ascii_character, b'e');
+        assert_eq!(writer.char_at(3, 10).ascii_character, b'x');
+        assert_eq!(writer.char_at(3, 12).ascii_character, b' ');
+        // 窗口右边的字符和 Writer 的光标都不受影响
+        assert_eq!(writer.char_at(3, 14).ascii_character, b'i');
+        assert_eq!(writer.cursor_position(), (0, 0));
+    }
+
+    #[test_case]
+    fn window_is_clamped_to_current_height() {
+        let mut writer = test_writer();
+        let window = writer.window(40, 0, 20, 5, COLOR);
+        assert_eq!((window.top, window.height), (VGA_HEIGHT - 1, 1));
+
+        // 80×50 模式下窗口可以放在第 25 行以下
+        writer.set_height(MAX_HEIGHT);
+        let window = writer.window(40, 0, 20, 5, COLOR);
+        assert_eq!((window.top, window.height), (40, MAX_HEIGHT - 40));
+    }
+
+    #[test_case]
+    fn progress_bar_draws_partial_cells_and_label() {
+        let mut writer = test_writer();
+        writer.write_string("x");
+
+        // 10 格进度条 + 5 列标签：37% = 14/40 级，即 3 个整格加半格
+        let mut bar = ProgressBar::new(4, 2, 15, COLOR);
+        bar.percent = 37;
+        writer.draw_progress_bar(&mut bar);
+
+        let row: [u8; 15] = core::array::from_fn(|i| writer.char_at(4, 2 + i).ascii_character);
+        assert_eq!(&row[..5], &[0xDB, 0xDB, 0xDB, 0xB1, b' ']);
+        assert_eq!(&row[10..], b"  37%");
+        assert_eq!(writer.cursor_position(), (0, 1));
+
+        // 没有变化的单元格不重画：手工改掉的第一格保持原样
+        writer.write_string_at("#", 4, 2, COLOR);
+        bar.percent = 100;
+        writer.draw_progress_bar(&mut bar);
+        assert_eq!(writer.char_at(4, 2).ascii_character, b'#');
+        assert_eq!(writer.char_at(4, 11).ascii_character, 0xDB);
+        assert_eq!(writer.char_at(4, 13).ascii_character, b'1');
+    }
+
+    #[test_case]
+    fn oversized_progress_bar_is_clamped_to_screen() {
+        let mut writer = test_writer();
+        let mut bar = ProgressBar::new(4, 0, usize::MAX, COLOR);
+        assert_eq!((bar.left(), bar.width()), (0, VGA_WIDTH));
+
+        // 靠右的进度条整体左移，至少留出一格进度条和百分比标签
+        let mut right = ProgressBar::new(5, VGA_WIDTH, 200, COLOR);
+        assert_eq!(right.left(), VGA_WIDTH - PROGRESS_LABEL_WIDTH - 1);
+        assert_eq!(right.width(), PROGRESS_LABEL_WIDTH + 1);
+
+        bar.percent = 100;
+        right.percent = 100;
+        writer.draw_progress_bar(&mut bar);
+        writer.draw_progress_bar(&mut right);
+        assert_eq!(writer.char_at(4, VGA_WIDTH - 1).ascii_character, b'%');
+        assert_eq!(writer.char_at(5, VGA_WIDTH - 1).ascii_character, b'%');
+    }
+
+    #[test_case]
+    fn styled_run_wraps_and_restores_color() {
+        let mut writer = test_writer();
+        let red = ColorCode::WHITE_ON_RED;
+        writer.set_cursor(0, VGA_WIDTH - 2);
+
+        // 这段文字在行尾换行，后半段仍然是红色
+        writer.write_styled("xyz", red);
+        writer.write_string("n");
+        assert_eq!(writer.color(), COLOR);
+        assert_eq!(writer.char_at(0, VGA_WIDTH - 2).color_code, red);
+        assert_eq!(writer.char_at(0, VGA_WIDTH - 1).color_code, red);
+        assert_eq!(
+            writer.char_at(1, 0),
+            ScreenChar {
+                ascii_character: b'z',
+                color_code: red
+            }
+        );
+        assert_eq!(
+            writer.char_at(1, 1),
+            ScreenChar {
+                ascii_character: b'n',
+                color_code: COLOR
+            }
+        );
+    }
+
+    #[test_case]
+    fn styled_run_scrolling_keeps_blank_lines_in_base_color() {
+        let mut writer = test_writer();
+        let red = ColorCode::WHITE_ON_RED;
+        let last = writer.scroll_bottom - 1;
+        writer.set_cursor(last, VGA_WIDTH - 2);
+
+        // 在最后一行行尾换行并滚动：新行的字形是红色，其余空白保持当前颜色
+        writer.write_styled("xyz", red);
+        assert_eq!(writer.char_at(last - 1, VGA_WIDTH - 1).color_code, red);
+        assert_eq!(
+            writer.char_at(last, 0),
+            ScreenChar {
+                ascii_character: b'z',
+                color_code: red
+            }
+        );
+        assert_eq!(
+            writer.char_at(last, 1),
+            ScreenChar {
+                ascii_character: b' ',
+                color_code: COLOR
+            }
+        );
+        assert_eq!(writer.char_at(last, VGA_WIDTH - 1).color_code, COLOR);
+    }
+
+    #[test_case]
+    fn erase_to_end_keeps_cursor() {
+        let mut writer = test_writer();
+        writer.write_string("first line\nsecond line\nthird");
+        writer.set_cursor(1, 3);
+        let red = ColorCode::WHITE_ON_RED;
+        writer.set_color(red);
+
+        writer.clear_to_eol();
+        assert_eq!(writer.cursor_position(), (1, 3));
+        assert_eq!(writer.char_at(1, 2).ascii_character, b'c');
+        assert_eq!(
+            writer.char_at(1, 3),
+            ScreenChar {
+                ascii_character: b' ',
+                color_code: red
+            }
+        );
+        assert_eq!(writer.char_at(2, 0).ascii_character, b't');
+
+        writer.clear_to_eos();
+        assert_eq!(writer.cursor_position(), (1, 3));
+        assert_eq!(writer.char_at(0, 0).ascii_character, b'f');
+        assert_eq!(
+            writer.char_at(2, 0),
+            ScreenChar {
+                ascii_character: b' ',
+                color_code: red
+            }
+        );
+    }
+
+    #[test_case]
+    fn typed_cells_round_trip() {
+        let cell = Cell {
+            glyph: 0xDB,
+            fg: Color::Yellow,
+            bg: Color::LightBlue,
+        };
+        assert_eq!(Cell::from(ScreenChar::from(cell)), cell);
+
+        let mut writer = test_writer();
+        let (row, col) = (writer.scroll_bottom - 1, VGA_WIDTH - 1);
+        writer.put_cell(row, col, cell);
+
+        assert_eq!(writer.get_cell(row, col), Some(cell));
+        assert_eq!(writer.get_cell(row, VGA_WIDTH), None);
+    }
+
+    #[test_case]
+    fn newline_policy_presents_each_line() {
+        let mut writer = test_writer();
+        writer.set_double_buffered(true);
+        writer.show_cursor(false);
+        writer.set_flush_policy(FlushPolicy::OnNewline);
+
+        writer.write_string("ab");
+        let before_newline = writer.buffer.chars[0][0].read().ascii_character;
+        writer.write_string("\n");
+        let after_newline = writer.buffer.chars[0][0].read().ascii_character;
+        writer.set_double_buffered(false);
+
+        assert_eq!(before_newline, 0);
+        assert_eq!(after_newline, b'a');
+    }
+
+    #[test_case]
+    fn centered_column_does_not_underflow() {
+        assert_eq!(centered_column(VGA_WIDTH + 10), 0);
+        assert_eq!(centered_column(VGA_WIDTH - 10), 5);
+    }
+}
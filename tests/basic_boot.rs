@@ -0,0 +1,48 @@
+//! 集成测试：只初始化串口就运行测试，确认内核库在最小的启动环境下也能正常输出
+//!
+//! 每个 `tests/` 下的文件都是一个独立的内核二进制，有自己的入口和 panic 处理函数
+
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(gwen_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::BootInfo;
+use core::panic::PanicInfo;
+use gwen_os::{println, serial, serial_assert, serial_assert_eq, serial_println, vga};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _start(_boot_info: &'static BootInfo) -> ! {
+    let _ = serial::init();
+    test_main();
+    gwen_os::cpu::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    gwen_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn serial_println_works() {
+    serial_println!("serial output from an integration test");
+}
+
+#[test_case]
+fn println_works() {
+    println!("VGA output from an integration test");
+}
+
+#[test_case]
+fn serial_asserts_pass_silently() {
+    serial_assert!(serial::is_initialized());
+
+    // 写完一行后光标停在下一行行首，上一行就是刚写的内容
+    println!("ok");
+    let (row, col) = vga::cursor_position();
+    serial_assert_eq!(col, 0);
+    serial_assert!(row > 0);
+    serial_assert_eq!(vga::get_cell(row - 1, 0).map(|cell| cell.glyph), Some(b'o'));
+    serial_assert_eq!(vga::get_cell(row - 1, 1).map(|cell| cell.glyph), Some(b'k'));
+}
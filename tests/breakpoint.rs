@@ -0,0 +1,45 @@
+//! 断点异常集成测试
+//!
+//! 加载内核的 IDT 后执行 `int3`，断点处理函数返回后测试继续执行，
+//! 串口上依次出现测试名、处理函数的输出和 `[ok]`，最后以成功退出 QEMU
+
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(gwen_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+use gwen_os::{cpu, interrupts, serial};
+
+/// 断点异常的向量号
+const BREAKPOINT_VECTOR: u8 = 3;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _start() -> ! {
+    let _ = serial::init();
+    interrupts::init();
+    test_main();
+    cpu::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    gwen_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn execution_continues_after_breakpoint() {
+    let before = interrupts::count(BREAKPOINT_VECTOR);
+    x86_64::instructions::interrupts::int3();
+    assert_eq!(interrupts::count(BREAKPOINT_VECTOR), before + 1);
+}
+
+#[test_case]
+fn repeated_breakpoints_are_each_handled() {
+    let before = interrupts::count(BREAKPOINT_VECTOR);
+    for _ in 0..3 {
+        x86_64::instructions::interrupts::int3();
+    }
+    assert_eq!(interrupts::count(BREAKPOINT_VECTOR), before + 3);
+}
@@ -0,0 +1,41 @@
+//! 双重故障集成测试
+//!
+//! 内核 IDT 没有注册缺页和一般保护异常的处理函数：
+//! 写一个没有映射的地址触发缺页，CPU 找不到处理函数，升级为双重故障。
+//! 内核的双重故障处理函数打印完信息后以成功退出 QEMU；
+//! 如果处理函数没有运行（三重故障复位或者测试返回），测试都会失败
+
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(gwen_os::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+use gwen_os::qemu::{self, QemuExitCode};
+use gwen_os::{cpu, interrupts, serial, serial_println};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _start() -> ! {
+    let _ = serial::init();
+    interrupts::init();
+    interrupts::set_double_fault_exit(QemuExitCode::Success);
+    test_main();
+    cpu::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    gwen_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn unhandled_page_fault_reaches_double_fault_handler() {
+    unsafe {
+        core::ptr::write_volatile(0xdead_beef as *mut u8, 42);
+    }
+
+    // 不应到达这里：处理函数会直接退出 QEMU
+    serial_println!("[failed: execution continued after double fault]");
+    qemu::exit_qemu(QemuExitCode::Failed);
+}
@@ -0,0 +1,49 @@
+//! 集成测试：确认堆耗尽时会调用 `alloc_error_handler`，而不是返回一块越界的内存
+//!
+//! 处理函数不会返回，所以这个二进制只有一个测试；
+//! 运行器在测试返回（分配竟然成功）时以失败退出
+
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use bootloader::BootInfo;
+use core::panic::PanicInfo;
+use gwen_os::allocator::HEAP_SIZE;
+use gwen_os::qemu::{self, QemuExitCode};
+use gwen_os::{Testable, cpu, serial_println};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    gwen_os::init(boot_info);
+    test_main();
+    cpu::hlt_loop();
+}
+
+/// 运行测试：处理函数会直接以成功退出 QEMU，测试正常返回说明分配没有失败
+pub fn test_runner(tests: &[&dyn Testable]) {
+    serial_println!("Running {} tests", tests.len());
+    for test in tests {
+        test.run();
+        serial_println!("[allocation past the heap succeeded]");
+        qemu::exit_qemu(QemuExitCode::Failed);
+    }
+    qemu::exit_qemu(QemuExitCode::Success);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    gwen_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn allocating_past_heap_size_runs_handler() {
+    gwen_os::set_expected_panic();
+    let buffer: Vec<u8> = Vec::with_capacity(HEAP_SIZE + 1);
+    core::hint::black_box(&buffer);
+}
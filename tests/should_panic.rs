@@ -0,0 +1,44 @@
+//! 集成测试：确认 `set_expected_panic` 之后的 panic 被记为测试通过
+//!
+//! panic 后无法回到测试运行器，所以这个二进制只有一个测试；
+//! 运行器在测试返回（没有 panic）时以失败退出
+
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use bootloader::BootInfo;
+use core::panic::PanicInfo;
+use gwen_os::qemu::{self, QemuExitCode};
+use gwen_os::{Testable, cpu, serial, serial_println};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn _start(_boot_info: &'static BootInfo) -> ! {
+    let _ = serial::init();
+    test_main();
+    cpu::hlt_loop();
+}
+
+/// 运行测试：预期的 panic 会直接以成功退出 QEMU，测试正常返回说明它没有 panic
+pub fn test_runner(tests: &[&dyn Testable]) {
+    serial_println!("Running {} tests", tests.len());
+    for test in tests {
+        test.run();
+        serial_println!("[test did not panic]");
+        qemu::exit_qemu(QemuExitCode::Failed);
+    }
+    qemu::exit_qemu(QemuExitCode::Success);
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    gwen_os::test_panic_handler(info)
+}
+
+#[test_case]
+fn failed_assertion_panics() {
+    gwen_os::set_expected_panic();
+    assert_eq!(0, 1);
+}